@@ -0,0 +1,159 @@
+use log::info;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::ipc::{CommandArg, CommandItem, InvokeBody, InvokeError};
+use tauri::{Manager, Runtime, State, Webview, WindowEvent};
+
+use crate::db::{ApiInfo, Database};
+use crate::error::CommandError;
+use crate::http_client::ApiError;
+
+/// Invoke argument that picks the firewall a command talks to
+const PROFILE_ID_ARG: &str = "profileId";
+
+/// Profile each window is working with, so two windows can show two firewalls
+///
+/// Kept in memory only; a window without an entry uses the default profile.
+pub struct ActiveProfiles {
+    by_window: Mutex<HashMap<String, i64>>,
+}
+
+impl ActiveProfiles {
+    pub fn new() -> Self {
+        Self {
+            by_window: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, window: &str) -> Option<i64> {
+        self.by_window.lock().unwrap().get(window).copied()
+    }
+
+    pub fn set(&self, window: &str, profile_id: Option<i64>) {
+        let mut by_window = self.by_window.lock().unwrap();
+        match profile_id {
+            Some(profile_id) => by_window.insert(window.to_string(), profile_id),
+            None => by_window.remove(window),
+        };
+    }
+
+    /// Sends windows that were using a deleted profile back to the default one
+    pub fn forget_profile(&self, profile_id: i64) {
+        self.by_window
+            .lock()
+            .unwrap()
+            .retain(|_, active| *active != profile_id);
+    }
+}
+
+/// Firewall profile a command should use
+///
+/// Commands take this instead of always using the default profile. It comes from the
+/// optional `profileId` argument, falling back to the calling window's active profile
+/// and then to the default profile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SelectedProfile {
+    profile_id: Option<i64>,
+}
+
+impl SelectedProfile {
+    /// `None` selects the default profile
+    pub fn new(profile_id: Option<i64>) -> Self {
+        Self { profile_id }
+    }
+
+    pub fn api_info(&self, database: &Database) -> Result<Option<ApiInfo>, String> {
+        let api_info = match self.profile_id {
+            Some(profile_id) => database.get_api_info_by_id(profile_id),
+            None => database.get_default_api_info(),
+        };
+        api_info.map_err(|e| format!("Failed to get API info: {}", e))
+    }
+
+    /// Like [`Self::api_info`], but a missing profile is an error
+    pub fn require_api_info(&self, database: &Database) -> Result<ApiInfo, String> {
+        self.api_info(database)?
+            .ok_or_else(|| match self.profile_id {
+                Some(profile_id) => format!("API profile {} not found", profile_id),
+                None => "API info not found".to_string(),
+            })
+    }
+
+    /// Refuses a command that changes the firewall before any of its requests are sent
+    ///
+    /// The HTTP layer refuses the individual requests too; this keeps a multi-step
+    /// change from stopping halfway.
+    pub fn require_writable(&self, database: &Database) -> Result<(), ApiError> {
+        match self.api_info(database).map_err(ApiError::Profile)? {
+            Some(api_info) if api_info.read_only => Err(ApiError::ReadOnly {
+                profile: api_info.profile_name,
+                endpoint: None,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'de, R: Runtime> CommandArg<'de, R> for SelectedProfile {
+    fn from_command(command: CommandItem<'de, R>) -> Result<Self, InvokeError> {
+        let requested = match command.message.payload() {
+            InvokeBody::Json(args) => match args.get(PROFILE_ID_ARG) {
+                None | Some(Value::Null) => None,
+                Some(value) => Some(value.as_i64().ok_or_else(|| {
+                    InvokeError::from(format!(
+                        "{} of command {} must be a profile id",
+                        PROFILE_ID_ARG, command.name
+                    ))
+                })?),
+            },
+            InvokeBody::Raw(_) => None,
+        };
+
+        let webview = command.message.webview_ref();
+        let active = webview
+            .try_state::<ActiveProfiles>()
+            .and_then(|active| active.get(webview.label()));
+
+        Ok(Self::new(requested.or(active)))
+    }
+}
+
+/// Drops a closed window's selection so a new window with the same label starts fresh
+pub fn forget_closed_window<R: Runtime>(window: &tauri::Window<R>, event: &WindowEvent) {
+    if let WindowEvent::Destroyed = event {
+        if let Some(active) = window.try_state::<ActiveProfiles>() {
+            active.set(window.label(), None);
+        }
+    }
+}
+
+/// Makes `profile_id` the calling window's profile, or with `None` goes back to the default
+#[tauri::command]
+pub fn set_active_profile(
+    profile_id: Option<i64>,
+    webview: Webview,
+    database: State<Database>,
+    active: State<ActiveProfiles>,
+) -> Result<(), CommandError> {
+    if let Some(profile_id) = profile_id {
+        SelectedProfile::new(Some(profile_id)).require_api_info(&database)?;
+    }
+
+    info!(
+        "Window {} now uses profile {:?}",
+        webview.label(),
+        profile_id
+    );
+    active.set(webview.label(), profile_id);
+    Ok(())
+}
+
+/// Id of the profile commands from this window use when they don't pass `profileId`
+#[tauri::command]
+pub fn get_active_profile(
+    profile: SelectedProfile,
+    database: State<Database>,
+) -> Result<Option<i64>, CommandError> {
+    Ok(profile.api_info(&database)?.map(|api_info| api_info.id))
+}
@@ -1,69 +1,90 @@
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::search_grid::{self, SearchPage, SearchPager, SearchQuery, SortOrder, STREAM_PAGE_SIZE};
+use log::error;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use serde_json::Value;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use tauri::State;
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
+const ALIAS_SEARCH_ENDPOINT: &str = "/api/firewall/alias/searchItem";
+
+/// Row of the alias grid; fields the app doesn't use are passed through untouched
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AliasItem {
+    pub uuid: String,
+    pub name: String,
+    #[serde(default)]
+    pub enabled: String,
+    #[serde(rename = "type", default)]
+    pub alias_type: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[tauri::command]
-pub async fn list_network_aliases(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/firewall/alias/listNetworkAliases");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn list_network_aliases(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/firewall/alias/listNetworkAliases").await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_alias(database: State<'_, Database>, alias_name: String) -> Result<Value, String> {
-    let aliases = search_alias_items(database).await?;
-
-    let alias = aliases["rows"]
-        .as_array()
-        .and_then(|rows| {
-            rows.iter()
-                .find(|row| row["name"].as_str() == Some(&alias_name))
-        })
-        .ok_or_else(|| format!("Alias '{}' not found", alias_name))?;
-
-    Ok(alias.clone())
+pub async fn get_alias(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    alias_name: String,
+) -> Result<AliasItem, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    // The phrase also matches descriptions and content, so check the name as well
+    let query = SearchQuery::new()
+        .rows(STREAM_PAGE_SIZE)
+        .search(alias_name.as_str());
+    let mut aliases = SearchPager::<AliasItem>::new(&client, ALIAS_SEARCH_ENDPOINT, query);
+
+    while let Some(alias) = aliases.next_row().await? {
+        if alias.name == alias_name {
+            return Ok(alias);
+        }
+    }
+
+    Err(format!("Alias '{}' not found", alias_name).into())
 }
 
 #[tauri::command]
 pub async fn add_alias(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     name: String,
     alias_type: String,
     content: String,
     description: String,
     enabled: bool,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/firewall/alias/addItem/");
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     let formatted_content = content
         .split(',')
@@ -88,16 +109,7 @@ pub async fn add_alias(
         "authgroup_content": ""
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post("/api/firewall/alias/addItem/", payload).await?;
 
     let result = response
         .json::<Value>()
@@ -105,7 +117,15 @@ pub async fn add_alias(
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
     if result["result"].as_str() == Some("saved") {
-        apply_alias_changes(database).await?;
+        let mut change = Change::new(Module::Alias, "add");
+        if let Some(uuid) = result["uuid"].as_str() {
+            change = change
+                .target(uuid)
+                .after(get_alias_info(&client, uuid).await.ok());
+        }
+        change.record(&database, &client);
+
+        apply_alias_changes(profile, database, clients).await?;
     }
 
     Ok(result)
@@ -113,19 +133,20 @@ pub async fn add_alias(
 
 #[tauri::command]
 pub async fn add_ip_to_alias(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
     current_content: String,
     _new_ip: String,
-) -> Result<(), String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+) -> Result<(), CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
-    let url = build_api_url(&api_info, &format!("/api/firewall/alias/setItem/{}", uuid));
+    let endpoint = format!("/api/firewall/alias/setItem/{}", uuid);
 
-    let alias_info = get_alias_info(&api_info, &uuid).await?;
+    let alias_info = get_alias_info(&client, &uuid).await?;
     let alias_name = alias_info["alias"]["name"].as_str().unwrap_or("");
 
     let payload = json!({
@@ -135,39 +156,37 @@ pub async fn add_ip_to_alias(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post(&endpoint, payload).await?;
 
     if response.status().is_success() {
-        apply_alias_changes(database).await?;
+        Change::new(Module::Alias, "add_ip")
+            .target(&uuid)
+            .before(Some(alias_info))
+            .after(get_alias_info(&client, &uuid).await.ok())
+            .record(&database, &client);
+
+        apply_alias_changes(profile, database, clients).await?;
         Ok(())
     } else {
-        Err(format!("Failed to add IP to alias: {}", response.status()))
+        Err(format!("Failed to add IP to alias: {}", response.status()).into())
     }
 }
 
 #[tauri::command]
 pub async fn remove_ip_from_alias(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
     current_content: String,
-) -> Result<(), String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+) -> Result<(), CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
-    let url = build_api_url(&api_info, &format!("/api/firewall/alias/setItem/{}", uuid));
+    let endpoint = format!("/api/firewall/alias/setItem/{}", uuid);
 
-    let alias_info = get_alias_info(&api_info, &uuid).await?;
+    let alias_info = get_alias_info(&client, &uuid).await?;
     let alias_name = alias_info["alias"]["name"].as_str().unwrap_or("");
 
     let payload = json!({
@@ -177,50 +196,38 @@ pub async fn remove_ip_from_alias(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post(&endpoint, payload).await?;
 
     if response.status().is_success() {
-        apply_alias_changes(database).await?;
+        Change::new(Module::Alias, "remove_ip")
+            .target(&uuid)
+            .before(Some(alias_info))
+            .after(get_alias_info(&client, &uuid).await.ok())
+            .record(&database, &client);
+
+        apply_alias_changes(profile, database, clients).await?;
         Ok(())
     } else {
-        Err(format!(
-            "Failed to remove IP from alias: {}",
-            response.status()
-        ))
+        Err(format!("Failed to remove IP from alias: {}", response.status()).into())
     }
 }
 
 #[tauri::command]
-pub async fn toggle_alias(database: State<'_, Database>, uuid: String) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(
-        &api_info,
-        &format!("/api/firewall/alias/toggleItem/{}", uuid),
-    );
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn toggle_alias(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/firewall/alias/toggleItem/{}", uuid);
+
+    let before = get_alias_info(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await?;
 
     let result = response
         .json::<Value>()
@@ -228,31 +235,34 @@ pub async fn toggle_alias(database: State<'_, Database>, uuid: String) -> Result
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
     if result["changed"].as_bool().unwrap_or(false) {
-        apply_alias_changes(database).await?;
+        Change::new(Module::Alias, "toggle")
+            .target(&uuid)
+            .before(before)
+            .after(get_alias_info(&client, &uuid).await.ok())
+            .record(&database, &client);
+
+        apply_alias_changes(profile, database, clients).await?;
     }
 
     Ok(result)
 }
 
 #[tauri::command]
-pub async fn delete_alias(database: State<'_, Database>, uuid: String) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, &format!("/api/firewall/alias/delItem/{}", uuid));
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await;
+pub async fn delete_alias(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/firewall/alias/delItem/{}", uuid);
+
+    let before = get_alias_info(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await;
 
     match response {
         Ok(response) => {
@@ -262,7 +272,12 @@ pub async fn delete_alias(database: State<'_, Database>, uuid: String) -> Result
                     .await
                     .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-                apply_alias_changes(database).await?;
+                Change::new(Module::Alias, "delete")
+                    .target(&uuid)
+                    .before(before)
+                    .record(&database, &client);
+
+                apply_alias_changes(profile, database, clients).await?;
                 Ok(result)
             } else {
                 let error_text = response
@@ -271,25 +286,27 @@ pub async fn delete_alias(database: State<'_, Database>, uuid: String) -> Result
                     .unwrap_or_else(|_| "Unknown error".to_string());
                 if error_text.contains("Alias in use") || error_text.contains("Currently in use by")
                 {
-                    Err("Cannot delete this alias because it is currently in use by firewall rules. Please remove references to this alias in your firewall rules first.".to_string())
+                    Err("Cannot delete this alias because it is currently in use by firewall rules. Please remove references to this alias in your firewall rules first.".into())
                 } else {
-                    Err(format!("Server returned error: {}", error_text))
+                    Err(format!("Server returned error: {}", error_text).into())
                 }
             }
         }
-        Err(e) => Err(e),
+        Err(e) => Err(e.into()),
     }
 }
 
 #[tauri::command]
-pub async fn apply_alias_changes(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+pub async fn apply_alias_changes(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     // Step 1: Call the set API
-    let set_url = build_api_url(&api_info, "/api/firewall/alias/set");
     let set_payload = json!({
         "alias": {
             "geoip": {
@@ -298,16 +315,7 @@ pub async fn apply_alias_changes(database: State<'_, Database>) -> Result<Value,
         }
     });
 
-    let set_response = make_http_request(
-        "POST",
-        &set_url,
-        Some(set_payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let set_response = client.post("/api/firewall/alias/set", set_payload).await?;
 
     let set_result = set_response
         .json::<Value>()
@@ -315,72 +323,53 @@ pub async fn apply_alias_changes(database: State<'_, Database>) -> Result<Value,
         .map_err(|e| format!("Failed to parse set response: {}", e))?;
 
     // Step 2: Call the reconfigure API to apply the changes
-    let reconfigure_url = build_api_url(&api_info, "/api/firewall/alias/reconfigure");
     let reconfigure_payload = json!({});
 
-    let reconfigure_response = make_http_request(
-        "POST",
-        &reconfigure_url,
-        Some(reconfigure_payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let reconfigure_response = client
+        .post("/api/firewall/alias/reconfigure", reconfigure_payload)
+        .await?;
 
     // Return the result of the reconfigure call, or the set result if reconfigure fails
     match reconfigure_response.json::<Value>().await {
         Ok(reconfigure_result) => Ok(reconfigure_result),
         Err(e) => {
-            eprintln!("Failed to parse reconfigure response: {}", e);
+            error!("Failed to parse reconfigure response: {}", e);
             Ok(set_result) // Return the set result as a fallback
         }
     }
 }
 
-async fn get_alias_info(api_info: &crate::db::ApiInfo, uuid: &str) -> Result<Value, String> {
-    let url = build_api_url(api_info, &format!("/api/firewall/alias/getItem/{}", uuid));
+async fn get_alias_info(client: &OpnsenseClient, uuid: &str) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/firewall/alias/getItem/{}", uuid);
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.get(&endpoint).await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn search_alias_items(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/firewall/alias/searchItem");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+pub async fn search_alias_items(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    current_page: u32,
+    row_count: u32,
+    sort: Option<BTreeMap<String, SortOrder>>,
+    search_phrase: String,
+) -> Result<SearchPage<AliasItem>, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let query = match sort {
+        Some(sort) => SearchQuery::new().sorted_by(sort),
+        None => SearchQuery::new().sort_by("name", SortOrder::Asc),
+    };
+    let query = query
+        .page(current_page)
+        .rows(row_count)
+        .search(search_phrase);
+
+    Ok(search_grid::search(&client, ALIAS_SEARCH_ENDPOINT, &query).await?)
 }
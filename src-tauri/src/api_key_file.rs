@@ -0,0 +1,55 @@
+use zeroize::Zeroizing;
+
+/// Credentials from the `apikey.txt` file OPNsense downloads when a key is created
+///
+/// The file holds `key=...` and `secret=...` lines; pasted copies may have them on one
+/// line or wrapped in quotes, so any whitespace separates the two entries.
+pub struct ApiKeyFile {
+    pub key: Zeroizing<String>,
+    pub secret: Zeroizing<String>,
+}
+
+impl ApiKeyFile {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut key = None;
+        let mut secret = None;
+
+        for entry in contents.split_whitespace() {
+            let entry = entry.trim_matches(|c| c == '"' || c == '\'');
+            let Some((name, value)) = entry.split_once('=') else {
+                return Err("API key file should only contain key= and secret= lines".to_string());
+            };
+            let value = value.trim_matches(|c| c == '"' || c == '\'');
+
+            let slot = match name.to_ascii_lowercase().as_str() {
+                "key" => &mut key,
+                "secret" => &mut secret,
+                other => return Err(format!("Unexpected '{}' entry in API key file", other)),
+            };
+            if slot.is_some() {
+                return Err(format!("API key file contains more than one {}", name));
+            }
+            *slot = Some(validated(name, value)?);
+        }
+
+        match (key, secret) {
+            (Some(key), Some(secret)) => Ok(Self { key, secret }),
+            (None, _) => Err("API key file is missing the key= line".to_string()),
+            (_, None) => Err("API key file is missing the secret= line".to_string()),
+        }
+    }
+}
+
+/// OPNsense generates base64 keys and secrets, so anything else is a copy mistake
+fn validated(name: &str, value: &str) -> Result<Zeroizing<String>, String> {
+    if value.is_empty() {
+        return Err(format!("API key file has an empty {}", name));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+    {
+        return Err(format!("API key file {} contains invalid characters", name));
+    }
+    Ok(Zeroizing::new(value.to_string()))
+}
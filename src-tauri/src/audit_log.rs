@@ -0,0 +1,156 @@
+use log::error;
+use rusqlite::{params, Connection, Result};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::local_users::Role;
+
+/// Oldest entries are dropped once the table grows past this
+const MAX_AUDIT_ENTRIES: i64 = 2000;
+const DEFAULT_PAGE_SIZE: u32 = 200;
+
+/// One API call as sent to a firewall, without headers, payloads or query strings
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: String,
+    pub profile_id: i64,
+    pub profile_name: String,
+    pub method: String,
+    pub endpoint: String,
+    pub status: Option<u16>,
+    /// `ApiError` kind when the request failed before a response arrived
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub request_bytes: u64,
+    /// Missing when the firewall streamed the body without a Content-Length
+    pub response_bytes: Option<u64>,
+}
+
+/// Bounded SQLite log of the requests made to each firewall
+///
+/// Shares the database connection, so it can be handed to the HTTP layer
+/// without it needing the `Database` state.
+pub struct AuditLog {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl AuditLog {
+    pub fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    pub fn create_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS api_audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                profile_id INTEGER NOT NULL,
+                profile_name TEXT NOT NULL,
+                method TEXT NOT NULL,
+                endpoint TEXT NOT NULL,
+                status INTEGER,
+                error TEXT,
+                latency_ms INTEGER NOT NULL,
+                request_bytes INTEGER NOT NULL,
+                response_bytes INTEGER
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Appends an entry, logging instead of failing so auditing never breaks a request
+    pub fn record(&self, entry: &AuditEntry) {
+        if let Err(e) = self.insert(entry) {
+            error!("Failed to write API audit log: {}", e);
+        }
+    }
+
+    fn insert(&self, entry: &AuditEntry) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO api_audit_log (timestamp, profile_id, profile_name, method, endpoint,
+             status, error, latency_ms, request_bytes, response_bytes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                entry.timestamp,
+                entry.profile_id,
+                entry.profile_name,
+                entry.method,
+                entry.endpoint,
+                entry.status,
+                entry.error,
+                entry.latency_ms as i64,
+                entry.request_bytes as i64,
+                entry.response_bytes.map(|bytes| bytes as i64),
+            ],
+        )?;
+        conn.execute(
+            "DELETE FROM api_audit_log WHERE id <= last_insert_rowid() - ?1",
+            [MAX_AUDIT_ENTRIES],
+        )?;
+        Ok(())
+    }
+
+    /// Newest entries first, optionally for a single profile
+    pub fn recent(&self, profile_id: Option<i64>, limit: u32) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, timestamp, profile_id, profile_name, method, endpoint, status, error,
+             latency_ms, request_bytes, response_bytes
+             FROM api_audit_log
+             WHERE ?1 IS NULL OR profile_id = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )?;
+
+        let entries = stmt
+            .query_map(params![profile_id, limit], |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    profile_id: row.get(2)?,
+                    profile_name: row.get(3)?,
+                    method: row.get(4)?,
+                    endpoint: row.get(5)?,
+                    status: row.get(6)?,
+                    error: row.get(7)?,
+                    latency_ms: row.get::<_, i64>(8)? as u64,
+                    request_bytes: row.get::<_, i64>(9)? as u64,
+                    response_bytes: row.get::<_, Option<i64>>(10)?.map(|bytes| bytes as u64),
+                })
+            })?
+            .collect::<Result<Vec<AuditEntry>>>()?;
+        Ok(entries)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM api_audit_log", [])?;
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn get_api_audit_log(
+    profile_id: Option<i64>,
+    limit: Option<u32>,
+    database: State<Database>,
+) -> Result<Vec<AuditEntry>, CommandError> {
+    database
+        .audit_log()
+        .recent(profile_id, limit.unwrap_or(DEFAULT_PAGE_SIZE))
+        .map_err(|e| format!("Failed to read API audit log: {}", e).into())
+}
+
+#[tauri::command]
+pub fn clear_api_audit_log(database: State<Database>) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    database
+        .audit_log()
+        .clear()
+        .map_err(|e| format!("Failed to clear API audit log: {}", e).into())
+}
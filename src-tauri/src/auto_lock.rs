@@ -0,0 +1,120 @@
+use log::{error, info};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::http_client::ClientManager;
+use crate::local_users::Role;
+
+/// Tells the frontend to show the lock screen
+pub const LOCK_EVENT: &str = "app-locked";
+pub const DEFAULT_IDLE_LOCK_MINUTES: u32 = 5;
+const MAX_IDLE_LOCK_MINUTES: u32 = 24 * 60;
+const IDLE_CHECK_INTERVAL_SECS: u64 = 15;
+
+/// Time since the user last did something, so the app can lock itself when left alone
+///
+/// Only the frontend reports activity; background polling doesn't keep the app unlocked.
+pub struct IdleLock {
+    last_activity: Mutex<Instant>,
+    timeout: Mutex<Option<Duration>>,
+}
+
+impl IdleLock {
+    pub fn new(minutes: u32) -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            timeout: Mutex::new(timeout_from_minutes(minutes)),
+        }
+    }
+
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    pub fn set_minutes(&self, minutes: u32) {
+        *self.timeout.lock().unwrap() = timeout_from_minutes(minutes);
+    }
+
+    pub fn is_idle(&self, now: Instant) -> bool {
+        let last_activity = *self.last_activity.lock().unwrap();
+        self.timeout
+            .lock()
+            .unwrap()
+            .is_some_and(|timeout| now.saturating_duration_since(last_activity) >= timeout)
+    }
+}
+
+/// 0 turns the idle lock off
+fn timeout_from_minutes(minutes: u32) -> Option<Duration> {
+    (minutes > 0).then(|| Duration::from_secs(u64::from(minutes) * 60))
+}
+
+/// Forgets the derived key and every pooled client holding decrypted credentials
+pub fn lock(database: &Database, clients: &ClientManager) {
+    database.lock();
+    clients.clear();
+}
+
+pub fn register_idle_lock(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    let minutes = app.state::<Database>().get_idle_lock_minutes()?;
+    app.manage(IdleLock::new(minutes));
+
+    let handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(IDLE_CHECK_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            lock_if_idle(&handle);
+        }
+    });
+
+    Ok(())
+}
+
+fn lock_if_idle(app: &AppHandle) {
+    let database = app.state::<Database>();
+    if !database.is_unlocked() || !app.state::<IdleLock>().is_idle(Instant::now()) {
+        return;
+    }
+
+    info!("Locking after a period of inactivity");
+    lock(&database, &app.state::<ClientManager>());
+
+    if let Err(e) = app.emit(LOCK_EVENT, ()) {
+        error!("Failed to emit {} event: {}", LOCK_EVENT, e);
+    }
+}
+
+/// Called by the frontend on user input, throttled on its side
+#[tauri::command]
+pub fn record_activity(idle_lock: State<IdleLock>) {
+    idle_lock.touch();
+}
+
+#[tauri::command]
+pub fn get_idle_lock_minutes(database: State<Database>) -> Result<u32, CommandError> {
+    Ok(database.get_idle_lock_minutes()?)
+}
+
+#[tauri::command]
+pub fn set_idle_lock_minutes(
+    minutes: u32,
+    database: State<Database>,
+    idle_lock: State<IdleLock>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    if !database.is_unlocked() {
+        return Err("PIN authentication required. Please login again.".into());
+    }
+    if minutes > MAX_IDLE_LOCK_MINUTES {
+        return Err(format!("Idle lock can be at most {} minutes", MAX_IDLE_LOCK_MINUTES).into());
+    }
+
+    database.set_idle_lock_minutes(minutes)?;
+    idle_lock.set_minutes(minutes);
+    idle_lock.touch();
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use log::info;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::State;
+
+use crate::active_profile::SelectedProfile;
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::http_client::{ApiError, ApiRequest, ClientManager, OpnsenseClient};
+
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+/// What a firewall's firmware and plugins support, discovered once per profile
+///
+/// Version checks are used where the firmware API is readable; otherwise the
+/// relevant endpoints are probed directly.
+#[derive(Serialize, Debug, Clone)]
+pub struct Capabilities {
+    /// e.g. `25.1.2`, missing when the API key can't read firmware information
+    pub firmware_version: Option<String>,
+    /// Installed plugin packages such as `os-wol`, missing for the same reason
+    pub plugins: Option<Vec<String>>,
+    /// Filter rules can be listed per interface (`get_interface_list`)
+    pub interface_rule_filter: bool,
+    /// System tunables API, new in 25.1
+    pub tunables: bool,
+    /// ZFS boot environment snapshots, new in 24.7 and only on ZFS installs
+    pub snapshots: bool,
+    /// Wake on LAN plugin
+    pub wol: bool,
+}
+
+impl Capabilities {
+    /// `None` when the firmware version is unknown
+    pub fn version_at_least(&self, major: u32, minor: u32) -> Option<bool> {
+        let version = parse_version(self.firmware_version.as_deref()?)?;
+        Some(version >= (major, minor))
+    }
+
+    pub fn has_plugin(&self, name: &str) -> Option<bool> {
+        Some(self.plugins.as_ref()?.iter().any(|plugin| plugin == name))
+    }
+}
+
+/// Major and minor release of versions like `24.7.12_4` or `25.1.r1`
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '_', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// The endpoint is missing or hidden from this API key, rather than the firewall being down
+fn is_unavailable(error: &ApiError) -> bool {
+    matches!(error, ApiError::NotFound { .. } | ApiError::Permission)
+}
+
+fn is_installed(value: &Value) -> bool {
+    match value {
+        Value::String(flag) => flag == "1",
+        Value::Number(flag) => flag.as_u64() == Some(1),
+        Value::Bool(flag) => *flag,
+        _ => false,
+    }
+}
+
+async fn probe(client: &OpnsenseClient, endpoint: &str) -> Result<Option<Value>, ApiError> {
+    match client
+        .send(ApiRequest::get(endpoint).timeout(PROBE_TIMEOUT_SECS))
+        .await
+    {
+        Ok(response) => Ok(Some(response.json::<Value>().await.unwrap_or(Value::Null))),
+        Err(e) if is_unavailable(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the firmware version and plugin list, then fills in what they can't answer
+///
+/// Fails only when the firewall can't be reached, so a transient outage is never
+/// cached as missing features.
+pub async fn discover(client: &OpnsenseClient) -> Result<Capabilities, ApiError> {
+    let info = probe(client, "/api/core/firmware/info").await?;
+
+    let firmware_version = info
+        .as_ref()
+        .and_then(|info| info["product"]["product_version"].as_str())
+        .map(str::to_string);
+    let plugins = info.as_ref().map(|info| {
+        info["plugin"]
+            .as_array()
+            .map(|plugins| {
+                plugins
+                    .iter()
+                    .filter(|plugin| is_installed(&plugin["installed"]))
+                    .filter_map(|plugin| plugin["name"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    });
+
+    let mut capabilities = Capabilities {
+        firmware_version,
+        plugins,
+        interface_rule_filter: false,
+        tunables: false,
+        snapshots: false,
+        wol: false,
+    };
+
+    capabilities.interface_rule_filter = probe(client, "/api/firewall/filter/get_interface_list")
+        .await?
+        .is_some();
+
+    // Unknown firmware gets the benefit of the doubt; the endpoint itself will tell
+    capabilities.tunables = capabilities.version_at_least(25, 1).unwrap_or(true);
+
+    capabilities.snapshots = match capabilities.version_at_least(24, 7) {
+        Some(false) => false,
+        _ => probe(client, "/api/core/snapshots/is_supported/")
+            .await?
+            .and_then(|result| result["supported"].as_bool())
+            .unwrap_or(false),
+    };
+
+    capabilities.wol = match capabilities.has_plugin("os-wol") {
+        Some(installed) => installed,
+        // A 403 still means the plugin is there, the key just can't use it
+        None => match client
+            .send(ApiRequest::get("/api/wol/wol/getwake").timeout(PROBE_TIMEOUT_SECS))
+            .await
+        {
+            Ok(_) | Err(ApiError::Permission) => true,
+            Err(ApiError::NotFound { .. }) => false,
+            Err(e) => return Err(e),
+        },
+    };
+
+    info!(
+        "Discovered capabilities of profile {}: {:?}",
+        client.profile_id(),
+        capabilities
+    );
+    Ok(capabilities)
+}
+
+#[tauri::command]
+pub async fn get_capabilities(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Capabilities, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    Ok(clients.capabilities(&client).await?)
+}
+
+/// Discovers capabilities again, e.g. after a firmware upgrade or plugin install
+#[tauri::command]
+pub async fn refresh_capabilities(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Capabilities, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    clients.forget_capabilities(client.profile_id());
+    Ok(clients.capabilities(&client).await?)
+}
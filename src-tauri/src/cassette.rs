@@ -0,0 +1,399 @@
+use log::{info, warn};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::http_client::{ApiError, ApiRequest, ClientManager, RequestBody};
+use crate::local_users::Role;
+
+const CASSETTE_VERSION: u32 = 1;
+const REDACTED: &str = "[REDACTED]";
+/// Folder under the app's local data dir that cassette names are resolved in
+const CASSETTE_DIR: &str = "cassettes";
+
+/// JSON keys whose values are replaced before an interaction is written to disk
+const SENSITIVE_KEY_PARTS: &[&str] = &[
+    "password", "passwd", "secret", "token", "apikey", "api_key", "psk", "private", "privkey",
+];
+const SENSITIVE_KEYS: &[&str] = &["key", "prv", "authorization"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    Off,
+    Record,
+    Replay,
+}
+
+/// One recorded request/response pair
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interaction {
+    pub method: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub request_headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub request_body: Option<Value>,
+    pub status: u16,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    pub response_body: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CassetteFile {
+    version: u32,
+    recorded_at: String,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CassetteStatus {
+    mode: CassetteMode,
+    path: Option<String>,
+    interactions: usize,
+}
+
+struct CassetteState {
+    mode: CassetteMode,
+    path: Option<PathBuf>,
+    file: CassetteFile,
+    replayed: Vec<bool>,
+}
+
+/// Records OPNsense API traffic to a cassette file, or serves a recorded file back
+///
+/// Shared by every pooled client, so switching modes applies to all profiles at once.
+/// Credentials and secret-looking JSON fields are redacted before anything is written.
+pub struct Cassette {
+    state: Mutex<CassetteState>,
+}
+
+impl Cassette {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(CassetteState {
+                mode: CassetteMode::Off,
+                path: None,
+                file: CassetteFile::default(),
+                replayed: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.state.lock().unwrap().mode
+    }
+
+    pub fn status(&self) -> CassetteStatus {
+        let state = self.state.lock().unwrap();
+        CassetteStatus {
+            mode: state.mode,
+            path: state.path.as_ref().map(|p| p.display().to_string()),
+            interactions: state.file.interactions.len(),
+        }
+    }
+
+    pub fn start_recording(&self, path: &Path) -> Result<(), String> {
+        let file = CassetteFile {
+            version: CASSETTE_VERSION,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            interactions: Vec::new(),
+        };
+        write_file(path, &file)?;
+
+        info!("Recording API traffic to {}", path.display());
+        let mut state = self.state.lock().unwrap();
+        state.mode = CassetteMode::Record;
+        state.path = Some(path.to_path_buf());
+        state.file = file;
+        state.replayed.clear();
+        Ok(())
+    }
+
+    pub fn start_replay(&self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cassette {}: {}", path.display(), e))?;
+        let file: CassetteFile = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid cassette {}: {}", path.display(), e))?;
+
+        if file.version != CASSETTE_VERSION {
+            return Err(format!(
+                "Unsupported cassette version {} (expected {})",
+                file.version, CASSETTE_VERSION
+            ));
+        }
+
+        info!(
+            "Replaying {} recorded interactions from {}",
+            file.interactions.len(),
+            path.display()
+        );
+        let mut state = self.state.lock().unwrap();
+        state.mode = CassetteMode::Replay;
+        state.path = Some(path.to_path_buf());
+        state.replayed = vec![false; file.interactions.len()];
+        state.file = file;
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.mode = CassetteMode::Off;
+        state.path = None;
+        state.file = CassetteFile::default();
+        state.replayed.clear();
+    }
+
+    /// Buffers a live response, appends it to the cassette and hands back an equivalent response
+    pub async fn record(
+        &self,
+        request: &ApiRequest,
+        has_credentials: bool,
+        secrets: &[&str],
+        response: Response,
+    ) -> Result<Response, ApiError> {
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| ApiError::Parse(format!("Failed to read response body: {}", e)))?;
+
+        let mut request_headers = BTreeMap::new();
+        if has_credentials {
+            request_headers.insert("authorization".to_string(), REDACTED.to_string());
+        }
+        if let Some(headers) = &request.headers {
+            for (name, value) in headers {
+                let value = if SENSITIVE_KEYS.contains(&name.as_str()) {
+                    REDACTED.to_string()
+                } else {
+                    redact_text(value.to_str().unwrap_or_default(), secrets)
+                };
+                request_headers.insert(name.to_string(), value);
+            }
+        }
+
+        let request_body = match &request.body {
+            RequestBody::Empty => None,
+            RequestBody::Json(payload) => Some(redact_json(payload, secrets)),
+            RequestBody::Form(form_data) => Some(Value::String(redact_text(form_data, secrets))),
+        };
+
+        let interaction = Interaction {
+            method: request.method.to_string(),
+            endpoint: request.endpoint.clone(),
+            request_headers,
+            request_body,
+            status,
+            content_type: content_type.clone(),
+            response_body: redact_body(&String::from_utf8_lossy(&body), secrets),
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.mode == CassetteMode::Record {
+                state.file.interactions.push(interaction);
+                // Rewritten on every call so a crash still leaves a usable capture
+                if let Some(path) = state.path.clone() {
+                    if let Err(e) = write_file(&path, &state.file) {
+                        warn!("{}", e);
+                    }
+                }
+            }
+        }
+
+        build_response(status, content_type.as_deref(), body.to_vec())
+    }
+
+    /// Returns the recorded response for a request, in recording order
+    ///
+    /// Repeated calls to the same endpoint get successive recordings; once those run out
+    /// the last one is served again so polling loops keep working.
+    pub fn replay(&self, method: &Method, endpoint: &str) -> Result<Response, ApiError> {
+        let mut state = self.state.lock().unwrap();
+        let method = method.to_string();
+
+        let matching: Vec<usize> = state
+            .file
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.method == method && i.endpoint == endpoint)
+            .map(|(index, _)| index)
+            .collect();
+
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&index| !state.replayed[index])
+            .or_else(|| matching.last().copied())
+            .ok_or_else(|| {
+                ApiError::Client(format!(
+                    "No recorded response for {} {} in the loaded cassette",
+                    method, endpoint
+                ))
+            })?;
+
+        state.replayed[index] = true;
+        let interaction = &state.file.interactions[index];
+        build_response(
+            interaction.status,
+            interaction.content_type.as_deref(),
+            interaction.response_body.clone().into_bytes(),
+        )
+    }
+}
+
+fn write_file(path: &Path, file: &CassetteFile) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(file)
+        .map_err(|e| format!("Failed to serialize cassette: {}", e))?;
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write cassette {}: {}", path.display(), e))
+}
+
+fn build_response(
+    status: u16,
+    content_type: Option<&str>,
+    body: Vec<u8>,
+) -> Result<Response, ApiError> {
+    let mut builder = http::Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+
+    builder
+        .body(body)
+        .map(Response::from)
+        .map_err(|e| ApiError::Client(format!("Failed to build recorded response: {}", e)))
+}
+
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.contains(&key.as_str())
+        || SENSITIVE_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+pub fn redact_text(text: &str, secrets: &[&str]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+}
+
+/// Replaces the values of secret-looking keys and any occurrence of `secrets`
+pub fn redact_json(value: &Value, secrets: &[&str]) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_sensitive_key(key) && !value.is_object() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact_json(value, secrets)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| redact_json(v, secrets)).collect())
+        }
+        Value::String(text) => Value::String(redact_text(text, secrets)),
+        other => other.clone(),
+    }
+}
+
+fn redact_body(body: &str, secrets: &[&str]) -> String {
+    match serde_json::from_str::<Value>(body) {
+        Ok(json) => redact_json(&json, secrets).to_string(),
+        Err(_) => redact_text(body, secrets),
+    }
+}
+
+/// Resolves a cassette name from the frontend to a file inside `dir`
+///
+/// Only plain relative paths are accepted, so a cassette can't be written or read
+/// anywhere else on disk.
+pub fn resolve_path(dir: &Path, path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    let is_plain = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if path.is_empty() || !is_plain {
+        return Err(format!(
+            "Invalid cassette path '{}': use a file name inside the cassette folder",
+            path
+        ));
+    }
+
+    let resolved = dir.join(relative);
+    if let Some(parent) = resolved.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    Ok(resolved)
+}
+
+fn cassette_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    app.path()
+        .app_local_data_dir()
+        .map(|dir| dir.join(CASSETTE_DIR))
+        .map_err(|e| format!("Failed to get app data dir: {}", e))
+}
+
+#[tauri::command]
+pub fn start_api_recording<R: Runtime>(
+    app: AppHandle<R>,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    path: String,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    let path = resolve_path(&cassette_dir(&app)?, &path)?;
+    Ok(clients.cassette().start_recording(&path)?)
+}
+
+#[tauri::command]
+pub fn start_api_replay<R: Runtime>(
+    app: AppHandle<R>,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    path: String,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    let path = resolve_path(&cassette_dir(&app)?, &path)?;
+    Ok(clients.cassette().start_replay(&path)?)
+}
+
+#[tauri::command]
+pub fn stop_api_cassette(
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<CassetteStatus, CommandError> {
+    database.require_role(Role::Admin)?;
+    let status = clients.cassette().status();
+    clients.cassette().stop();
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn get_api_cassette_status(
+    clients: State<'_, ClientManager>,
+) -> Result<CassetteStatus, CommandError> {
+    Ok(clients.cassette().status())
+}
@@ -0,0 +1,412 @@
+use log::error;
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::http_client::OpnsenseClient;
+
+const DEFAULT_PAGE_SIZE: u32 = 200;
+
+/// Action journaled for a change reverted by `undo_last_change`
+pub const UNDO_ACTION: &str = "undo";
+
+/// Part of the firewall configuration a journaled change touched
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Module {
+    Alias,
+    Firewall,
+    Routes,
+    Tunables,
+    Unbound,
+    Wol,
+    Snapshots,
+}
+
+impl Module {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Module::Alias => "alias",
+            Module::Firewall => "firewall",
+            Module::Routes => "routes",
+            Module::Tunables => "tunables",
+            Module::Unbound => "unbound",
+            Module::Wol => "wol",
+            Module::Snapshots => "snapshots",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "alias" => Ok(Module::Alias),
+            "firewall" => Ok(Module::Firewall),
+            "routes" => Ok(Module::Routes),
+            "tunables" => Ok(Module::Tunables),
+            "unbound" => Ok(Module::Unbound),
+            "wol" => Ok(Module::Wol),
+            "snapshots" => Ok(Module::Snapshots),
+            other => Err(rusqlite::Error::FromSqlConversionFailure(
+                5,
+                rusqlite::types::Type::Text,
+                format!("Unknown module '{}'", other).into(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One change made on a firewall, with the item as it was before and after
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangeEntry {
+    pub id: i64,
+    pub timestamp: String,
+    /// Local user who was signed in when the change was made
+    pub user_name: String,
+    pub profile_id: i64,
+    pub profile_name: String,
+    pub module: Module,
+    pub action: String,
+    pub target_uuid: Option<String>,
+    /// Missing for new items, or when the firewall couldn't return the item
+    pub before: Option<Value>,
+    /// Missing for deleted items, or when the firewall couldn't return the item
+    pub after: Option<Value>,
+    /// When the change was reverted with `undo_last_change`
+    pub undone_at: Option<String>,
+}
+
+/// Which journal entries to return; every field left out matches everything
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ChangeFilter {
+    pub profile_id: Option<i64>,
+    pub module: Option<Module>,
+    pub user_name: Option<String>,
+    pub target_uuid: Option<String>,
+    /// RFC 3339 timestamps; `since` is inclusive, `until` exclusive
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// A change a command made, journaled once the firewall accepted it
+pub struct Change {
+    module: Module,
+    action: &'static str,
+    target_uuid: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl Change {
+    pub fn new(module: Module, action: &'static str) -> Self {
+        Self {
+            module,
+            action,
+            target_uuid: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn target(mut self, uuid: impl Into<String>) -> Self {
+        self.target_uuid = Some(uuid.into());
+        self
+    }
+
+    pub fn before(mut self, before: Option<Value>) -> Self {
+        self.before = before;
+        self
+    }
+
+    pub fn after(mut self, after: Option<Value>) -> Self {
+        self.after = after;
+        self
+    }
+
+    /// Journals the change against the client's profile and the signed-in user
+    pub fn record(self, database: &Database, client: &OpnsenseClient) {
+        let user_name = database
+            .signed_in_user()
+            .map(|user| user.name)
+            .unwrap_or_default();
+
+        database.change_journal().record(&ChangeEntry {
+            id: 0,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            user_name,
+            profile_id: client.profile_id(),
+            profile_name: client.profile_name().to_string(),
+            module: self.module,
+            action: self.action.to_string(),
+            target_uuid: self.target_uuid,
+            before: self.before,
+            after: self.after,
+            undone_at: None,
+        });
+    }
+}
+
+/// SQLite journal of the changes made on each firewall through the app
+///
+/// Applying pending changes isn't journaled on its own; the edit that made them is.
+pub struct ChangeJournal {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl ChangeJournal {
+    pub fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    pub fn create_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS change_journal (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                user_name TEXT NOT NULL,
+                profile_id INTEGER NOT NULL,
+                profile_name TEXT NOT NULL,
+                module TEXT NOT NULL,
+                action TEXT NOT NULL,
+                target_uuid TEXT,
+                before_json TEXT,
+                after_json TEXT,
+                undone_at TEXT
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS change_journal_target
+             ON change_journal (profile_id, target_uuid)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Appends an entry, logging instead of failing since the change itself already succeeded
+    pub fn record(&self, entry: &ChangeEntry) {
+        if let Err(e) = self.insert(entry) {
+            error!("Failed to write change journal: {}", e);
+        }
+    }
+
+    fn insert(&self, entry: &ChangeEntry) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO change_journal (timestamp, user_name, profile_id, profile_name, module,
+             action, target_uuid, before_json, after_json)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                entry.timestamp,
+                entry.user_name,
+                entry.profile_id,
+                entry.profile_name,
+                entry.module.as_str(),
+                entry.action,
+                entry.target_uuid,
+                entry.before.as_ref().map(Value::to_string),
+                entry.after.as_ref().map(Value::to_string),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Newest entries first; `limit` of `None` returns every match
+    pub fn search(&self, filter: &ChangeFilter, limit: Option<u32>) -> Result<Vec<ChangeEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM change_journal
+             WHERE (?1 IS NULL OR profile_id = ?1)
+               AND (?2 IS NULL OR module = ?2)
+               AND (?3 IS NULL OR user_name = ?3)
+               AND (?4 IS NULL OR target_uuid = ?4)
+               AND (?5 IS NULL OR timestamp >= ?5)
+               AND (?6 IS NULL OR timestamp < ?6)
+             ORDER BY id DESC LIMIT ?7",
+            ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt
+            .query_map(
+                params![
+                    filter.profile_id,
+                    filter.module.map(|module| module.as_str()),
+                    filter.user_name,
+                    filter.target_uuid,
+                    filter.since,
+                    filter.until,
+                    limit.map(i64::from).unwrap_or(-1),
+                ],
+                entry_from_row,
+            )?
+            .collect::<Result<Vec<ChangeEntry>>>()?;
+        Ok(entries)
+    }
+
+    /// Newest change on the profile that is neither an undo nor undone and that
+    /// `undoable` accepts
+    pub fn last_undoable(
+        &self,
+        profile_id: i64,
+        undoable: impl Fn(&ChangeEntry) -> bool,
+    ) -> Result<Option<ChangeEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM change_journal
+             WHERE profile_id = ?1 AND undone_at IS NULL AND action != ?2
+             ORDER BY id DESC",
+            ENTRY_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query(params![profile_id, UNDO_ACTION])?;
+        while let Some(row) = rows.next()? {
+            let entry = entry_from_row(row)?;
+            if undoable(&entry) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn mark_undone(&self, id: i64, undone_at: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE change_journal SET undone_at = ?1 WHERE id = ?2",
+            params![undone_at, id],
+        )?;
+        Ok(())
+    }
+}
+
+const ENTRY_COLUMNS: &str = "id, timestamp, user_name, profile_id, profile_name, module, action,
+    target_uuid, before_json, after_json, undone_at";
+
+fn entry_from_row(row: &rusqlite::Row) -> Result<ChangeEntry> {
+    Ok(ChangeEntry {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        user_name: row.get(2)?,
+        profile_id: row.get(3)?,
+        profile_name: row.get(4)?,
+        module: Module::parse(&row.get::<_, String>(5)?)?,
+        action: row.get(6)?,
+        target_uuid: row.get(7)?,
+        before: parse_json(row.get(8)?),
+        after: parse_json(row.get(9)?),
+        undone_at: row.get(10)?,
+    })
+}
+
+fn parse_json(stored: Option<String>) -> Option<Value> {
+    stored.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+/// Accepts any RFC 3339 offset and converts to the UTC form entries are stored with,
+/// so timestamps compare as text
+fn normalize_timestamp(timestamp: Option<String>) -> Result<Option<String>, String> {
+    timestamp
+        .map(|timestamp| {
+            chrono::DateTime::parse_from_rfc3339(&timestamp)
+                .map(|parsed| parsed.with_timezone(&chrono::Utc).to_rfc3339())
+                .map_err(|e| format!("Invalid timestamp '{}': {}", timestamp, e))
+        })
+        .transpose()
+}
+
+fn search_journal(
+    database: &Database,
+    mut filter: ChangeFilter,
+    limit: Option<u32>,
+) -> Result<Vec<ChangeEntry>, String> {
+    filter.since = normalize_timestamp(filter.since)?;
+    filter.until = normalize_timestamp(filter.until)?;
+
+    database
+        .change_journal()
+        .search(&filter, limit)
+        .map_err(|e| format!("Failed to read change journal: {}", e))
+}
+
+/// Quotes a field when it holds a delimiter, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[ChangeEntry]) -> String {
+    let mut csv = String::from(
+        "id,timestamp,user,profile_id,profile,module,action,target_uuid,undone_at,before,after\n",
+    );
+
+    for entry in entries {
+        let json = |value: &Option<Value>| value.as_ref().map(Value::to_string).unwrap_or_default();
+        let fields = [
+            entry.id.to_string(),
+            entry.timestamp.clone(),
+            entry.user_name.clone(),
+            entry.profile_id.to_string(),
+            entry.profile_name.clone(),
+            entry.module.to_string(),
+            entry.action.clone(),
+            entry.target_uuid.clone().unwrap_or_default(),
+            entry.undone_at.clone().unwrap_or_default(),
+            json(&entry.before),
+            json(&entry.after),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+#[tauri::command]
+pub fn get_change_journal(
+    filter: Option<ChangeFilter>,
+    database: State<Database>,
+) -> Result<Vec<ChangeEntry>, CommandError> {
+    let filter = filter.unwrap_or_default();
+    let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    Ok(search_journal(&database, filter, Some(limit))?)
+}
+
+/// Every entry matching `filter`, oldest first, as a file for the frontend to save
+#[tauri::command]
+pub fn export_change_journal(
+    filter: Option<ChangeFilter>,
+    format: ExportFormat,
+    database: State<Database>,
+) -> Result<String, CommandError> {
+    let filter = filter.unwrap_or_default();
+    let limit = filter.limit;
+    let mut entries = search_journal(&database, filter, limit)?;
+    entries.reverse();
+
+    match format {
+        ExportFormat::Csv => Ok(to_csv(&entries)),
+        ExportFormat::Json => serde_json::to_string_pretty(&entries)
+            .map_err(|e| format!("Failed to export change journal: {}", e).into()),
+    }
+}
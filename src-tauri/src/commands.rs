@@ -1,18 +1,36 @@
+use crate::active_profile::{ActiveProfiles, SelectedProfile};
+use crate::api_key_file::ApiKeyFile;
+use crate::confirmation::{Confirmations, DestructiveAction};
 use crate::db::{self, ApiInfo, Database};
-use crate::http_client::make_http_request;
-use crate::pin_cache::PinCache;
+use crate::error::CommandError;
+use crate::http_client::{ApiRequest, ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::proxy::ProxySettings;
+use crate::rate_limit::{RateLimits, DEFAULT_LIMITS};
+use crate::ssh_tunnel::SshTunnelSettings;
+use crate::tls;
 use log::{error, info};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use tauri::State;
+use zeroize::Zeroizing;
 
 #[tauri::command]
-pub async fn get_vendor_info(mac: String) -> Result<String, String> {
+pub async fn get_vendor_info(
+    mac: String,
+    clients: State<'_, ClientManager>,
+) -> Result<String, CommandError> {
     let formatted_mac = mac.replace(":", "-");
     let url = format!("https://api.macvendors.com/{}", formatted_mac);
 
-    match make_http_request("GET", &url, None, None, Some(30), None, None).await {
+    match clients
+        .public_client()
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await
+    {
         Ok(response) => {
             if response.status().is_success() {
                 Ok(response
@@ -22,16 +40,53 @@ pub async fn get_vendor_info(mac: String) -> Result<String, String> {
             } else if response.status().as_u16() == 404 {
                 Ok("Unknown".to_string())
             } else {
-                Err(format!("Failed to get vendor info: {}", response.status()))
+                Err(format!("Failed to get vendor info: {}", response.status()).into())
             }
         }
-        Err(e) => Err(format!("Failed to get vendor info: {}", e)),
+        Err(e) => Err(format!("Failed to get vendor info: {}", e).into()),
     }
 }
 
+/// Treats blank PEM fields from the settings form as not configured
+fn non_empty<T: AsRef<str>>(value: Option<T>) -> Option<T> {
+    value.filter(|v| !v.as_ref().trim().is_empty())
+}
+
+fn validated_proxy(proxy: Option<ProxySettings>) -> Result<Option<ProxySettings>, String> {
+    let Some(mut proxy) = proxy else {
+        return Ok(None);
+    };
+    proxy.host = proxy.host.trim().to_string();
+    proxy.username = non_empty(proxy.username);
+    proxy.password = non_empty(proxy.password);
+    proxy.validate()?;
+    Ok(Some(proxy))
+}
+
+fn validated_ssh_tunnel(
+    tunnel: Option<SshTunnelSettings>,
+) -> Result<Option<SshTunnelSettings>, String> {
+    let Some(mut tunnel) = tunnel else {
+        return Ok(None);
+    };
+    tunnel.host = tunnel.host.trim().to_string();
+    tunnel.password = non_empty(tunnel.password);
+    tunnel.private_key = non_empty(tunnel.private_key);
+    tunnel.passphrase = non_empty(tunnel.passphrase);
+    tunnel.validate()?;
+    Ok(Some(tunnel))
+}
+
+fn ensure_single_transport(api_info: &ApiInfo) -> Result<(), String> {
+    if api_info.proxy.is_some() && api_info.ssh_tunnel.is_some() {
+        return Err("A profile can use a proxy or an SSH tunnel, not both".to_string());
+    }
+    Ok(())
+}
+
 #[tauri::command]
-pub fn check_first_run(database: State<Database>) -> Result<bool, String> {
-    database.is_first_run().map_err(|e| e.to_string())
+pub fn check_first_run(database: State<Database>) -> Result<bool, CommandError> {
+    database.is_first_run().map_err(|e| e.to_string().into())
 }
 
 #[derive(Deserialize)]
@@ -42,14 +97,23 @@ pub struct InitialConfig {
     api_url: String,
     port: u16,
     pin: String,
+    #[serde(default)]
+    ca_chain_pem: Option<String>,
+    #[serde(default)]
+    client_cert_pem: Option<String>,
+    #[serde(default)]
+    client_key_pem: Option<Zeroizing<String>>,
+    #[serde(default)]
+    proxy: Option<ProxySettings>,
+    #[serde(default)]
+    ssh_tunnel: Option<SshTunnelSettings>,
 }
 
 #[tauri::command]
 pub async fn save_initial_config(
     config: InitialConfig,
     database: State<'_, Database>,
-    pin_cache: State<'_, PinCache>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     info!("Starting save_initial_config");
 
     info!("Hashing password");
@@ -64,21 +128,32 @@ pub async fn save_initial_config(
         format!("Failed to save password hash: {}", e)
     })?;
 
-    pin_cache.set_pin(config.pin.clone());
-
     info!("Creating ApiInfo");
     let api_info = ApiInfo {
         id: 0,
         profile_name: config.profile_name,
-        api_key: config.api_key,
-        api_secret: config.api_secret,
+        api_key: config.api_key.into(),
+        api_secret: config.api_secret.into(),
         api_url: config.api_url,
         port: config.port,
         is_default: true,
+        cert_fingerprint: None,
+        ca_chain_pem: non_empty(config.ca_chain_pem),
+        client_cert_pem: non_empty(config.client_cert_pem),
+        client_key_pem: non_empty(config.client_key_pem),
+        proxy: validated_proxy(config.proxy)?,
+        ssh_tunnel: validated_ssh_tunnel(config.ssh_tunnel)?,
+        read_only: false,
     };
+    ensure_single_transport(&api_info)?;
+
+    // Unlocking derives the credential key, so the first profile is stored encrypted
+    if !database.verify_pin(&config.pin)? {
+        return Err("Failed to unlock with the new PIN".into());
+    }
 
     info!("Saving API info");
-    database.save_initial_api_info(&api_info).map_err(|e| {
+    database.save_api_info(&api_info).map_err(|e| {
         error!("Failed to save API info: {}", e);
         format!("Failed to save API info: {}", e)
     })?;
@@ -96,16 +171,26 @@ pub async fn save_initial_config(
     Ok(())
 }
 
+/// Only admins may edit profiles, so only they get the secrets back
 #[tauri::command]
-pub fn get_api_info(database: State<Database>) -> Result<Option<ApiInfo>, String> {
-    database.get_default_api_info().map_err(|e| e.to_string())
+pub fn get_api_info(
+    profile: SelectedProfile,
+    database: State<Database>,
+) -> Result<Option<ApiInfo>, CommandError> {
+    let mut api_info = profile.api_info(&database)?;
+    if database.require_role(Role::Admin).is_err() {
+        if let Some(api_info) = api_info.as_mut() {
+            api_info.redact_secrets();
+        }
+    }
+    Ok(api_info)
 }
 
 #[tauri::command]
-pub fn get_api_profiles(database: State<Database>) -> Result<Vec<ApiInfo>, String> {
+pub fn get_api_profiles(database: State<Database>) -> Result<Vec<ApiInfo>, CommandError> {
     database
         .list_api_profiles()
-        .map_err(|e| format!("Failed to get API profiles: {}", e))
+        .map_err(|e| format!("Failed to get API profiles: {}", e).into())
 }
 
 #[tauri::command]
@@ -116,14 +201,19 @@ pub fn update_api_info(
     api_url: String,
     port: u16,
     is_default: bool,
+    ca_chain_pem: Option<String>,
+    client_cert_pem: Option<String>,
+    client_key_pem: Option<String>,
     database: State<Database>,
-) -> Result<(), String> {
+    clients: State<ClientManager>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
     let result = database
         .get_api_info(Some(&profile_name))
         .map_err(|e| e.to_string())?;
 
     if result.is_none() {
-        return Err(format!("API profile '{}' not found", profile_name));
+        return Err(format!("API profile '{}' not found", profile_name).into());
     }
 
     // Get the existing profile info
@@ -132,17 +222,35 @@ pub fn update_api_info(
     // Check if credentials are empty (which happens when PIN cache is expired)
     if api_info.api_key.is_empty() && api_info.api_secret.is_empty() {
         return Err(
-            "PIN authentication required. Please login again before editing profiles.".to_string(),
+            "PIN authentication required. Please login again before editing profiles.".into(),
         );
     }
 
+    // A different host presents a different certificate, so pin it again on next connect
+    if api_info.api_url != api_url || api_info.port != port {
+        api_info.cert_fingerprint = None;
+    }
+
     // Update the profile info
-    api_info.api_key = api_key;
-    api_info.api_secret = api_secret;
+    api_info.api_key = api_key.into();
+    api_info.api_secret = api_secret.into();
     api_info.api_url = api_url;
     api_info.port = port;
     api_info.is_default = is_default;
 
+    // Omitted TLS fields keep their current value, an empty string removes it
+    for (field, value) in [
+        (&mut api_info.ca_chain_pem, ca_chain_pem),
+        (&mut api_info.client_cert_pem, client_cert_pem),
+    ] {
+        if value.is_some() {
+            *field = non_empty(value);
+        }
+    }
+    if client_key_pem.is_some() {
+        api_info.client_key_pem = non_empty(client_key_pem.map(Zeroizing::new));
+    }
+
     // Save the updated profile
     database.save_api_info(&api_info).map_err(|e| {
         if e.to_string().contains("PIN authentication required") {
@@ -152,6 +260,9 @@ pub fn update_api_info(
         }
     })?;
 
+    // Drop the pooled client so the next request picks up the new settings
+    clients.invalidate(api_info.id);
+
     // Update default status if needed
     if is_default {
         database
@@ -163,24 +274,158 @@ pub fn update_api_info(
     Ok(())
 }
 
+/// Loads a profile, applies `update` to it, saves it and drops its pooled client
+fn update_connection_settings(
+    profile_name: &str,
+    database: &Database,
+    clients: &ClientManager,
+    update: impl FnOnce(&mut ApiInfo) -> Result<(), String>,
+) -> Result<(), CommandError> {
+    let mut api_info = database
+        .get_api_info(Some(profile_name))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("API profile '{}' not found", profile_name))?;
+
+    if api_info.api_key.is_empty() && api_info.api_secret.is_empty() {
+        return Err(
+            "PIN authentication required. Please login again before editing profiles.".into(),
+        );
+    }
+
+    update(&mut api_info)?;
+    ensure_single_transport(&api_info)?;
+
+    database
+        .save_api_info(&api_info)
+        .map_err(|e| format!("Failed to save connection settings: {}", e))?;
+
+    clients.invalidate(api_info.id);
+
+    info!("Connection settings for '{}' updated", profile_name);
+    Ok(())
+}
+
+/// Sets or, with `None`, removes the proxy a profile is reached through
+#[tauri::command]
+pub fn set_profile_proxy(
+    profile_name: String,
+    proxy: Option<ProxySettings>,
+    database: State<Database>,
+    clients: State<ClientManager>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    let proxy = validated_proxy(proxy)?;
+    update_connection_settings(&profile_name, &database, &clients, |api_info| {
+        api_info.proxy = proxy;
+        Ok(())
+    })
+}
+
+/// Sets or, with `None`, removes the SSH tunnel a profile's API is reached through
+///
+/// The host key is pinned again on the next connect whenever the SSH server changes.
+#[tauri::command]
+pub fn set_profile_ssh_tunnel(
+    profile_name: String,
+    ssh_tunnel: Option<SshTunnelSettings>,
+    database: State<Database>,
+    clients: State<ClientManager>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    let ssh_tunnel = validated_ssh_tunnel(ssh_tunnel)?;
+    update_connection_settings(&profile_name, &database, &clients, |api_info| {
+        let same_server = match (&api_info.ssh_tunnel, &ssh_tunnel) {
+            (Some(current), Some(new)) => current.host == new.host && current.port == new.port,
+            _ => false,
+        };
+        api_info.ssh_tunnel = ssh_tunnel.map(|mut tunnel| {
+            if same_server && tunnel.host_key_fingerprint.is_none() {
+                tunnel.host_key_fingerprint = api_info
+                    .ssh_tunnel
+                    .as_ref()
+                    .and_then(|current| current.host_key_fingerprint.clone());
+            }
+            tunnel
+        });
+        Ok(())
+    })
+}
+
+/// Blocks, or allows again, every request that would change the profile's firewall
+#[tauri::command]
+pub fn set_profile_read_only(
+    profile_name: String,
+    read_only: bool,
+    database: State<Database>,
+    clients: State<ClientManager>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    update_connection_settings(&profile_name, &database, &clients, |api_info| {
+        api_info.read_only = read_only;
+        Ok(())
+    })
+}
+
+fn profile_id(database: &Database, profile_name: &str) -> Result<i64, String> {
+    database
+        .get_api_info(Some(profile_name))
+        .map_err(|e| e.to_string())?
+        .map(|api_info| api_info.id)
+        .ok_or_else(|| format!("API profile '{}' not found", profile_name))
+}
+
+#[tauri::command]
+pub fn get_profile_rate_limits(
+    profile_name: String,
+    database: State<Database>,
+) -> Result<RateLimits, CommandError> {
+    let profile_id = profile_id(&database, &profile_name)?;
+    database
+        .get_rate_limits(profile_id)
+        .map(|limits| limits.unwrap_or(DEFAULT_LIMITS))
+        .map_err(|e| format!("Failed to get rate limits: {}", e).into())
+}
+
+/// Sets or, with `None`, resets how many requests a profile's firewall gets at once
+///
+/// Takes effect immediately, including for requests already queued.
+#[tauri::command]
+pub fn set_profile_rate_limits(
+    profile_name: String,
+    limits: Option<RateLimits>,
+    database: State<Database>,
+    clients: State<ClientManager>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    if let Some(limits) = &limits {
+        limits.validate()?;
+    }
+    let profile_id = profile_id(&database, &profile_name)?;
+
+    database
+        .save_rate_limits(profile_id, limits.as_ref())
+        .map_err(|e| format!("Failed to save rate limits: {}", e))?;
+    clients.set_rate_limits(profile_id, limits.unwrap_or(DEFAULT_LIMITS));
+
+    info!("Rate limits for '{}' updated", profile_name);
+    Ok(())
+}
+
 #[tauri::command]
 pub fn update_pin(
     current_pin: String,
     new_pin: String,
     confirm_new_pin: String,
     database: State<Database>,
-    pin_cache: State<PinCache>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
     if new_pin != confirm_new_pin {
-        return Err("New PIN and confirmation do not match".to_string());
+        return Err("New PIN and confirmation do not match".into());
     }
 
     log::info!("Updating PIN in database and re-encrypting API keys");
     database.update_pin(&current_pin, &new_pin)?;
 
-    log::info!("Updating PIN cache with new PIN");
-    pin_cache.set_pin(new_pin.clone());
-
     log::info!("PIN update completed successfully");
 
     Ok(())
@@ -189,28 +434,85 @@ pub fn update_pin(
 #[derive(Deserialize)]
 pub struct NewApiProfile {
     profile_name: String,
+    #[serde(default)]
     api_key: String,
+    #[serde(default)]
     api_secret: String,
+    /// Contents of the apikey.txt OPNsense downloads, in place of `api_key` and `api_secret`
+    #[serde(default)]
+    api_key_file: Option<String>,
     api_url: String,
     port: u16,
+    #[serde(default)]
+    ca_chain_pem: Option<String>,
+    #[serde(default)]
+    client_cert_pem: Option<String>,
+    #[serde(default)]
+    client_key_pem: Option<Zeroizing<String>>,
+    #[serde(default)]
+    proxy: Option<ProxySettings>,
+    #[serde(default)]
+    ssh_tunnel: Option<SshTunnelSettings>,
+    #[serde(default)]
+    read_only: bool,
 }
 
 #[tauri::command]
 pub async fn add_api_profile(
     profile: NewApiProfile,
     database: State<'_, Database>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
     info!("Starting add_api_profile");
 
-    let api_info = ApiInfo {
+    let key_file = non_empty(profile.api_key_file)
+        .map(|contents| ApiKeyFile::parse(&Zeroizing::new(contents)))
+        .transpose()?;
+    let from_key_file = key_file.is_some();
+    let (api_key, api_secret) = match key_file {
+        Some(file) => (file.key, file.secret),
+        None => (profile.api_key.into(), profile.api_secret.into()),
+    };
+
+    let mut api_info = ApiInfo {
         id: 0,
         profile_name: profile.profile_name,
-        api_key: profile.api_key,
-        api_secret: profile.api_secret,
+        api_key,
+        api_secret,
         api_url: profile.api_url,
         port: profile.port,
         is_default: false,
+        cert_fingerprint: None,
+        ca_chain_pem: non_empty(profile.ca_chain_pem),
+        client_cert_pem: non_empty(profile.client_cert_pem),
+        client_key_pem: non_empty(profile.client_key_pem),
+        proxy: validated_proxy(profile.proxy)?,
+        ssh_tunnel: validated_ssh_tunnel(profile.ssh_tunnel)?,
+        read_only: profile.read_only,
     };
+    ensure_single_transport(&api_info)?;
+
+    let client = OpnsenseClient::new(&api_info)?;
+
+    // A key file is usually picked from the phone's downloads, so make sure it belongs
+    // to this firewall before saving it
+    if from_key_file {
+        validate_api_url(&api_info.api_url)?;
+        check_connection(&client).await?;
+    }
+
+    // Pin the firewall certificate now; if it's unreachable it gets pinned on first use
+    match client.probe_certificate().await {
+        Ok(fingerprint) => {
+            info!("Pinned certificate fingerprint: {:?}", fingerprint);
+            api_info.cert_fingerprint = fingerprint;
+        }
+        Err(e) => error!("Could not read certificate for new profile: {}", e),
+    }
+
+    if let Some(tunnel) = api_info.ssh_tunnel.as_mut() {
+        tunnel.host_key_fingerprint = client.ssh_host_key();
+    }
 
     info!("Saving new API profile");
     database.save_api_info(&api_info).map_err(|e| {
@@ -223,7 +525,16 @@ pub async fn add_api_profile(
 }
 
 #[tauri::command]
-pub fn delete_api_profile(profile_name: String, database: State<Database>) -> Result<(), String> {
+pub fn delete_api_profile(
+    profile_name: String,
+    confirmation_token: String,
+    pin: String,
+    database: State<Database>,
+    clients: State<ClientManager>,
+    active_profiles: State<ActiveProfiles>,
+    confirmations: State<Confirmations>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
     info!("Starting delete_api_profile for profile: {}", profile_name);
 
     let profiles = database
@@ -231,20 +542,35 @@ pub fn delete_api_profile(profile_name: String, database: State<Database>) -> Re
         .map_err(|e| format!("Failed to list API profiles: {}", e))?;
 
     if profiles.len() == 1 {
-        return Err("Cannot delete the last profile".to_string());
+        return Err("Cannot delete the last profile".into());
     }
 
-    let is_default = profiles
-        .iter()
-        .find(|p| p.profile_name == profile_name)
-        .map(|p| p.is_default)
-        .unwrap_or(false);
+    let deleted = profiles.iter().find(|p| p.profile_name == profile_name);
+    let profile_id = deleted
+        .map(|p| p.id)
+        .ok_or_else(|| format!("API profile '{}' not found", profile_name))?;
+    let pin = Zeroizing::new(pin);
+    confirmations.confirm(
+        &database,
+        &confirmation_token,
+        &pin,
+        &DestructiveAction::DeleteApiProfile {
+            profile_name: profile_name.clone(),
+        },
+        profile_id,
+    )?;
+    let is_default = deleted.map(|p| p.is_default).unwrap_or(false);
 
     database.delete_api_profile(&profile_name).map_err(|e| {
         error!("Failed to delete API profile: {}", e);
         format!("Failed to delete API profile: {}", e)
     })?;
 
+    if let Some(profile) = deleted {
+        clients.invalidate(profile.id);
+        active_profiles.forget_profile(profile.id);
+    }
+
     if is_default {
         let new_default = profiles
             .iter()
@@ -264,31 +590,19 @@ pub fn delete_api_profile(profile_name: String, database: State<Database>) -> Re
 }
 
 #[tauri::command]
-pub fn set_default_profile(profile_name: String, database: State<Database>) -> Result<(), String> {
+pub fn set_default_profile(
+    profile_name: String,
+    database: State<Database>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Operator)?;
     info!("Setting default profile: {}", profile_name);
     database.set_default_profile(&profile_name).map_err(|e| {
         error!("Failed to set default profile: {}", e);
-        format!("Failed to set default profile: {}", e)
+        format!("Failed to set default profile: {}", e).into()
     })
 }
 
-#[tauri::command]
-pub async fn test_api_connection(
-    api_key: String,
-    api_secret: String,
-    api_url: String,
-    port: u16,
-) -> Result<bool, String> {
-    info!("Testing API connection to {}:{}", api_url, port);
-
-    if api_key.contains('+')
-        || api_key.contains('\\')
-        || api_secret.contains('+')
-        || api_secret.contains('\\')
-    {
-        info!("API key or secret contains special characters (+ or \\) that might require special handling");
-    }
-
+fn validate_api_url(api_url: &str) -> Result<(), String> {
     if api_url.ends_with('/') {
         return Err("Invalid URL format: URL should not end with a trailing slash".to_string());
     }
@@ -300,19 +614,15 @@ pub async fn test_api_connection(
         );
     }
 
-    let url = format!("{}:{}/api/diagnostics/system/systemTime", api_url, port);
-    info!("Making connection test request to {}", url);
+    Ok(())
+}
+
+/// Confirms the firewall answers an authenticated request with these settings
+async fn check_connection(client: &OpnsenseClient) -> Result<(), CommandError> {
+    let endpoint = "/api/diagnostics/system/systemTime";
+    info!("Making connection test request to {}", client.url(endpoint));
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(10),
-        Some(&api_key),
-        Some(&api_secret),
-    )
-    .await;
+    let response = client.send(ApiRequest::get(endpoint).timeout(10)).await;
 
     match response {
         Ok(resp) => {
@@ -321,51 +631,181 @@ pub async fn test_api_connection(
                 resp.status()
             );
             match resp.json::<Value>().await {
-                Ok(json) => {
+                Ok(_) => {
                     info!("Successfully parsed JSON response");
-                    Ok(true)
+                    Ok(())
                 }
                 Err(e) => {
                     error!("Connection succeeded but returned invalid data: {}", e);
-                    Err(format!(
-                        "Connection succeeded but returned invalid data: {}",
-                        e
-                    ))
+                    Err(format!("Connection succeeded but returned invalid data: {}", e).into())
                 }
             }
         }
         Err(e) => {
             error!("Connection test failed: {}", e);
-            Err(e)
+            Err(e.into())
         }
     }
 }
 
 #[tauri::command]
-pub fn get_dashboard_preferences(
-    database: State<Database>,
-) -> Result<HashMap<String, db::DashboardWidgetPref>, String> {
+pub async fn test_api_connection(
+    api_key: String,
+    api_secret: String,
+    api_url: String,
+    port: u16,
+    ca_chain_pem: Option<String>,
+    client_cert_pem: Option<String>,
+    client_key_pem: Option<Zeroizing<String>>,
+    proxy: Option<ProxySettings>,
+    ssh_tunnel: Option<SshTunnelSettings>,
+) -> Result<bool, CommandError> {
+    info!("Testing API connection to {}:{}", api_url, port);
+
+    if api_key.contains('+')
+        || api_key.contains('\\')
+        || api_secret.contains('+')
+        || api_secret.contains('\\')
+    {
+        info!("API key or secret contains special characters (+ or \\) that might require special handling");
+    }
+
+    validate_api_url(&api_url)?;
+
+    // Throwaway client: the profile isn't saved yet, so it must not enter the pool
+    let api_info = ApiInfo {
+        id: 0,
+        profile_name: String::new(),
+        api_key: api_key.into(),
+        api_secret: api_secret.into(),
+        api_url,
+        port,
+        is_default: false,
+        cert_fingerprint: None,
+        ca_chain_pem: non_empty(ca_chain_pem),
+        client_cert_pem: non_empty(client_cert_pem),
+        client_key_pem: non_empty(client_key_pem),
+        proxy: validated_proxy(proxy)?,
+        ssh_tunnel: validated_ssh_tunnel(ssh_tunnel)?,
+        read_only: false,
+    };
+    ensure_single_transport(&api_info)?;
+    let client = OpnsenseClient::new(&api_info)?;
+
+    check_connection(&client).await.map(|_| true)
+}
+
+#[derive(Serialize)]
+pub struct CertificateStatus {
+    pinned: Option<String>,
+    presented: Option<String>,
+    matches: bool,
+}
+
+/// Compares the pinned certificate with the one the firewall presents right now,
+/// so the UI can show both fingerprints before asking the user to re-trust
+#[tauri::command]
+pub async fn get_certificate_status(
+    profile_name: String,
+    database: State<'_, Database>,
+) -> Result<CertificateStatus, CommandError> {
     let api_info = database
-        .get_default_api_info()
+        .get_api_info(Some(&profile_name))
         .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+        .ok_or_else(|| format!("API profile '{}' not found", profile_name))?;
+
+    let pinned = api_info.cert_fingerprint.clone();
+    let probe = OpnsenseClient::new(&ApiInfo {
+        cert_fingerprint: None,
+        ..api_info
+    })?;
+    let presented = probe.probe_certificate().await?;
+
+    let matches = match (&pinned, &presented) {
+        (Some(pinned), Some(presented)) => tls::fingerprints_match(pinned, presented),
+        _ => false,
+    };
+
+    Ok(CertificateStatus {
+        pinned,
+        presented,
+        matches,
+    })
+}
+
+/// Replaces the pinned certificate after the user confirmed the new fingerprint
+#[tauri::command]
+pub async fn trust_certificate(
+    profile_name: String,
+    fingerprint: String,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    let api_info = database
+        .get_api_info(Some(&profile_name))
+        .map_err(|e| format!("Failed to get API info: {}", e))?
+        .ok_or_else(|| format!("API profile '{}' not found", profile_name))?;
+
+    if api_info.ca_chain_pem.is_some() {
+        return Err(
+            "This profile verifies the firewall against its CA chain, so no certificate is pinned"
+                .into(),
+        );
+    }
+
+    let profile_id = api_info.id;
+    let probe = OpnsenseClient::new(&ApiInfo {
+        cert_fingerprint: None,
+        ..api_info
+    })?;
+
+    // Only trust what the firewall is presenting now, not a stale fingerprint from the UI
+    match probe.probe_certificate().await? {
+        Some(presented) if tls::fingerprints_match(&presented, &fingerprint) => {
+            database
+                .set_cert_fingerprint(profile_id, Some(&presented))
+                .map_err(|e| format!("Failed to save certificate fingerprint: {}", e))?;
+        }
+        Some(presented) => {
+            return Err(format!(
+                "The firewall is presenting a different certificate ({}) than the one you confirmed. Please review it again.",
+                presented
+            ).into());
+        }
+        None => {
+            return Err("This profile does not use HTTPS, there is no certificate to trust".into())
+        }
+    }
+
+    clients.invalidate(profile_id);
+    info!("Re-trusted certificate for profile '{}'", profile_name);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_dashboard_preferences(
+    profile: SelectedProfile,
+    database: State<Database>,
+) -> Result<HashMap<String, db::DashboardWidgetPref>, CommandError> {
+    let api_info = profile.require_api_info(&database)?;
 
     database
         .get_dashboard_preferences(api_info.id)
-        .map_err(|e| format!("Failed to get dashboard preferences: {}", e))
+        .map_err(|e| format!("Failed to get dashboard preferences: {}", e).into())
 }
 
 #[tauri::command]
 pub fn save_dashboard_preferences(
     prefs: Vec<db::DashboardWidgetPref>,
+    profile: SelectedProfile,
     database: State<Database>,
-) -> Result<(), String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+) -> Result<(), CommandError> {
+    // The layout is shared by everyone using the profile
+    database.require_role(Role::Operator)?;
+    let api_info = profile.require_api_info(&database)?;
 
     database
         .save_dashboard_preferences(api_info.id, &prefs)
-        .map_err(|e| format!("Failed to save dashboard preferences: {}", e))
+        .map_err(|e| format!("Failed to save dashboard preferences: {}", e).into())
 }
@@ -0,0 +1,222 @@
+use log::{info, warn};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::active_profile::SelectedProfile;
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::local_users::Role;
+
+/// How long a confirmation token can be redeemed after it was issued
+pub const CONFIRMATION_TTL_SECS: u64 = 60;
+
+/// An operation that only runs with a confirmation token and a fresh PIN check
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DestructiveAction {
+    RebootFirewall,
+    FlushArpTable,
+    ActivateSnapshot { uuid: String },
+    StartUpdate,
+    DeleteApiProfile { profile_name: String },
+}
+
+impl DestructiveAction {
+    pub fn required_role(&self) -> Role {
+        match self {
+            DestructiveAction::FlushArpTable => Role::Operator,
+            _ => Role::Admin,
+        }
+    }
+
+    fn describe(&self, profile_name: &str) -> String {
+        match self {
+            DestructiveAction::RebootFirewall => {
+                format!("Reboot the firewall of profile '{}'", profile_name)
+            }
+            DestructiveAction::FlushArpTable => {
+                format!("Flush the ARP table of profile '{}'", profile_name)
+            }
+            DestructiveAction::ActivateSnapshot { uuid } => format!(
+                "Activate snapshot {} on profile '{}'; it takes effect after a reboot",
+                uuid, profile_name
+            ),
+            DestructiveAction::StartUpdate => format!(
+                "Install firmware updates on profile '{}', which may reboot the firewall",
+                profile_name
+            ),
+            DestructiveAction::DeleteApiProfile { .. } => {
+                format!(
+                    "Delete profile '{}' and its saved credentials",
+                    profile_name
+                )
+            }
+        }
+    }
+}
+
+/// What the UI shows before asking for the PIN
+#[derive(Serialize, Clone, Debug)]
+pub struct PendingConfirmation {
+    pub token: String,
+    pub action: DestructiveAction,
+    pub profile_id: i64,
+    pub profile_name: String,
+    pub summary: String,
+    pub expires_in_secs: u64,
+}
+
+struct Issued {
+    action: DestructiveAction,
+    profile_id: i64,
+    requested_by: String,
+    expires_at: Instant,
+}
+
+/// Confirmation tokens issued and not yet redeemed, kept in memory only
+pub struct Confirmations {
+    issued: Mutex<HashMap<String, Issued>>,
+    ttl: Duration,
+}
+
+impl Confirmations {
+    pub fn new() -> Self {
+        Self {
+            issued: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(CONFIRMATION_TTL_SECS),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn issue(
+        &self,
+        action: DestructiveAction,
+        profile_id: i64,
+        profile_name: String,
+        requested_by: String,
+    ) -> PendingConfirmation {
+        let token = format!("{:032x}", thread_rng().gen::<u128>());
+        let now = Instant::now();
+
+        let mut issued = self.issued.lock().unwrap();
+        issued.retain(|_, pending| pending.expires_at > now);
+        issued.insert(
+            token.clone(),
+            Issued {
+                action: action.clone(),
+                profile_id,
+                requested_by,
+                expires_at: now + self.ttl,
+            },
+        );
+
+        PendingConfirmation {
+            token,
+            summary: action.describe(&profile_name),
+            action,
+            profile_id,
+            profile_name,
+            expires_in_secs: self.ttl.as_secs(),
+        }
+    }
+
+    /// Redeems `token` for exactly `action` on `profile_id`, after checking the PIN
+    ///
+    /// A token works once, whether or not the PIN was right, and only for the user
+    /// who asked for it.
+    pub fn confirm(
+        &self,
+        database: &Database,
+        token: &str,
+        pin: &str,
+        action: &DestructiveAction,
+        profile_id: i64,
+    ) -> Result<(), String> {
+        let issued = self
+            .issued
+            .lock()
+            .unwrap()
+            .remove(token)
+            .ok_or_else(|| "This confirmation is unknown or was already used".to_string())?;
+
+        if issued.expires_at <= Instant::now() {
+            return Err("This confirmation has expired, please confirm again".to_string());
+        }
+        if issued.action != *action || issued.profile_id != profile_id {
+            warn!(
+                "Confirmation for {:?} on profile {} presented for {:?} on profile {}",
+                issued.action, issued.profile_id, action, profile_id
+            );
+            return Err("This confirmation was issued for a different operation".to_string());
+        }
+
+        let wrong_user =
+            || "Confirm with the PIN of the user who started this operation".to_string();
+        if database.signed_in_user().map(|user| user.name) != Some(issued.requested_by.clone()) {
+            return Err(wrong_user());
+        }
+        // Checked against the requester's own PIN, so confirming never switches the signed-in user
+        if !database.check_user_pin(&issued.requested_by, pin)? {
+            if database.pin_user_name(pin)?.is_some() {
+                return Err(wrong_user());
+            }
+            return Err("Incorrect PIN".to_string());
+        }
+
+        info!("Confirmed {:?} on profile {}", action, profile_id);
+        Ok(())
+    }
+
+    /// Like [`Self::confirm`], for an operation on the profile a command selected
+    pub fn confirm_selected(
+        &self,
+        database: &Database,
+        token: &str,
+        pin: &str,
+        action: &DestructiveAction,
+        profile: SelectedProfile,
+    ) -> Result<(), String> {
+        let profile_id = profile.require_api_info(database)?.id;
+        self.confirm(database, token, pin, action, profile_id)
+    }
+}
+
+/// First step of a destructive operation: describes it and returns the token to run it with
+///
+/// The selected profile is the target, except for `delete_api_profile`, which names its own.
+#[tauri::command]
+pub fn request_confirmation(
+    action: DestructiveAction,
+    profile: SelectedProfile,
+    database: State<Database>,
+    confirmations: State<Confirmations>,
+) -> Result<PendingConfirmation, CommandError> {
+    database.require_role(action.required_role())?;
+
+    let api_info = match &action {
+        DestructiveAction::DeleteApiProfile { profile_name } => database
+            .get_api_info(Some(profile_name))
+            .map_err(|e| format!("Failed to get API info: {}", e))?
+            .ok_or_else(|| format!("API profile '{}' not found", profile_name))?,
+        _ => {
+            profile.require_writable(&database)?;
+            profile.require_api_info(&database)?
+        }
+    };
+
+    let requested_by = database
+        .signed_in_user()
+        .map(|user| user.name)
+        .ok_or_else(|| "PIN authentication required. Please login again.".to_string())?;
+
+    Ok(confirmations.issue(action, api_info.id, api_info.profile_name, requested_by))
+}
@@ -1,5 +1,8 @@
+use crate::active_profile::SelectedProfile;
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::local_users::Role;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
@@ -38,6 +41,17 @@ pub struct Service {
     name: String,
 }
 
+impl ServicesResponse {
+    /// Names of the services that aren't running
+    pub fn stopped(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .filter(|service| service.running == 0)
+            .map(|service| service.name.clone())
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RestartServiceResponse {
     result: String,
@@ -51,121 +65,86 @@ pub struct SystemTime {
     loadavg: String,
 }
 
+impl SystemTime {
+    pub fn uptime(&self) -> &str {
+        &self.uptime
+    }
+}
+
 #[tauri::command]
-pub async fn get_gateway_status(database: State<'_, Database>) -> Result<GatewayStatus, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/routes/gateway/status",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_gateway_status(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<GatewayStatus, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    fetch_gateway_status(&client).await
+}
+
+pub async fn fetch_gateway_status(client: &OpnsenseClient) -> Result<GatewayStatus, CommandError> {
+    let response = client.get("/api/routes/gateway/status").await?;
 
     response
         .json::<GatewayStatus>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_services(database: State<'_, Database>) -> Result<ServicesResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/service/search",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_services(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<ServicesResponse, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    fetch_services(&client).await
+}
+
+pub async fn fetch_services(client: &OpnsenseClient) -> Result<ServicesResponse, CommandError> {
+    let response = client.get("/api/core/service/search").await?;
 
     response
         .json::<ServicesResponse>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn restart_service(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     service_id: String,
-) -> Result<RestartServiceResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/service/restart/{}",
-        api_info.api_url, api_info.port, service_id
-    );
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+) -> Result<RestartServiceResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/core/service/restart/{}", service_id);
+
+    let response = client.post(&endpoint, serde_json::json!({})).await?;
 
     response
         .json::<RestartServiceResponse>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_system_time(database: State<'_, Database>) -> Result<SystemTime, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/system/systemTime",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_system_time(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<SystemTime, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    fetch_system_time(&client).await
+}
+
+pub async fn fetch_system_time(client: &OpnsenseClient) -> Result<SystemTime, CommandError> {
+    let response = client.get("/api/diagnostics/system/systemTime").await?;
 
     response
         .json::<SystemTime>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
@@ -1,36 +1,150 @@
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    Algorithm, Argon2, Params, Version,
 };
 use chacha20poly1305::aead::Aead;
 use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
 use log::{error, info};
-use rusqlite::{params, types::Type, Connection, OptionalExtension, Result};
-use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
 };
 use tauri::Manager;
-
-use crate::pin_cache::PinCache;
-
+use zeroize::Zeroizing;
+
+use crate::audit_log::AuditLog;
+use crate::auto_lock::DEFAULT_IDLE_LOCK_MINUTES;
+use crate::change_journal::ChangeJournal;
+use crate::local_users::{LocalUser, LocalUserRecord, Role, Sealed, SignedInUser, OWNER_NAME};
+use crate::migrations::{self, MIGRATIONS};
+use crate::pin_lockout::PinLockout;
+use crate::proxy::ProxySettings;
+use crate::rate_limit::RateLimits;
+use crate::ssh_tunnel::SshTunnelSettings;
+
+/// Credentials encrypted with a plain SHA-256 of the PIN, before key derivation was used
+const KEY_VERSION_SHA256: i64 = 1;
+/// Credentials encrypted with an Argon2id key derived from the PIN and `pin_salt`
+const KEY_VERSION_ARGON2: i64 = 2;
+
+/// Argon2id cost of the credential key; OWASP's recommended minimum keeps unlocking
+/// quick on phones while making each offline PIN guess expensive
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+/// Run after the credentials are erased, to drop anything built from them
+type WipeHook = Box<dyn Fn() + Send + Sync>;
+
+/// Cheap to clone; clones share the connection and the unlocked key
+#[derive(Clone)]
 pub struct Database {
     conn: Arc<Mutex<Connection>>,
-    current_pin_key: Arc<Mutex<Option<Vec<u8>>>>,
-    pin_cache: Arc<PinCache>,
+    current_pin_key: Arc<Mutex<Option<Zeroizing<Vec<u8>>>>>,
+    signed_in: Arc<Mutex<Option<SignedInUser>>>,
+    wipe_hooks: Arc<Mutex<Vec<WipeHook>>>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ApiInfo {
     pub id: i64,
     pub profile_name: String,
-    pub api_key: String,
-    pub api_secret: String,
+    pub api_key: Zeroizing<String>,
+    pub api_secret: Zeroizing<String>,
     pub api_url: String,
     pub port: u16,
     pub is_default: bool,
+    /// SHA-256 fingerprint of the firewall certificate, pinned on first connect
+    #[serde(default)]
+    pub cert_fingerprint: Option<String>,
+    /// PEM CA chain for strict verification, replaces pinning when set
+    #[serde(default)]
+    pub ca_chain_pem: Option<String>,
+    /// PEM client certificate and key for firewalls behind an mTLS proxy
+    #[serde(default)]
+    pub client_cert_pem: Option<String>,
+    #[serde(default)]
+    pub client_key_pem: Option<Zeroizing<String>>,
+    /// HTTP or SOCKS5 proxy the firewall is reached through
+    #[serde(default)]
+    pub proxy: Option<ProxySettings>,
+    /// SSH server the API is port-forwarded through when it isn't exposed directly
+    #[serde(default)]
+    pub ssh_tunnel: Option<SshTunnelSettings>,
+    /// Refuses every request that would change the firewall, for observation-only profiles
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl ApiInfo {
+    /// Blanks the API secret and the TLS, proxy and SSH secrets, keeping everything else
+    pub fn redact_secrets(&mut self) {
+        self.api_secret = Zeroizing::default();
+        self.client_key_pem = None;
+        if let Some(proxy) = self.proxy.as_mut() {
+            proxy.password = None;
+        }
+        if let Some(tunnel) = self.ssh_tunnel.as_mut() {
+            tunnel.password = None;
+            tunnel.private_key = None;
+            tunnel.passphrase = None;
+        }
+    }
+
+    fn proxy_json(&self) -> Option<Zeroizing<String>> {
+        to_json_setting(&self.proxy)
+    }
+
+    fn ssh_tunnel_json(&self) -> Option<Zeroizing<String>> {
+        to_json_setting(&self.ssh_tunnel)
+    }
+}
+
+/// Structured connection settings are stored as JSON in a single column
+fn to_json_setting<T: Serialize>(value: &Option<T>) -> Option<Zeroizing<String>> {
+    value
+        .as_ref()
+        .and_then(|value| serde_json::to_string(value).ok())
+        .map(Zeroizing::new)
+}
+
+fn parse_json_setting<T: DeserializeOwned>(
+    json: Option<Zeroizing<String>>,
+    label: &str,
+) -> Option<T> {
+    let json = json?;
+    match serde_json::from_str(&json) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!("Ignoring invalid {}: {}", label, e);
+            None
+        }
+    }
+}
+
+/// Ciphertext and nonce of an optional encrypted column pair
+type EncryptedField = Option<(Vec<u8>, Vec<u8>)>;
+
+struct EncryptedApiInfoRow {
+    id: i64,
+    profile_name: String,
+    encrypted_api_key: Vec<u8>,
+    api_key_nonce: Vec<u8>,
+    encrypted_api_secret: Vec<u8>,
+    api_secret_nonce: Vec<u8>,
+    api_url: String,
+    port: u16,
+    is_default: bool,
+    cert_fingerprint: Option<String>,
+    ca_chain: EncryptedField,
+    client_cert: EncryptedField,
+    client_key: EncryptedField,
+    proxy: EncryptedField,
+    ssh_tunnel: EncryptedField,
+    read_only: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -52,92 +166,82 @@ impl Database {
 
         let conn = Connection::open(db_path)?;
 
-        let pin_cache = Arc::new(PinCache::new());
-
-        let db = Database {
-            conn: Arc::new(Mutex::new(conn)),
-            current_pin_key: Arc::new(Mutex::new(None)),
-            pin_cache,
-        };
-        db.initialize_tables()?;
-        db.migrate_data()?;
+        Self::from_connection(conn)
+    }
 
-        Ok(db)
+    /// Fresh database that lives only as long as the test using it
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
     }
 
-    fn initialize_tables(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+    /// Opens an existing database file, such as one written by an older release
+    #[cfg(test)]
+    pub fn open(path: &std::path::Path) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS first_run (
-                id INTEGER PRIMARY KEY,
-                has_run BOOLEAN NOT NULL DEFAULT 0
-            )",
-            [],
-        )?;
+    fn from_connection(mut conn: Connection) -> Result<Self> {
+        migrations::migrate(&mut conn, MIGRATIONS)?;
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS app_settings (
-                id INTEGER PRIMARY KEY,
-                password_hash TEXT NOT NULL,
-                pin_salt TEXT NOT NULL DEFAULT ''
-            )",
-            [],
-        )?;
+        Ok(Database {
+            conn: Arc::new(Mutex::new(conn)),
+            current_pin_key: Arc::new(Mutex::new(None)),
+            signed_in: Arc::new(Mutex::new(None)),
+            wipe_hooks: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS dashboard_preferences (
-                id INTEGER PRIMARY KEY,
-                profile_id INTEGER NOT NULL,
-                widget_key TEXT NOT NULL,
-                visible BOOLEAN NOT NULL DEFAULT 1,
-                position INTEGER NOT NULL,
-                FOREIGN KEY(profile_id) REFERENCES api_info(id)
-            )",
-            [],
-        )?;
+    pub fn audit_log(&self) -> AuditLog {
+        AuditLog::new(self.conn.clone())
+    }
 
-        Ok(())
+    pub fn change_journal(&self) -> ChangeJournal {
+        ChangeJournal::new(self.conn.clone())
     }
 
-    fn derive_encryption_key(&self, pin: &str, salt: &str) -> Result<Vec<u8>, String> {
-        let salt = SaltString::from_b64(salt).map_err(|e| format!("Invalid salt: {}", e))?;
+    fn derive_encryption_key(&self, pin: &str, salt: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+        let params = Params::new(KDF_MEMORY_KIB, KDF_ITERATIONS, KDF_PARALLELISM, Some(32))
+            .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(pin.as_bytes(), &salt)
-            .map_err(|e| format!("Failed to hash PIN for encryption key: {}", e))?;
+        let mut key = Zeroizing::new(vec![0u8; 32]);
+        argon2
+            .hash_password_into(pin.as_bytes(), salt.as_bytes(), &mut key)
+            .map_err(|e| format!("Failed to derive encryption key: {}", e))?;
 
-        let hash_bytes = password_hash
-            .hash
-            .ok_or_else(|| "Failed to get hash bytes".to_string())?
-            .as_bytes()
-            .to_vec();
+        Ok(key)
+    }
 
-        Ok(hash_bytes)
+    /// Key used for credentials written before `KEY_VERSION_ARGON2`
+    fn legacy_encryption_key(pin: &str) -> Zeroizing<Vec<u8>> {
+        let mut hasher = Sha256::new();
+        hasher.update(pin.as_bytes());
+        Zeroizing::new(hasher.finalize().to_vec())
     }
 
-    fn set_current_pin_key(&self, key: Vec<u8>) {
+    fn set_current_pin_key(&self, key: Zeroizing<Vec<u8>>) {
         let mut current_key = self.current_pin_key.lock().unwrap();
         *current_key = Some(key);
     }
 
-    fn encrypt_string(&self, plaintext: &str, pin: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    fn encrypt_string(&self, plaintext: &str, key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        self.encrypt_bytes(plaintext.as_bytes(), key)
+    }
+
+    fn encrypt_bytes(&self, plaintext: &[u8], key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
         use rand::{thread_rng, Rng};
 
         let mut nonce_bytes = [0u8; 12];
         thread_rng().fill(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes);
 
-        let mut hasher = Sha256::new();
-        hasher.update(pin.as_bytes());
-        let key_bytes = hasher.finalize();
-        let key = Key::from_slice(&key_bytes);
+        let key = Key::from_slice(key);
 
         let cipher = ChaCha20Poly1305::new(key);
 
         let ciphertext = cipher
-            .encrypt(nonce, plaintext.as_bytes())
+            .encrypt(nonce, plaintext)
             .map_err(|e| format!("Encryption failed: {}", e))?;
 
         Ok((ciphertext, nonce_bytes.to_vec()))
@@ -147,247 +251,178 @@ impl Database {
         &self,
         ciphertext: &[u8],
         nonce_bytes: &[u8],
-        pin: &str,
-    ) -> Result<String, String> {
-        let mut hasher = Sha256::new();
-        hasher.update(pin.as_bytes());
-        let key_bytes = hasher.finalize();
-        let key = Key::from_slice(&key_bytes);
+        key: &[u8],
+    ) -> Result<Zeroizing<String>, String> {
+        let mut plaintext = self.decrypt_bytes(ciphertext, nonce_bytes, key)?;
+
+        String::from_utf8(std::mem::take(&mut *plaintext))
+            .map(Zeroizing::new)
+            .map_err(|e| format!("UTF-8 error: {}", e))
+    }
+
+    fn decrypt_bytes(
+        &self,
+        ciphertext: &[u8],
+        nonce_bytes: &[u8],
+        key: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, String> {
+        let key = Key::from_slice(key);
 
         let cipher = ChaCha20Poly1305::new(key);
 
         let nonce = Nonce::from_slice(nonce_bytes);
 
-        let plaintext = cipher
+        cipher
             .decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))?;
-
-        String::from_utf8(plaintext).map_err(|e| format!("UTF-8 error: {}", e))
+            .map(Zeroizing::new)
+            .map_err(|e| format!("Decryption failed: {}", e))
     }
 
-    fn migrate_data(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-
-        let has_api_key_column: bool = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='api_key'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )?;
-
-        let has_encrypted_api_key_column: bool = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='encrypted_api_key'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )?;
-
-        if has_api_key_column && !has_encrypted_api_key_column {
-            info!("Migrating from unencrypted to encrypted API info schema");
-
-            conn.execute(
-                "CREATE TABLE api_info_new (
-                    id INTEGER PRIMARY KEY,
-                    profile_name TEXT NOT NULL UNIQUE,
-                    encrypted_api_key BLOB NOT NULL,
-                    api_key_nonce BLOB NOT NULL,
-                    encrypted_api_secret BLOB NOT NULL,
-                    api_secret_nonce BLOB NOT NULL,
-                    api_url TEXT NOT NULL,
-                    port INTEGER NOT NULL,
-                    is_default BOOLEAN NOT NULL DEFAULT 0
-                )",
-                [],
-            )?;
-        }
-
-        let has_pin_salt_column: bool = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('app_settings') WHERE name='pin_salt'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )?;
-
-        if !has_pin_salt_column {
-            conn.execute(
-                "ALTER TABLE app_settings ADD COLUMN pin_salt TEXT NOT NULL DEFAULT ''",
-                [],
-            )?;
-        }
-
-        Ok(())
+    fn encrypt_optional(
+        &self,
+        plaintext: Option<&str>,
+        key: &[u8],
+    ) -> Result<EncryptedField, String> {
+        plaintext
+            .map(|value| self.encrypt_string(value, key))
+            .transpose()
     }
 
-    fn complete_migration(&self, pin: &str) -> Result<(), String> {
-        info!("Starting complete_migration process");
-
-        let (has_api_key_column, has_encrypted_api_key_column, has_api_info_new) =
-            {
-                let conn = self.conn.lock().unwrap();
-
-                let has_api_key = conn
-                    .query_row(
-                        "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='api_key'",
-                        [],
-                        |row| {
-                            let count: i64 = row.get(0)?;
-                            Ok(count > 0)
-                        },
-                    )
-                    .map_err(|e| format!("Failed to check for api_key column: {}", e))?;
-
-                let has_encrypted_api_key = conn.query_row(
-                "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='encrypted_api_key'",
-                [],
-                |row| {
-                    let count: i64 = row.get(0)?;
-                    Ok(count > 0)
-                },
-            ).map_err(|e| format!("Failed to check for encrypted_api_key column: {}", e))?;
-
-                let has_new_table = conn.query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='api_info_new'",
-                [],
-                |row| {
-                    let count: i64 = row.get(0)?;
-                    Ok(count > 0)
-                },
-            ).map_err(|e| format!("Failed to check for api_info_new table: {}", e))?;
-
-                (has_api_key, has_encrypted_api_key, has_new_table)
-            };
-
-        info!(
-            "Migration check: has_api_key={}, has_encrypted_api_key={}, has_api_info_new={}",
-            has_api_key_column, has_encrypted_api_key_column, has_api_info_new
-        );
-
-        if has_encrypted_api_key_column || !has_api_key_column {
-            info!("No migration needed - schema already using encryption or no data to migrate");
-            return Ok(());
-        }
-
-        {
-            let conn = self.conn.lock().unwrap();
-
-            info!("Setting up salt for encryption");
-            let salt_exists: bool = conn
-                .query_row(
-                    "SELECT COUNT(*) FROM app_settings WHERE id = 1 AND pin_salt != ''",
-                    [],
-                    |row| {
-                        let count: i64 = row.get(0)?;
-                        Ok(count > 0)
-                    },
-                )
-                .map_err(|e| format!("Failed to check for salt: {}", e))?;
-
-            if !salt_exists {
-                info!("Creating new salt for encryption");
-                let salt = SaltString::generate(&mut OsRng).to_string();
-                conn.execute(
-                    "UPDATE app_settings SET pin_salt = ?1 WHERE id = 1",
-                    params![salt],
-                )
-                .map_err(|e| format!("Failed to update salt: {}", e))?;
+    fn decrypt_optional(
+        &self,
+        field: &EncryptedField,
+        key: &[u8],
+        label: &str,
+    ) -> Option<Zeroizing<String>> {
+        let (ciphertext, nonce) = field.as_ref()?;
+        match self.decrypt_string(ciphertext, nonce, key) {
+            Ok(decrypted) => Some(decrypted),
+            Err(e) => {
+                error!("Failed to decrypt {}: {}", label, e);
+                None
             }
         }
+    }
 
-        if !has_api_info_new {
-            let conn = self.conn.lock().unwrap();
-
-            info!("Creating new table for encrypted data");
-            conn.execute(
-                "CREATE TABLE api_info_new (
-                    id INTEGER PRIMARY KEY,
-                    profile_name TEXT NOT NULL UNIQUE,
-                    encrypted_api_key BLOB NOT NULL,
-                    api_key_nonce BLOB NOT NULL,
-                    encrypted_api_secret BLOB NOT NULL,
-                    api_secret_nonce BLOB NOT NULL,
-                    api_url TEXT NOT NULL,
-                    port INTEGER NOT NULL,
-                    is_default BOOLEAN NOT NULL DEFAULT 0
-                )",
+    /// Credentials are still encrypted with the SHA-256 PIN key
+    fn has_legacy_credentials(conn: &Connection) -> Result<bool, String> {
+        let key_version: i64 = conn
+            .query_row(
+                "SELECT key_version FROM app_settings WHERE id = 1",
                 [],
+                |row| row.get(0),
             )
-            .map_err(|e| format!("Failed to create new table: {}", e))?;
-        }
+            .map_err(|e| format!("Failed to read key version: {}", e))?;
 
-        let profiles = {
-            let conn = self.conn.lock().unwrap();
+        Ok(key_version == KEY_VERSION_SHA256)
+    }
 
-            info!("Reading data from unencrypted table");
-            let mut stmt = conn.prepare(
-                "SELECT id, profile_name, api_key, api_secret, api_url, port, is_default FROM api_info"
-            ).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    /// Re-encrypts credentials still stored under the SHA-256 PIN key with the Argon2 key
+    ///
+    /// Everything is rewritten in one transaction, so an interrupted upgrade leaves all
+    /// rows on the old key and is simply retried on the next unlock.
+    fn upgrade_encryption_key(&self, pin: &str, key: &[u8]) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
 
-            let rows = stmt
-                .query_map([], |row| {
-                    Ok((
-                        row.get::<_, i64>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, String>(3)?,
-                        row.get::<_, String>(4)?,
-                        row.get::<_, i64>(5)?,
-                        row.get::<_, bool>(6)?,
-                    ))
-                })
-                .map_err(|e| format!("Failed to query old data: {}", e))?;
+        if !Self::has_legacy_credentials(&conn)? {
+            return Ok(());
+        }
 
-            rows.collect::<Result<Vec<_>, _>>()
-                .map_err(|e| format!("Failed to collect profiles: {}", e))?
+        info!("Re-encrypting credentials with the Argon2 derived key");
+        let legacy_key = Self::legacy_encryption_key(pin);
+        let reencrypt = |ciphertext: &[u8], nonce: &[u8]| {
+            let plaintext = self.decrypt_string(ciphertext, nonce, &legacy_key)?;
+            self.encrypt_string(&plaintext, key)
+        };
+        let reencrypt_optional = |field: &EncryptedField| {
+            field
+                .as_ref()
+                .map(|(ciphertext, nonce)| reencrypt(ciphertext, nonce))
+                .transpose()
+                .map(Option::unzip)
         };
 
-        info!("Found {} profiles to migrate", profiles.len());
-
-        for (id, profile_name, api_key, api_secret, api_url, port, is_default) in profiles {
-            info!("Encrypting data for profile: {}", profile_name);
-            let (encrypted_api_key, api_key_nonce) = self.encrypt_string(&api_key, pin)?;
-            let (encrypted_api_secret, api_secret_nonce) = self.encrypt_string(&api_secret, pin)?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
 
-            {
-                let conn = self.conn.lock().unwrap();
-                info!("Inserting encrypted data for profile: {}", profile_name);
-                conn.execute(
-                    "INSERT INTO api_info_new (id, profile_name, encrypted_api_key, api_key_nonce, 
-                     encrypted_api_secret, api_secret_nonce, api_url, port, is_default) 
-                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-                    params![
-                        id,
-                        profile_name,
-                        encrypted_api_key,
-                        api_key_nonce,
-                        encrypted_api_secret,
-                        api_secret_nonce,
-                        api_url,
-                        port,
-                        is_default
-                    ],
+        let rows = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT id, profile_name, encrypted_api_key, api_key_nonce, encrypted_api_secret, api_secret_nonce, api_url, port, is_default, cert_fingerprint, encrypted_ca_chain, ca_chain_nonce, encrypted_client_cert, client_cert_nonce, encrypted_client_key, client_key_nonce, encrypted_proxy, proxy_nonce, encrypted_ssh_tunnel, ssh_tunnel_nonce, read_only FROM api_info",
                 )
-                .map_err(|e| format!("Failed to insert encrypted data: {}", e))?;
-            }
-        }
-
-        {
-            let conn = self.conn.lock().unwrap();
+                .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+            let rows = stmt
+                .query_map([], |row| self.row_to_encrypted_api_info(row))
+                .map_err(|e| format!("Failed to query credentials: {}", e))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to read credentials: {}", e))?;
+            rows
+        };
 
-            info!("Finalizing migration by replacing tables");
-            conn.execute("DROP TABLE api_info", [])
-                .map_err(|e| format!("Failed to drop old table: {}", e))?;
+        for row in rows {
+            let label = |e: String| format!("Profile '{}': {}", row.profile_name, e);
+            let (encrypted_api_key, api_key_nonce) =
+                reencrypt(&row.encrypted_api_key, &row.api_key_nonce).map_err(label)?;
+            let (encrypted_api_secret, api_secret_nonce) =
+                reencrypt(&row.encrypted_api_secret, &row.api_secret_nonce).map_err(label)?;
+            let (encrypted_ca_chain, ca_chain_nonce) =
+                reencrypt_optional(&row.ca_chain).map_err(label)?;
+            let (encrypted_client_cert, client_cert_nonce) =
+                reencrypt_optional(&row.client_cert).map_err(label)?;
+            let (encrypted_client_key, client_key_nonce) =
+                reencrypt_optional(&row.client_key).map_err(label)?;
+            let (encrypted_proxy, proxy_nonce) = reencrypt_optional(&row.proxy).map_err(label)?;
+            let (encrypted_ssh_tunnel, ssh_tunnel_nonce) =
+                reencrypt_optional(&row.ssh_tunnel).map_err(label)?;
 
-            conn.execute("ALTER TABLE api_info_new RENAME TO api_info", [])
-                .map_err(|e| format!("Failed to rename new table: {}", e))?;
+            tx.execute(
+                "UPDATE api_info SET
+                    encrypted_api_key = ?1,
+                    api_key_nonce = ?2,
+                    encrypted_api_secret = ?3,
+                    api_secret_nonce = ?4,
+                    encrypted_ca_chain = ?5,
+                    ca_chain_nonce = ?6,
+                    encrypted_client_cert = ?7,
+                    client_cert_nonce = ?8,
+                    encrypted_client_key = ?9,
+                    client_key_nonce = ?10,
+                    encrypted_proxy = ?11,
+                    proxy_nonce = ?12,
+                    encrypted_ssh_tunnel = ?13,
+                    ssh_tunnel_nonce = ?14
+                WHERE id = ?15",
+                params![
+                    encrypted_api_key,
+                    api_key_nonce,
+                    encrypted_api_secret,
+                    api_secret_nonce,
+                    encrypted_ca_chain,
+                    ca_chain_nonce,
+                    encrypted_client_cert,
+                    client_cert_nonce,
+                    encrypted_client_key,
+                    client_key_nonce,
+                    encrypted_proxy,
+                    proxy_nonce,
+                    encrypted_ssh_tunnel,
+                    ssh_tunnel_nonce,
+                    row.id
+                ],
+            )
+            .map_err(|e| format!("Failed to update credentials: {}", e))?;
         }
 
-        info!("Migration to encrypted API info completed successfully");
+        tx.execute(
+            "UPDATE app_settings SET key_version = ?1 WHERE id = 1",
+            params![KEY_VERSION_ARGON2],
+        )
+        .map_err(|e| format!("Failed to update key version: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit re-encrypted credentials: {}", e))?;
+
+        info!("Credentials re-encrypted with the Argon2 derived key");
         Ok(())
     }
 
@@ -415,266 +450,147 @@ impl Database {
         }
     }
 
-    pub fn save_initial_api_info(&self, api_info: &ApiInfo) -> Result<()> {
-        info!("Entering save_initial_api_info for first-time setup");
-        let conn = self.conn.lock().unwrap();
-
-        let table_exists: bool = conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='api_info'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )?;
-
-        if table_exists {
-            let has_encrypted_columns: bool = conn.query_row(
-                "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='encrypted_api_key'",
-                [],
-                |row| {
-                    let count: i64 = row.get(0)?;
-                    Ok(count > 0)
-                },
-            )?;
-
-            if has_encrypted_columns {
-                info!("Dropping existing encrypted table for fresh setup");
-                conn.execute("DROP TABLE api_info", [])?;
-            }
-        }
-
-        info!("Creating fresh unencrypted table for initial setup");
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS api_info (
-                id INTEGER PRIMARY KEY,
-                profile_name TEXT NOT NULL UNIQUE,
-                api_key TEXT NOT NULL,
-                api_secret TEXT NOT NULL,
-                api_url TEXT NOT NULL,
-                port INTEGER NOT NULL,
-                is_default BOOLEAN NOT NULL DEFAULT 0
-            )",
-            [],
-        )?;
-
-        info!("Inserting first profile with unencrypted schema");
-        conn.execute(
-            "INSERT INTO api_info (profile_name, api_key, api_secret, api_url, port, is_default) 
-            VALUES (?1, ?2, ?3, ?4, ?5, 1)",
-            params![
-                api_info.profile_name,
-                api_info.api_key,
-                api_info.api_secret,
-                api_info.api_url,
-                api_info.port
-            ],
-        )?;
-
-        info!("Initial API info saved successfully");
-        Ok(())
-    }
     pub fn save_api_info(&self, api_info: &ApiInfo) -> Result<()> {
         info!(
             "Entering save_api_info for profile: {}",
             api_info.profile_name
         );
-        let conn = self.conn.lock().unwrap();
+        let key = self.encryption_key().map_err(|e| {
+            error!("Failed to get encryption key: {}", e);
+            rusqlite::Error::InvalidParameterName(
+                "PIN authentication required. Please login again.".to_string(),
+            )
+        })?;
 
-        let table_exists: bool = conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='api_info'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )?;
+        let conn = self.conn.lock().unwrap();
+        self.write_api_info(&conn, api_info, &key)?;
 
-        let has_encrypted_columns: bool = if table_exists {
-            conn.query_row(
-                "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='encrypted_api_key'",
-                [],
-                |row| {
-                    let count: i64 = row.get(0)?;
-                    Ok(count > 0)
-                },
-            )?
-        } else {
-            false
-        };
+        info!("save_api_info completed successfully");
+        Ok(())
+    }
 
-        let has_unencrypted_columns: bool = if table_exists {
-            conn.query_row(
-                "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='api_key'",
-                [],
-                |row| {
-                    let count: i64 = row.get(0)?;
-                    Ok(count > 0)
-                },
-            )?
-        } else {
-            false
+    /// Encrypts and stores `api_info`, keeping the id of an existing profile with its name
+    fn write_api_info(&self, conn: &Connection, api_info: &ApiInfo, key: &[u8]) -> Result<i64> {
+        let encrypt = |value: Option<&str>, label: &str| {
+            self.encrypt_optional(value, key).map_err(|e| {
+                error!("Failed to encrypt {}: {}", label, e);
+                rusqlite::Error::InvalidParameterName(format!("Failed to encrypt {}", label))
+            })
         };
 
-        let is_first_profile: bool = conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='api_info'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count == 0)
-            },
-        )?;
-
-        info!("Table exists: {}, Has encrypted columns: {}, Has unencrypted columns: {}, Is first profile: {}", 
-              table_exists, has_encrypted_columns, has_unencrypted_columns, is_first_profile);
-
-        if !table_exists {
-            info!("Creating new unencrypted api_info table");
-            conn.execute(
-                "CREATE TABLE api_info (
-                    id INTEGER PRIMARY KEY,
-                    profile_name TEXT NOT NULL UNIQUE,
-                    api_key TEXT NOT NULL,
-                    api_secret TEXT NOT NULL,
-                    api_url TEXT NOT NULL,
-                    port INTEGER NOT NULL,
-                    is_default BOOLEAN NOT NULL DEFAULT 0
-                )",
-                [],
-            )?;
-
-            info!("Inserting first profile with unencrypted schema");
-            conn.execute(
-                "INSERT INTO api_info (profile_name, api_key, api_secret, api_url, port, is_default) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, 1)",
-                params![api_info.profile_name,api_info.api_key,api_info.api_secret,api_info.api_url,api_info.port],
-            )?;
-
-            info!("First profile inserted successfully");
-            return Ok(());
-        }
-
-        if has_unencrypted_columns {
-            info!("Using existing unencrypted schema");
-            conn.execute(
-                "INSERT OR REPLACE INTO api_info (profile_name, api_key, api_secret, api_url, port, is_default) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                params![api_info.profile_name,api_info.api_key,api_info.api_secret,api_info.api_url,api_info.port,api_info.is_default],
-            )?;
-        } else if has_encrypted_columns {
-            info!("Using existing encrypted schema");
-
-            let pin_hash: Option<String> = conn
-                .query_row(
-                    "SELECT password_hash FROM app_settings WHERE id = 1",
-                    [],
-                    |row| row.get(0),
-                )
-                .optional()?;
-
-            if pin_hash.is_none() {
-                return Err(rusqlite::Error::InvalidParameterName(
-                    "No PIN available for encryption".to_string(),
-                ));
-            }
-
-            let pin = self.get_cached_pin().map_err(|e| {
-                error!("Failed to get cached PIN: {}", e);
-                rusqlite::Error::InvalidParameterName(
-                    "PIN authentication required. Please login again.".to_string(),
-                )
-            })?;
-
-            let (encrypted_api_key, api_key_nonce) =
-                self.encrypt_string(&api_info.api_key, &pin).map_err(|e| {
-                    error!("Failed to encrypt API key: {}", e);
-                    rusqlite::Error::InvalidParameterName("Failed to encrypt API key".to_string())
-                })?;
-
-            let (encrypted_api_secret, api_secret_nonce) = self
-                .encrypt_string(&api_info.api_secret, &pin)
-                .map_err(|e| {
-                    error!("Failed to encrypt API secret: {}", e);
-                    rusqlite::Error::InvalidParameterName(
-                        "Failed to encrypt API secret".to_string(),
-                    )
-                })?;
-
-            // Check if this profile already exists to preserve its ID
-            let existing_id: Option<i64> = conn
-                .query_row(
-                    "SELECT id FROM api_info WHERE profile_name = ?1",
-                    params![api_info.profile_name],
-                    |row| row.get(0),
-                )
-                .optional()?;
-
-            if let Some(id) = existing_id {
-                // Update the existing profile, preserving its ID
-                conn.execute(
-                    "UPDATE api_info SET 
-                        encrypted_api_key = ?1, 
-                        api_key_nonce = ?2, 
-                        encrypted_api_secret = ?3, 
-                        api_secret_nonce = ?4, 
-                        api_url = ?5, 
-                        port = ?6, 
-                        is_default = ?7
-                    WHERE id = ?8",
-                    params![
-                        encrypted_api_key,
-                        api_key_nonce,
-                        encrypted_api_secret,
-                        api_secret_nonce,
-                        api_info.api_url,
-                        api_info.port,
-                        api_info.is_default,
-                        id
-                    ],
-                )?;
-            } else {
-                // Insert a new profile
-                conn.execute(
-                    "INSERT INTO api_info (profile_name, encrypted_api_key, api_key_nonce, 
-                    encrypted_api_secret, api_secret_nonce, api_url, port, is_default) 
-                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                    params![
-                        api_info.profile_name,
-                        encrypted_api_key,
-                        api_key_nonce,
-                        encrypted_api_secret,
-                        api_secret_nonce,
-                        api_info.api_url,
-                        api_info.port,
-                        api_info.is_default
-                    ],
-                )?;
-            }
-        } else {
-            info!("Recreating table with unencrypted schema");
-            conn.execute("DROP TABLE IF EXISTS api_info", [])?;
-            conn.execute(
-                "CREATE TABLE api_info (
-                    id INTEGER PRIMARY KEY,
-                    profile_name TEXT NOT NULL UNIQUE,
-                    api_key TEXT NOT NULL,
-                    api_secret TEXT NOT NULL,
-                    api_url TEXT NOT NULL,
-                    port INTEGER NOT NULL,
-                    is_default BOOLEAN NOT NULL DEFAULT 0
-                )",
-                [],
-            )?;
+        let proxy = api_info.proxy_json();
+        let ssh_tunnel = api_info.ssh_tunnel_json();
+        let (encrypted_api_key, api_key_nonce) =
+            encrypt(Some(api_info.api_key.as_str()), "API key")?.unwrap_or_default();
+        let (encrypted_api_secret, api_secret_nonce) =
+            encrypt(Some(api_info.api_secret.as_str()), "API secret")?.unwrap_or_default();
+        let (encrypted_ca_chain, ca_chain_nonce) =
+            encrypt(api_info.ca_chain_pem.as_deref(), "CA chain")?.unzip();
+        let (encrypted_client_cert, client_cert_nonce) =
+            encrypt(api_info.client_cert_pem.as_deref(), "client certificate")?.unzip();
+        let (encrypted_client_key, client_key_nonce) = encrypt(
+            api_info.client_key_pem.as_deref().map(String::as_str),
+            "client key",
+        )?
+        .unzip();
+        let (encrypted_proxy, proxy_nonce) =
+            encrypt(proxy.as_deref().map(String::as_str), "proxy settings")?.unzip();
+        let (encrypted_ssh_tunnel, ssh_tunnel_nonce) = encrypt(
+            ssh_tunnel.as_deref().map(String::as_str),
+            "SSH tunnel settings",
+        )?
+        .unzip();
+
+        // Check if this profile already exists to preserve its ID
+        let existing_id: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM api_info WHERE profile_name = ?1",
+                params![api_info.profile_name],
+                |row| row.get(0),
+            )
+            .optional()?;
 
+        if let Some(id) = existing_id {
+            // Update the existing profile, preserving its ID
             conn.execute(
-                "INSERT INTO api_info (profile_name, api_key, api_secret, api_url, port, is_default) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, 1)",
-                params![api_info.profile_name,api_info.api_key,api_info.api_secret,api_info.api_url,api_info.port],
+                "UPDATE api_info SET
+                    encrypted_api_key = ?1,
+                    api_key_nonce = ?2,
+                    encrypted_api_secret = ?3,
+                    api_secret_nonce = ?4,
+                    api_url = ?5,
+                    port = ?6,
+                    is_default = ?7,
+                    cert_fingerprint = ?8,
+                    encrypted_ca_chain = ?9,
+                    ca_chain_nonce = ?10,
+                    encrypted_client_cert = ?11,
+                    client_cert_nonce = ?12,
+                    encrypted_client_key = ?13,
+                    client_key_nonce = ?14,
+                    encrypted_proxy = ?15,
+                    proxy_nonce = ?16,
+                    encrypted_ssh_tunnel = ?17,
+                    ssh_tunnel_nonce = ?18,
+                    read_only = ?19
+                WHERE id = ?20",
+                params![
+                    encrypted_api_key,
+                    api_key_nonce,
+                    encrypted_api_secret,
+                    api_secret_nonce,
+                    api_info.api_url,
+                    api_info.port,
+                    api_info.is_default,
+                    api_info.cert_fingerprint,
+                    encrypted_ca_chain,
+                    ca_chain_nonce,
+                    encrypted_client_cert,
+                    client_cert_nonce,
+                    encrypted_client_key,
+                    client_key_nonce,
+                    encrypted_proxy,
+                    proxy_nonce,
+                    encrypted_ssh_tunnel,
+                    ssh_tunnel_nonce,
+                    api_info.read_only,
+                    id
+                ],
             )?;
+            return Ok(id);
         }
 
-        info!("save_api_info completed successfully");
-        Ok(())
+        conn.execute(
+            "INSERT INTO api_info (profile_name, encrypted_api_key, api_key_nonce,
+            encrypted_api_secret, api_secret_nonce, api_url, port, is_default, cert_fingerprint,
+            encrypted_ca_chain, ca_chain_nonce, encrypted_client_cert, client_cert_nonce,
+            encrypted_client_key, client_key_nonce, encrypted_proxy, proxy_nonce,
+            encrypted_ssh_tunnel, ssh_tunnel_nonce, read_only)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+            params![
+                api_info.profile_name,
+                encrypted_api_key,
+                api_key_nonce,
+                encrypted_api_secret,
+                api_secret_nonce,
+                api_info.api_url,
+                api_info.port,
+                api_info.is_default,
+                api_info.cert_fingerprint,
+                encrypted_ca_chain,
+                ca_chain_nonce,
+                encrypted_client_cert,
+                client_cert_nonce,
+                encrypted_client_key,
+                client_key_nonce,
+                encrypted_proxy,
+                proxy_nonce,
+                encrypted_ssh_tunnel,
+                ssh_tunnel_nonce,
+                api_info.read_only
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
     }
 
     pub fn set_default_profile(&self, profile_name: &str) -> Result<()> {
@@ -703,166 +619,117 @@ impl Database {
         Ok(())
     }
 
-    fn get_cached_pin(&self) -> Result<String, String> {
-        if let Some(pin) = self.pin_cache.get_pin() {
-            log::info!(
-                "Successfully retrieved PIN from cache, length: {}",
-                pin.len()
-            );
-            return Ok(pin);
+    /// Drops the derived key, so credentials can't be decrypted until the PIN is verified again
+    pub fn lock(&self) {
+        *self.current_pin_key.lock().unwrap() = None;
+        *self.signed_in.lock().unwrap() = None;
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.current_pin_key.lock().unwrap().is_some()
+    }
+
+    /// Credential key derived when the PIN was last verified
+    fn encryption_key(&self) -> Result<Zeroizing<Vec<u8>>, String> {
+        if let Some(key) = self.current_pin_key.lock().unwrap().clone() {
+            return Ok(key);
         }
 
-        log::error!("PIN not found in cache");
+        log::error!("Encryption key not available");
         Err("User needs to authenticate first".to_string())
     }
 
     pub fn get_api_info(&self, profile_name: Option<&str>) -> Result<Option<ApiInfo>> {
         let conn = self.conn.lock().unwrap();
 
-        let has_encrypted_columns: bool = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='encrypted_api_key'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )?;
+        let query = match profile_name {
+            Some(_) => "SELECT id, profile_name, encrypted_api_key, api_key_nonce, encrypted_api_secret, api_secret_nonce, api_url, port, is_default, cert_fingerprint, encrypted_ca_chain, ca_chain_nonce, encrypted_client_cert, client_cert_nonce, encrypted_client_key, client_key_nonce, encrypted_proxy, proxy_nonce, encrypted_ssh_tunnel, ssh_tunnel_nonce, read_only FROM api_info WHERE profile_name = ?1",
+            None => "SELECT id, profile_name, encrypted_api_key, api_key_nonce, encrypted_api_secret, api_secret_nonce, api_url, port, is_default, cert_fingerprint, encrypted_ca_chain, ca_chain_nonce, encrypted_client_cert, client_cert_nonce, encrypted_client_key, client_key_nonce, encrypted_proxy, proxy_nonce, encrypted_ssh_tunnel, ssh_tunnel_nonce, read_only FROM api_info WHERE is_default = 1",
+        };
 
-        if has_encrypted_columns {
-            let query = match profile_name {
-                Some(_) => "SELECT id, profile_name, encrypted_api_key, api_key_nonce, encrypted_api_secret, api_secret_nonce, api_url, port, is_default FROM api_info WHERE profile_name = ?1",
-                None => "SELECT id, profile_name, encrypted_api_key, api_key_nonce, encrypted_api_secret, api_secret_nonce, api_url, port, is_default FROM api_info WHERE is_default = 1",
-            };
-
-            let mut stmt = conn.prepare(query)?;
-
-            let result = if let Some(name) = profile_name {
-                stmt.query_row(params![name], |row| {
-                    let id: i64 = row.get(0)?;
-                    let profile_name: String = row.get(1)?;
-                    let encrypted_api_key: Vec<u8> = row.get(2)?;
-                    let api_key_nonce: Vec<u8> = row.get(3)?;
-                    let encrypted_api_secret: Vec<u8> = row.get(4)?;
-                    let api_secret_nonce: Vec<u8> = row.get(5)?;
-                    let api_url: String = row.get(6)?;
-                    let port: u16 = row.get(7)?;
-                    let is_default: bool = row.get(8)?;
-
-                    Ok((
-                        id,
-                        profile_name,
-                        encrypted_api_key,
-                        api_key_nonce,
-                        encrypted_api_secret,
-                        api_secret_nonce,
-                        api_url,
-                        port,
-                        is_default,
-                    ))
-                })
-            } else {
-                stmt.query_row([], |row| {
-                    let id: i64 = row.get(0)?;
-                    let profile_name: String = row.get(1)?;
-                    let encrypted_api_key: Vec<u8> = row.get(2)?;
-                    let api_key_nonce: Vec<u8> = row.get(3)?;
-                    let encrypted_api_secret: Vec<u8> = row.get(4)?;
-                    let api_secret_nonce: Vec<u8> = row.get(5)?;
-                    let api_url: String = row.get(6)?;
-                    let port: u16 = row.get(7)?;
-                    let is_default: bool = row.get(8)?;
-
-                    Ok((
-                        id,
-                        profile_name,
-                        encrypted_api_key,
-                        api_key_nonce,
-                        encrypted_api_secret,
-                        api_secret_nonce,
-                        api_url,
-                        port,
-                        is_default,
-                    ))
-                })
-            };
+        let mut stmt = conn.prepare(query)?;
 
-            match result {
-                Ok((
-                    id,
-                    profile_name,
-                    encrypted_api_key,
-                    api_key_nonce,
-                    encrypted_api_secret,
-                    api_secret_nonce,
-                    api_url,
-                    port,
-                    is_default,
-                )) => {
-                    let pin = match self.get_cached_pin() {
-                        Ok(pin) => pin,
-                        Err(_) => {
-                            return Ok(Some(ApiInfo {
-                                id,
-                                profile_name,
-                                api_key: String::new(),
-                                api_secret: String::new(),
-                                api_url,
-                                port,
-                                is_default,
-                            }));
+        let result = if let Some(name) = profile_name {
+            stmt.query_row(params![name], |row| self.row_to_encrypted_api_info(row))
+        } else {
+            stmt.query_row([], |row| self.row_to_encrypted_api_info(row))
+        };
+
+        match result {
+            Ok(row) => {
+                let key = match self.encryption_key() {
+                    Ok(key) => key,
+                    Err(_) => {
+                        return Ok(Some(ApiInfo {
+                            id: row.id,
+                            profile_name: row.profile_name,
+                            api_key: Zeroizing::default(),
+                            api_secret: Zeroizing::default(),
+                            api_url: row.api_url,
+                            port: row.port,
+                            is_default: row.is_default,
+                            cert_fingerprint: row.cert_fingerprint,
+                            ca_chain_pem: None,
+                            client_cert_pem: None,
+                            client_key_pem: None,
+                            proxy: None,
+                            ssh_tunnel: None,
+                            read_only: row.read_only,
+                        }));
+                    }
+                };
+
+                // Decrypt the API key and secret
+                let api_key =
+                    match self.decrypt_string(&row.encrypted_api_key, &row.api_key_nonce, &key) {
+                        Ok(decrypted) => decrypted,
+                        Err(e) => {
+                            error!("Failed to decrypt API key: {}", e);
+                            Zeroizing::default()
                         }
                     };
 
-                    // Decrypt the API key and secret
-                    let api_key =
-                        match self.decrypt_string(&encrypted_api_key, &api_key_nonce, &pin) {
-                            Ok(decrypted) => decrypted,
-                            Err(e) => {
-                                error!("Failed to decrypt API key: {}", e);
-                                String::new()
-                            }
-                        };
-
-                    let api_secret =
-                        match self.decrypt_string(&encrypted_api_secret, &api_secret_nonce, &pin) {
-                            Ok(decrypted) => decrypted,
-                            Err(e) => {
-                                error!("Failed to decrypt API secret: {}", e);
-                                String::new()
-                            }
-                        };
-
-                    Ok(Some(ApiInfo {
-                        id,
-                        profile_name,
-                        api_key,
-                        api_secret,
-                        api_url,
-                        port,
-                        is_default,
-                    }))
-                }
-                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-                Err(e) => Err(e),
-            }
-        } else {
-            let query = match profile_name {
-                Some(_) => "SELECT id, profile_name, api_key, api_secret, api_url, port, is_default FROM api_info WHERE profile_name = ?1",
-                None => "SELECT id, profile_name, api_key, api_secret, api_url, port, is_default FROM api_info WHERE is_default = 1",
-            };
-
-            let mut stmt = conn.prepare(query)?;
-            let api_info = if let Some(name) = profile_name {
-                stmt.query_row(params![name], |row| self.row_to_api_info(row))
-            } else {
-                stmt.query_row([], |row| self.row_to_api_info(row))
-            };
-
-            match api_info {
-                Ok(info) => Ok(Some(info)),
-                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-                Err(e) => Err(e),
+                let api_secret = match self.decrypt_string(
+                    &row.encrypted_api_secret,
+                    &row.api_secret_nonce,
+                    &key,
+                ) {
+                    Ok(decrypted) => decrypted,
+                    Err(e) => {
+                        error!("Failed to decrypt API secret: {}", e);
+                        Zeroizing::default()
+                    }
+                };
+
+                Ok(Some(ApiInfo {
+                    id: row.id,
+                    profile_name: row.profile_name,
+                    api_key,
+                    api_secret,
+                    api_url: row.api_url,
+                    port: row.port,
+                    is_default: row.is_default,
+                    cert_fingerprint: row.cert_fingerprint,
+                    ca_chain_pem: self
+                        .decrypt_optional(&row.ca_chain, &key, "CA chain")
+                        .map(|pem| pem.to_string()),
+                    client_cert_pem: self
+                        .decrypt_optional(&row.client_cert, &key, "client certificate")
+                        .map(|pem| pem.to_string()),
+                    client_key_pem: self.decrypt_optional(&row.client_key, &key, "client key"),
+                    proxy: parse_json_setting(
+                        self.decrypt_optional(&row.proxy, &key, "proxy settings"),
+                        "proxy settings",
+                    ),
+                    ssh_tunnel: parse_json_setting(
+                        self.decrypt_optional(&row.ssh_tunnel, &key, "SSH tunnel settings"),
+                        "SSH tunnel settings",
+                    ),
+                    read_only: row.read_only,
+                }))
             }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
         }
     }
 
@@ -870,58 +737,90 @@ impl Database {
         self.get_api_info(None)
     }
 
-    fn row_to_api_info(&self, row: &rusqlite::Row) -> rusqlite::Result<ApiInfo> {
-        Ok(ApiInfo {
+    pub fn get_api_info_by_id(&self, profile_id: i64) -> Result<Option<ApiInfo>> {
+        let profile_name: Option<String> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT profile_name FROM api_info WHERE id = ?1",
+                params![profile_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match profile_name {
+            Some(profile_name) => self.get_api_info(Some(&profile_name)),
+            None => Ok(None),
+        }
+    }
+
+    fn row_to_encrypted_api_info(
+        &self,
+        row: &rusqlite::Row,
+    ) -> rusqlite::Result<EncryptedApiInfoRow> {
+        let encrypted_field = |data: usize, nonce: usize| -> rusqlite::Result<EncryptedField> {
+            let data: Option<Vec<u8>> = row.get(data)?;
+            let nonce: Option<Vec<u8>> = row.get(nonce)?;
+            Ok(data.zip(nonce))
+        };
+
+        Ok(EncryptedApiInfoRow {
             id: row.get(0)?,
             profile_name: row.get(1)?,
-            api_key: row.get(2)?,
-            api_secret: row.get(3)?,
-            api_url: row.get(4)?,
-            port: row.get(5)?,
-            is_default: row.get(6)?,
+            encrypted_api_key: row.get(2)?,
+            api_key_nonce: row.get(3)?,
+            encrypted_api_secret: row.get(4)?,
+            api_secret_nonce: row.get(5)?,
+            api_url: row.get(6)?,
+            port: row.get(7)?,
+            is_default: row.get(8)?,
+            cert_fingerprint: row.get(9)?,
+            ca_chain: encrypted_field(10, 11)?,
+            client_cert: encrypted_field(12, 13)?,
+            client_key: encrypted_field(14, 15)?,
+            proxy: encrypted_field(16, 17)?,
+            ssh_tunnel: encrypted_field(18, 19)?,
+            read_only: row.get(20)?,
         })
     }
 
     pub fn list_api_profiles(&self) -> Result<Vec<ApiInfo>> {
         let conn = self.conn.lock().unwrap();
 
-        let has_encrypted_columns: bool = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('api_info') WHERE name='encrypted_api_key'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
+        let mut stmt = conn.prepare(
+            "SELECT id, profile_name, api_url, port, is_default, cert_fingerprint, read_only FROM api_info ORDER BY profile_name"
         )?;
 
-        if has_encrypted_columns {
-            let mut stmt = conn.prepare(
-                "SELECT id, profile_name, api_url, port, is_default FROM api_info ORDER BY profile_name"
-            )?;
-
-            let rows = stmt.query_map([], |row| {
-                Ok(ApiInfo {
-                    id: row.get(0)?,
-                    profile_name: row.get(1)?,
-                    api_key: String::new(),
-                    api_secret: String::new(),
-                    api_url: row.get(2)?,
-                    port: row.get(3)?,
-                    is_default: row.get(4)?,
-                })
-            })?;
+        let rows = stmt.query_map([], |row| {
+            Ok(ApiInfo {
+                id: row.get(0)?,
+                profile_name: row.get(1)?,
+                api_key: Zeroizing::default(),
+                api_secret: Zeroizing::default(),
+                api_url: row.get(2)?,
+                port: row.get(3)?,
+                is_default: row.get(4)?,
+                cert_fingerprint: row.get(5)?,
+                ca_chain_pem: None,
+                client_cert_pem: None,
+                client_key_pem: None,
+                proxy: None,
+                ssh_tunnel: None,
+                read_only: row.get(6)?,
+            })
+        })?;
 
-            rows.collect::<Result<Vec<ApiInfo>, _>>()
-        } else {
-            let mut stmt = conn.prepare(
-                "SELECT id, profile_name, api_key, api_secret, api_url, port, is_default FROM api_info ORDER BY profile_name"
-            )?;
+        rows.collect::<Result<Vec<ApiInfo>, _>>()
+    }
 
-            let profiles = stmt
-                .query_map([], |row| self.row_to_api_info(row))?
-                .collect::<Result<Vec<ApiInfo>, _>>()?;
-            Ok(profiles)
-        }
+    pub fn set_cert_fingerprint(&self, profile_id: i64, fingerprint: Option<&str>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE api_info SET cert_fingerprint = ?1 WHERE id = ?2",
+            params![fingerprint, profile_id],
+        )?;
+        Ok(())
     }
 
     pub fn delete_api_profile(&self, profile_name: &str) -> Result<()> {
@@ -954,6 +853,11 @@ impl Database {
             params![profile_id],
         )?;
 
+        tx.execute(
+            "DELETE FROM rate_limits WHERE profile_id = ?1",
+            params![profile_id],
+        )?;
+
         // Now delete the profile itself
         tx.execute(
             "DELETE FROM api_info WHERE profile_name = ?1",
@@ -990,7 +894,7 @@ impl Database {
             .is_ok())
     }
 
-    pub fn verify_pin(&self, pin: &str) -> Result<bool> {
+    pub fn verify_pin(&self, pin: &str) -> Result<bool, String> {
         let conn = self.conn.lock().unwrap();
 
         let password_hash_result: rusqlite::Result<String> = conn.query_row(
@@ -1001,57 +905,217 @@ impl Database {
 
         match password_hash_result {
             Ok(password_hash) => {
-                let result = Self::verify_password(&password_hash, pin).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        0,
-                        Type::Text,
-                        Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            e.to_string(),
-                        )),
-                    )
-                })?;
-
-                if result {
-                    log::info!("PIN verified successfully, saving to cache");
-                    self.pin_cache.set_pin(pin.to_string());
-
-                    let salt: String = conn
-                        .query_row(
-                            "SELECT pin_salt FROM app_settings WHERE id = 1",
-                            [],
-                            |row| row.get(0),
-                        )
-                        .map_err(|e| {
-                            error!("Failed to get PIN salt: {}", e);
-                            e
-                        })?;
+                // Checked under the connection lock so parallel guesses are counted one by one
+                let now = chrono::Utc::now().timestamp();
+                PinLockout::load(&conn)
+                    .map_err(|e| format!("Failed to read PIN attempts: {}", e))?
+                    .check(now)?;
+
+                let result = Self::verify_password(&password_hash, pin)
+                    .map_err(|e| format!("Failed to check PIN: {}", e))?;
+
+                if !result {
+                    if let Some(user) = Self::find_local_user(&conn, pin)? {
+                        PinLockout::reset(&conn)
+                            .map_err(|e| format!("Failed to reset PIN attempts: {}", e))?;
+                        drop(conn);
+                        return self.unlock_as_local_user(user, pin);
+                    }
 
                     drop(conn);
+                    self.record_pin_failure(now)?;
+                    return Ok(false);
+                }
 
-                    match self.complete_migration(pin) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("Failed to complete migration: {}", e);
-                        }
-                    }
+                PinLockout::reset(&conn)
+                    .map_err(|e| format!("Failed to reset PIN attempts: {}", e))?;
 
-                    let key = self.derive_encryption_key(pin, &salt).map_err(|e| {
-                        rusqlite::Error::FromSqlConversionFailure(
-                            0,
-                            Type::Text,
-                            Box::new(std::io::Error::new(std::io::ErrorKind::Other, e)),
-                        )
-                    })?;
+                log::info!("PIN verified successfully, deriving encryption key");
 
-                    self.set_current_pin_key(key);
+                let salt: String = conn
+                    .query_row(
+                        "SELECT pin_salt FROM app_settings WHERE id = 1",
+                        [],
+                        |row| row.get(0),
+                    )
+                    .map_err(|e| format!("Failed to get PIN salt: {}", e))?;
+
+                drop(conn);
+
+                let key = self.derive_encryption_key(pin, &salt)?;
+
+                if let Err(e) = self.upgrade_encryption_key(pin, &key) {
+                    error!("Failed to re-encrypt credentials: {}", e);
                 }
 
-                Ok(result)
+                self.set_current_pin_key(key);
+                self.set_signed_in_user(Some(SignedInUser::owner()));
+
+                Ok(true)
             }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
-            Err(e) => Err(e),
+            Err(e) => Err(format!("Failed to read PIN hash: {}", e)),
+        }
+    }
+
+    /// Checks `pin` against `user_name`'s own PIN, leaving whoever is signed in as they are
+    ///
+    /// Wrong PINs count towards the lockout like failed unlocks do.
+    pub fn check_user_pin(&self, user_name: &str, pin: &str) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        PinLockout::load(&conn)
+            .map_err(|e| format!("Failed to read PIN attempts: {}", e))?
+            .check(now)?;
+
+        let password_hash: Option<String> = if user_name == OWNER_NAME {
+            conn.query_row(
+                "SELECT password_hash FROM app_settings WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+        } else {
+            conn.query_row(
+                "SELECT password_hash FROM local_users WHERE name = ?1",
+                params![user_name],
+                |row| row.get(0),
+            )
+        }
+        .optional()
+        .map_err(|e| format!("Failed to read PIN hash: {}", e))?;
+
+        let matches = match password_hash {
+            Some(hash) => Self::verify_password(&hash, pin)
+                .map_err(|e| format!("Failed to check PIN: {}", e))?,
+            None => false,
+        };
+
+        if matches {
+            PinLockout::reset(&conn).map_err(|e| format!("Failed to reset PIN attempts: {}", e))?;
+        } else {
+            drop(conn);
+            self.record_pin_failure(now)?;
+        }
+        Ok(matches)
+    }
+
+    /// Names the user `pin` belongs to, without signing them in or counting an attempt
+    pub fn pin_user_name(&self, pin: &str) -> Result<Option<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        let owner_hash: Option<String> = conn
+            .query_row(
+                "SELECT password_hash FROM app_settings WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read PIN hash: {}", e))?;
+
+        if let Some(hash) = owner_hash {
+            if Self::verify_password(&hash, pin)
+                .map_err(|e| format!("Failed to check PIN: {}", e))?
+            {
+                return Ok(Some(OWNER_NAME.to_string()));
+            }
+        }
+        Ok(Self::find_local_user(&conn, pin)?.map(|user| user.name))
+    }
+
+    /// Counts a wrong PIN and erases the credentials once the wipe threshold is reached
+    fn record_pin_failure(&self, now: i64) -> Result<(), String> {
+        let lockout = PinLockout::record_failure(&self.conn.lock().unwrap(), now)
+            .map_err(|e| format!("Failed to record PIN attempt: {}", e))?;
+        log::warn!("Failed PIN attempt {}", lockout.failed_attempts);
+
+        if lockout.should_wipe() {
+            self.wipe_credentials()?;
+            return Err(
+                "Too many failed PIN attempts. All stored credentials have been erased."
+                    .to_string(),
+            );
         }
+        Ok(())
+    }
+
+    pub fn pin_lockout(&self) -> Result<PinLockout, String> {
+        let conn = self.conn.lock().unwrap();
+        PinLockout::load(&conn).map_err(|e| format!("Failed to read PIN attempts: {}", e))
+    }
+
+    pub fn set_pin_wipe_threshold(&self, threshold: Option<u32>) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        PinLockout::set_wipe_after(&conn, threshold)
+            .map_err(|e| format!("Failed to save wipe threshold: {}", e))
+    }
+
+    pub fn get_idle_lock_minutes(&self) -> Result<u32, String> {
+        let conn = self.conn.lock().unwrap();
+        let minutes: Option<u32> = conn
+            .query_row(
+                "SELECT idle_lock_minutes FROM app_settings WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read idle lock setting: {}", e))?;
+        Ok(minutes.unwrap_or(DEFAULT_IDLE_LOCK_MINUTES))
+    }
+
+    pub fn set_idle_lock_minutes(&self, minutes: u32) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE app_settings SET idle_lock_minutes = ?1 WHERE id = 1",
+            params![minutes],
+        )
+        .map_err(|e| format!("Failed to save idle lock setting: {}", e))?;
+        Ok(())
+    }
+
+    /// Registers `hook` to run every time [`Self::wipe_credentials`] erases the credentials
+    pub fn on_wipe(&self, hook: impl Fn() + Send + Sync + 'static) {
+        self.wipe_hooks.lock().unwrap().push(Box::new(hook));
+    }
+
+    /// Erases every profile along with the PIN, returning the app to first-run setup
+    ///
+    /// The API audit log and change journal go too, since they name the profiles and
+    /// hold whole configs from before and after each change.
+    pub fn wipe_credentials(&self) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        for table in [
+            "api_info",
+            "dashboard_preferences",
+            "rate_limits",
+            "local_users",
+            "app_settings",
+            "first_run",
+            "api_audit_log",
+            "change_journal",
+        ] {
+            tx.execute(&format!("DROP TABLE IF EXISTS {}", table), [])
+                .map_err(|e| format!("Failed to erase {}: {}", table, e))?;
+        }
+        // The dropped tables are recreated by running the migrations again
+        tx.pragma_update(None, "user_version", 0)
+            .map_err(|e| format!("Failed to reset schema version: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to erase credentials: {}", e))?;
+
+        migrations::migrate(&mut conn, MIGRATIONS)
+            .map_err(|e| format!("Failed to recreate tables: {}", e))?;
+        drop(conn);
+
+        self.lock();
+        for hook in self.wipe_hooks.lock().unwrap().iter() {
+            hook();
+        }
+
+        log::warn!("All stored credentials were erased");
+        Ok(())
     }
 
     pub fn update_password_hash(&self, new_hash: &str) -> Result<()> {
@@ -1059,28 +1123,48 @@ impl Database {
 
         let salt = SaltString::generate(&mut OsRng).to_string();
 
-        conn.execute(
-            "INSERT OR REPLACE INTO app_settings (id, password_hash, pin_salt) VALUES (1, ?1, ?2)",
-            params![new_hash, salt],
+        // Keeps the PIN attempt counters, wipe threshold and lock timeout in the same row
+        let updated = conn.execute(
+            "UPDATE app_settings SET password_hash = ?1, pin_salt = ?2, key_version = ?3 WHERE id = 1",
+            params![new_hash, salt, KEY_VERSION_ARGON2],
         )?;
+        if updated == 0 {
+            conn.execute(
+                "INSERT INTO app_settings (id, password_hash, pin_salt, key_version) VALUES (1, ?1, ?2, ?3)",
+                params![new_hash, salt, KEY_VERSION_ARGON2],
+            )?;
+        }
 
         let mut current_key = self.current_pin_key.lock().unwrap();
         *current_key = None;
+        *self.signed_in.lock().unwrap() = None;
 
         Ok(())
     }
 
     pub fn update_pin(&self, current_pin: &str, new_pin: &str) -> Result<(), String> {
         // First verify the current PIN
-        if !self
-            .verify_pin(current_pin)
-            .map_err(|e| format!("Failed to verify current PIN: {}", e))?
-        {
+        if !self.verify_pin(current_pin)? {
             return Err("Current PIN is incorrect".to_string());
         }
 
+        // Credentials are encrypted with the owner's key, so only the owner PIN can change it
+        if !self.signed_in_user().is_some_and(|user| user.is_owner) {
+            return Err(
+                "Only the owner PIN can be changed here. Ask an admin to re-add your user with a new PIN."
+                    .to_string(),
+            );
+        }
+
         log::info!("Current PIN verified successfully, proceeding with PIN update");
 
+        // Credentials that couldn't be upgraded on unlock would be re-saved blank
+        if Self::has_legacy_credentials(&self.conn.lock().unwrap())? {
+            return Err(
+                "Stored credentials could not be upgraded to the new encryption key".to_string(),
+            );
+        }
+
         // Get all API profiles to re-encrypt
         let api_profiles = self
             .list_api_profiles()
@@ -1110,25 +1194,32 @@ impl Database {
         let new_hash =
             Self::hash_password(new_pin).map_err(|e| format!("Failed to hash new PIN: {}", e))?;
 
-        // Update the PIN hash in the database
+        // A fresh salt means the new key shares nothing with the old one
+        let new_salt = SaltString::generate(&mut OsRng).to_string();
+        let new_key = self.derive_encryption_key(new_pin, &new_salt)?;
+        let local_users = self.rewrap_local_users(&self.encryption_key()?, &new_key)?;
+
+        // Update the PIN hash and salt in the database
         {
-            let conn = self.conn.lock().unwrap();
-            conn.execute(
-                "UPDATE app_settings SET password_hash = ? WHERE id = 1",
-                params![new_hash],
+            let mut conn = self.conn.lock().unwrap();
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("Failed to start transaction: {}", e))?;
+            tx.execute(
+                "UPDATE app_settings SET password_hash = ?1, pin_salt = ?2, key_version = ?3 WHERE id = 1",
+                params![new_hash, new_salt, KEY_VERSION_ARGON2],
             )
             .map_err(|e| format!("Failed to update PIN hash: {}", e))?;
+            for (name, user_key_by_owner, credential_key) in &local_users {
+                LocalUserRecord::rewrap(&tx, name, user_key_by_owner, credential_key)
+                    .map_err(|e| format!("Failed to update key of user {}: {}", name, e))?;
+            }
+            tx.commit()
+                .map_err(|e| format!("Failed to update PIN hash: {}", e))?;
         }
 
-        // Clear encryption key from memory
-        {
-            let mut current_key = self.current_pin_key.lock().unwrap();
-            *current_key = None;
-        }
-
-        // Temporarily store the new PIN in the cache for re-encryption
-        let old_pin = self.pin_cache.get_pin().clone(); // Save old PIN value
-        self.pin_cache.set_pin(new_pin.to_string()); // Set new PIN for re-encryption
+        // Swap in the new key for re-encryption
+        let old_key = self.current_pin_key.lock().unwrap().replace(new_key);
 
         log::info!(
             "Re-saving {} profiles with new PIN",
@@ -1138,10 +1229,8 @@ impl Database {
         // Re-encrypt all API profiles with the new PIN
         for api_info in decrypted_profiles {
             self.save_api_info(&api_info).map_err(|e| {
-                // If we fail, restore the old PIN in the cache
-                if let Some(old) = &old_pin {
-                    self.pin_cache.set_pin(old.clone());
-                }
+                // If we fail, go back to the old key
+                *self.current_pin_key.lock().unwrap() = old_key.clone();
 
                 format!(
                     "Failed to save API info for profile '{}': {}",
@@ -1156,6 +1245,200 @@ impl Database {
 
         Ok(())
     }
+
+    pub fn signed_in_user(&self) -> Option<SignedInUser> {
+        self.signed_in.lock().unwrap().clone()
+    }
+
+    /// Records who verified their PIN; [`Self::lock`] clears it again
+    pub fn set_signed_in_user(&self, user: Option<SignedInUser>) {
+        *self.signed_in.lock().unwrap() = user;
+    }
+
+    /// Fails unless the signed-in user's role includes `role`
+    pub fn require_role(&self, role: Role) -> Result<(), String> {
+        match self.signed_in_user() {
+            Some(user) if user.role >= role => Ok(()),
+            Some(user) => Err(format!(
+                "This needs the {} role, but {} is signed in as {}",
+                role, user.name, user.role
+            )),
+            None => Err("User needs to authenticate first".to_string()),
+        }
+    }
+
+    fn find_local_user(conn: &Connection, pin: &str) -> Result<Option<LocalUserRecord>, String> {
+        let users = LocalUserRecord::list(conn)
+            .map_err(|e| format!("Failed to read local users: {}", e))?;
+        for user in users {
+            let matches = Self::verify_password(&user.password_hash, pin)
+                .map_err(|e| format!("Failed to check PIN: {}", e))?;
+            if matches {
+                return Ok(Some(user));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Recovers the owner's credential key through the user's own key
+    fn unlock_as_local_user(&self, user: LocalUserRecord, pin: &str) -> Result<bool, String> {
+        let pin_key = self.derive_encryption_key(pin, &user.pin_salt)?;
+        let (sealed, nonce) = &user.user_key_by_pin;
+        let user_key = self.decrypt_bytes(sealed, nonce, &pin_key)?;
+        let (sealed, nonce) = &user.credential_key;
+        let credential_key = self.decrypt_bytes(sealed, nonce, &user_key)?;
+
+        info!("PIN of local user {} verified", user.name);
+        self.set_current_pin_key(credential_key);
+        self.set_signed_in_user(Some(SignedInUser {
+            name: user.name,
+            role: user.role,
+            is_owner: false,
+        }));
+        Ok(true)
+    }
+
+    /// Wraps `new_key` for every local user, returning the rows to store with the new owner PIN
+    fn rewrap_local_users(
+        &self,
+        old_key: &[u8],
+        new_key: &[u8],
+    ) -> Result<Vec<(String, Sealed, Sealed)>, String> {
+        let users = LocalUserRecord::list(&self.conn.lock().unwrap())
+            .map_err(|e| format!("Failed to read local users: {}", e))?;
+
+        users
+            .into_iter()
+            .map(|user| {
+                let (sealed, nonce) = &user.user_key_by_owner;
+                let user_key = self.decrypt_bytes(sealed, nonce, old_key)?;
+                Ok((
+                    user.name,
+                    self.encrypt_bytes(&user_key, new_key)?,
+                    self.encrypt_bytes(new_key, &user_key)?,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn list_local_users(&self) -> Result<Vec<LocalUser>, String> {
+        let conn = self.conn.lock().unwrap();
+        let users = LocalUserRecord::list(&conn)
+            .map_err(|e| format!("Failed to read local users: {}", e))?;
+        Ok(users
+            .into_iter()
+            .map(|user| LocalUser {
+                name: user.name,
+                role: user.role,
+            })
+            .collect())
+    }
+
+    pub fn add_local_user(&self, name: &str, pin: &str, role: Role) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("User name cannot be empty".to_string());
+        }
+        if name.eq_ignore_ascii_case(OWNER_NAME) {
+            return Err(format!("'{}' is reserved for the setup PIN", name));
+        }
+        if pin.is_empty() || !pin.chars().all(|c| c.is_ascii_digit()) {
+            return Err("PIN must contain only numbers".to_string());
+        }
+
+        let credential_key = self.encryption_key()?;
+
+        // A PIN has to identify exactly one user
+        {
+            let conn = self.conn.lock().unwrap();
+            let owner_hash: String = conn
+                .query_row(
+                    "SELECT password_hash FROM app_settings WHERE id = 1",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to read PIN hash: {}", e))?;
+            let owner_matches = Self::verify_password(&owner_hash, pin)
+                .map_err(|e| format!("Failed to check PIN: {}", e))?;
+            if owner_matches || Self::find_local_user(&conn, pin)?.is_some() {
+                return Err("Another user already has this PIN".to_string());
+            }
+        }
+
+        let password_hash =
+            Self::hash_password(pin).map_err(|e| format!("Failed to hash PIN: {}", e))?;
+        let pin_salt = SaltString::generate(&mut OsRng).to_string();
+        let pin_key = self.derive_encryption_key(pin, &pin_salt)?;
+
+        let mut user_key = Zeroizing::new(vec![0u8; 32]);
+        {
+            use rand::{thread_rng, Rng};
+            thread_rng().fill(&mut user_key[..]);
+        }
+
+        let user = LocalUserRecord {
+            name: name.to_string(),
+            role,
+            password_hash,
+            pin_salt,
+            user_key_by_pin: self.encrypt_bytes(&user_key, &pin_key)?,
+            user_key_by_owner: self.encrypt_bytes(&user_key, &credential_key)?,
+            credential_key: self.encrypt_bytes(&credential_key, &user_key)?,
+        };
+
+        let conn = self.conn.lock().unwrap();
+        user.insert(&conn).map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(error, _)
+                if error.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                format!("A user named '{}' already exists", name)
+            }
+            e => format!("Failed to save user: {}", e),
+        })?;
+
+        info!("Added local user {} with role {}", name, role);
+        Ok(())
+    }
+
+    pub fn set_local_user_role(&self, name: &str, role: Role) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        let updated = LocalUserRecord::set_role(&conn, name, role)
+            .map_err(|e| format!("Failed to update user: {}", e))?;
+        if !updated {
+            return Err(format!("User '{}' not found", name));
+        }
+        drop(conn);
+
+        // Takes effect right away for whoever is signed in as that user
+        let mut signed_in = self.signed_in.lock().unwrap();
+        if let Some(user) = signed_in
+            .as_mut()
+            .filter(|user| !user.is_owner && user.name == name)
+        {
+            user.role = role;
+        }
+
+        info!("Changed role of local user {} to {}", name, role);
+        Ok(())
+    }
+
+    pub fn remove_local_user(&self, name: &str) -> Result<(), String> {
+        if self
+            .signed_in_user()
+            .is_some_and(|user| !user.is_owner && user.name == name)
+        {
+            return Err("You can't remove the user you're signed in as".to_string());
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let deleted = LocalUserRecord::delete(&conn, name)
+            .map_err(|e| format!("Failed to remove user: {}", e))?;
+        if !deleted {
+            return Err(format!("User '{}' not found", name));
+        }
+
+        info!("Removed local user {}", name);
+        Ok(())
+    }
     pub fn get_dashboard_preferences(
         &self,
         profile_id: i64,
@@ -1190,16 +1473,25 @@ impl Database {
     ) -> Result<()> {
         let mut conn = self.conn.lock().unwrap();
         let tx = conn.transaction()?;
+        Self::write_dashboard_preferences(&tx, profile_id, preferences)?;
+        tx.commit()?;
+        Ok(())
+    }
 
+    fn write_dashboard_preferences(
+        conn: &Connection,
+        profile_id: i64,
+        preferences: &[DashboardWidgetPref],
+    ) -> Result<()> {
         // Clear existing preferences for this profile
-        tx.execute(
+        conn.execute(
             "DELETE FROM dashboard_preferences WHERE profile_id = ?1",
             [profile_id],
         )?;
 
         // Insert new preferences
         for pref in preferences {
-            tx.execute(
+            conn.execute(
                 "INSERT INTO dashboard_preferences (profile_id, widget_key, visible, position) 
                  VALUES (?1, ?2, ?3, ?4)",
                 params![
@@ -1211,7 +1503,74 @@ impl Database {
             )?;
         }
 
-        tx.commit()?;
+        Ok(())
+    }
+
+    /// Saves imported profiles and their dashboard layouts in one transaction
+    ///
+    /// Returns the id of each profile in order; when one fails, none are saved.
+    pub fn import_profiles(
+        &self,
+        profiles: &[(ApiInfo, Vec<DashboardWidgetPref>)],
+    ) -> Result<Vec<i64>, String> {
+        let key = self.encryption_key()?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut ids = Vec::with_capacity(profiles.len());
+        for (api_info, dashboard) in profiles {
+            let id = self
+                .write_api_info(&tx, api_info, &key)
+                .map_err(|e| format!("Failed to import '{}': {}", api_info.profile_name, e))?;
+            if !dashboard.is_empty() {
+                Self::write_dashboard_preferences(&tx, id, dashboard)
+                    .map_err(|e| format!("Failed to import dashboard preferences: {}", e))?;
+            }
+            ids.push(id);
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to import profiles: {}", e))?;
+        Ok(ids)
+    }
+
+    pub fn get_rate_limits(&self, profile_id: i64) -> Result<Option<RateLimits>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT max_concurrent, requests_per_second, burst FROM rate_limits WHERE profile_id = ?1",
+            [profile_id],
+            |row| {
+                Ok(RateLimits {
+                    max_concurrent: row.get(0)?,
+                    requests_per_second: row.get(1)?,
+                    burst: row.get(2)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    /// Stores custom limits for a profile, or with `None` goes back to the defaults
+    pub fn save_rate_limits(&self, profile_id: i64, limits: Option<&RateLimits>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match limits {
+            Some(limits) => conn.execute(
+                "INSERT OR REPLACE INTO rate_limits (profile_id, max_concurrent, requests_per_second, burst)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    profile_id,
+                    limits.max_concurrent,
+                    limits.requests_per_second,
+                    limits.burst
+                ],
+            )?,
+            None => conn.execute(
+                "DELETE FROM rate_limits WHERE profile_id = ?1",
+                [profile_id],
+            )?,
+        };
         Ok(())
     }
 }
@@ -1,10 +1,15 @@
+use crate::active_profile::SelectedProfile;
+use crate::confirmation::{Confirmations, DestructiveAction};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::ClientManager;
+use crate::local_users::Role;
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 use tauri::State;
+use zeroize::Zeroizing;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Device {
@@ -58,48 +63,33 @@ pub struct FlushArpResponse {
     deleted: Vec<String>,
 }
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
-
 fn is_ipv6(ip: &str) -> bool {
     ip.contains(':')
 }
 
 #[tauri::command]
-pub async fn get_devices(database: State<'_, Database>) -> Result<Vec<Device>, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/diagnostics/interface/getArp");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_devices(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Vec<Device>, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/diagnostics/interface/getArp").await?;
 
     response
         .json::<Vec<Device>>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_ndp_devices(database: State<'_, Database>) -> Result<Vec<NdpDevice>, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/diagnostics/interface/search_ndp/");
+pub async fn get_ndp_devices(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Vec<NdpDevice>, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
     let payload = json!({
         "current": 1,
@@ -108,16 +98,9 @@ pub async fn get_ndp_devices(database: State<'_, Database>) -> Result<Vec<NdpDev
         "searchPhrase": ""
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .post("/api/diagnostics/interface/search_ndp/", payload)
+        .await?;
 
     let ndp_response = response
         .json::<NdpResponse>()
@@ -129,8 +112,10 @@ pub async fn get_ndp_devices(database: State<'_, Database>) -> Result<Vec<NdpDev
 
 #[tauri::command]
 pub async fn get_combined_devices(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Vec<CombinedDevice>, String> {
+    clients: State<'_, ClientManager>,
+) -> Result<Vec<CombinedDevice>, CommandError> {
     // Start time tracking for performance monitoring
     let start_time = std::time::Instant::now();
 
@@ -169,8 +154,8 @@ pub async fn get_combined_devices(
     let main_timeout = 30; // 30 seconds for main fetch
 
     // First, try to fetch both in parallel with the main timeout
-    let arp_future = get_devices(database.clone());
-    let ndp_future = get_ndp_devices(database.clone());
+    let arp_future = get_devices(profile, database.clone(), clients.clone());
+    let ndp_future = get_ndp_devices(profile, database.clone(), clients.clone());
 
     // Start the futures in parallel
     let (arp_result, ndp_result) = tokio::join!(
@@ -391,24 +376,29 @@ fn natural_sort(a: &str, b: &str) -> std::cmp::Ordering {
 }
 
 #[tauri::command]
-pub async fn flush_arp_table(database: State<'_, Database>) -> Result<FlushArpResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/diagnostics/interface/flushArp");
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn flush_arp_table(
+    confirmation_token: String,
+    pin: String,
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<FlushArpResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let pin = Zeroizing::new(pin);
+    confirmations.confirm_selected(
+        &database,
+        &confirmation_token,
+        &pin,
+        &DestructiveAction::FlushArpTable,
+        profile,
+    )?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .post("/api/diagnostics/interface/flushArp", json!({}))
+        .await?;
 
     let body = response
         .text()
@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::search_grid::{self, SearchPage, SearchQuery, SortOrder};
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
@@ -15,15 +20,6 @@ pub struct FirewallRule {
     interface: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct FirewallRulesResponse {
-    rows: Vec<FirewallRule>,
-    #[serde(rename = "rowCount")]
-    row_count: u32,
-    total: u32,
-    current: u32,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ToggleRuleResponse {
     result: String,
@@ -79,217 +75,150 @@ pub struct InterfaceListResponse {
     pub interfaces: InterfaceGroup,
 }
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
-
 #[tauri::command]
 pub async fn get_interface_list(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<InterfaceListResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/firewall/filter/get_interface_list");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<InterfaceListResponse, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .get("/api/firewall/filter/get_interface_list")
+        .await?;
 
     response
         .json::<InterfaceListResponse>()
         .await
-        .map_err(|e| format!("Failed to parse interface list response: {}", e))
+        .map_err(|e| format!("Failed to parse interface list response: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn check_api_version(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<bool, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-    
-    let url = build_api_url(&api_info, "/api/firewall/filter/get_interface_list");
-    
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(10), 
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    ).await;
- 
-    Ok(response.is_ok())
+    clients: State<'_, ClientManager>,
+) -> Result<bool, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let capabilities = clients.capabilities(&client).await;
+
+    Ok(capabilities.is_ok_and(|c| c.interface_rule_filter))
 }
 
 #[tauri::command]
 pub async fn get_firewall_rules(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     interface: Option<String>,
-) -> Result<FirewallRulesResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let is_new_api = check_api_version(database.clone()).await.unwrap_or(false);
+    current_page: u32,
+    row_count: u32,
+    sort: Option<BTreeMap<String, SortOrder>>,
+    search_phrase: String,
+) -> Result<SearchPage<FirewallRule>, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
-    let url = build_api_url(&api_info, "/api/firewall/filter/search_rule");
+    let endpoint = "/api/firewall/filter/search_rule";
 
-    let mut payload = serde_json::json!({
-        "current": 1,
-        "rowCount": -1,
-        "sort": {},
-        "searchPhrase": ""
-    });
+    let mut query = SearchQuery::new()
+        .page(current_page)
+        .rows(row_count)
+        .sorted_by(sort.unwrap_or_default())
+        .search(search_phrase);
 
     if let Some(iface) = interface {
         if !iface.is_empty() {
-            payload["interface"] = serde_json::Value::String(iface);
+            query = query.filter("interface", iface);
         }
     }
-    
-    println!("Getting firewall rules from URL: {}", url);
-    println!("With payload: {}", serde_json::to_string_pretty(&payload).unwrap_or_default());
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
 
-    response
-        .json::<FirewallRulesResponse>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+    Ok(search_grid::search(&client, endpoint, &query).await?)
 }
 
 #[tauri::command]
 pub async fn toggle_firewall_rule(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
-) -> Result<ToggleRuleResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let toggle_url = build_api_url(
-        &api_info,
-        &format!("/api/firewall/filter/toggleRule/{}", uuid),
-    );
+) -> Result<ToggleRuleResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let toggle_endpoint = format!("/api/firewall/filter/toggleRule/{}", uuid);
 
-    let toggle_response = make_http_request(
-        "POST",
-        &toggle_url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-
-    toggle_response
+    let before = fetch_rule(&client, &uuid).await.ok();
+
+    let toggle_response = client.post(&toggle_endpoint, serde_json::json!({})).await?;
+
+    let result = toggle_response
         .json::<ToggleRuleResponse>()
         .await
-        .map_err(|e| format!("Failed to parse toggle response: {}", e))
+        .map_err(|e| format!("Failed to parse toggle response: {}", e))?;
+
+    if result.changed {
+        Change::new(Module::Firewall, "toggle")
+            .target(&uuid)
+            .before(before)
+            .after(fetch_rule(&client, &uuid).await.ok())
+            .record(&database, &client);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
 pub async fn apply_firewall_changes(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<ApplyResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let apply_url = build_api_url(&api_info, "/api/firewall/filter/apply");
-
-    let apply_response = make_http_request(
-        "POST",
-        &apply_url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<ApplyResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let apply_response = client
+        .post("/api/firewall/filter/apply", serde_json::json!({}))
+        .await?;
 
     apply_response
         .json::<ApplyResponse>()
         .await
-        .map_err(|e| format!("Failed to parse apply response: {}", e))
+        .map_err(|e| format!("Failed to parse apply response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_rule_template(database: State<'_, Database>) -> Result<serde_json::Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/firewall/filter/get_rule/");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_rule_template(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<serde_json::Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/firewall/filter/get_rule/").await?;
 
     response
         .json::<serde_json::Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn add_firewall_rule(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     rule_data: serde_json::Value,
-) -> Result<AddRuleResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/firewall/filter/add_rule/");
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(rule_data),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+) -> Result<AddRuleResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .post("/api/firewall/filter/add_rule/", rule_data)
+        .await?;
 
     let response_text = response
         .text()
@@ -300,31 +229,37 @@ pub async fn add_firewall_rule(
 
     let add_result = match serde_json::from_str::<AddRuleResponse>(&response_text) {
         Ok(result) => result,
-        Err(e) => {
-            match serde_json::from_str::<serde_json::Value>(&response_text) {
-                Ok(value) => {
-                    let result = value
-                        .get("result")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("error")
-                        .to_string();
-
-                    AddRuleResponse {
-                        result,
-                        uuid: None,
-                        status: None,
-                        validations: Some(value),
-                    }
-                }
-                Err(_) => {
-                    return Err(format!("Failed to parse API response: {}", response_text));
+        Err(_) => match serde_json::from_str::<serde_json::Value>(&response_text) {
+            Ok(value) => {
+                let result = value
+                    .get("result")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("error")
+                    .to_string();
+
+                AddRuleResponse {
+                    result,
+                    uuid: None,
+                    status: None,
+                    validations: Some(value),
                 }
             }
-        }
+            Err(_) => {
+                return Err(format!("Failed to parse API response: {}", response_text).into());
+            }
+        },
     };
 
     if add_result.result == "saved" {
-        apply_firewall_changes(database).await?;
+        let mut change = Change::new(Module::Firewall, "add");
+        if let Some(uuid) = &add_result.uuid {
+            change = change
+                .target(uuid)
+                .after(fetch_rule(&client, uuid).await.ok());
+        }
+        change.record(&database, &client);
+
+        apply_firewall_changes(profile, database, clients).await?;
     }
 
     Ok(add_result)
@@ -332,126 +267,91 @@ pub async fn add_firewall_rule(
 
 #[tauri::command]
 pub async fn delete_firewall_rule(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
-) -> Result<serde_json::Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(
-        &api_info,
-        &format!("/api/firewall/filter/del_rule/{}", uuid),
-    );
+) -> Result<serde_json::Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/firewall/filter/del_rule/{}", uuid);
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let before = fetch_rule(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, serde_json::json!({})).await?;
 
     let result = response
         .json::<serde_json::Value>()
         .await
         .map_err(|e| format!("Failed to parse delete rule response: {}", e))?;
 
-    apply_firewall_changes(database).await?;
+    if result["result"].as_str() == Some("deleted") {
+        Change::new(Module::Firewall, "delete")
+            .target(&uuid)
+            .before(before)
+            .record(&database, &client);
+    }
+
+    apply_firewall_changes(profile, database, clients).await?;
 
     Ok(result)
 }
 
 #[tauri::command]
 pub async fn list_network_select_options(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<NetworkSelectOptions, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(
-        &api_info,
-        "/api/firewall/filter/list_network_select_options",
-    );
+    clients: State<'_, ClientManager>,
+) -> Result<NetworkSelectOptions, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .get("/api/firewall/filter/list_network_select_options")
+        .await?;
 
     response
         .json::<NetworkSelectOptions>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn get_rule(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
-) -> Result<serde_json::Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(
-        &api_info,
-        &format!("/api/firewall/filter/get_rule/{}", uuid),
-    );
+) -> Result<serde_json::Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/firewall/filter/get_rule/{}", uuid);
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await;
+    let response = client.get(&endpoint).await;
 
     match response {
-        Ok(resp) => {
-            resp.json::<serde_json::Value>()
-                .await
-                .map_err(|e| format!("Failed to parse response: {}", e))
-        },
-        Err(e) => {
-            Err(format!("Failed to get rule: {}", e))
-        }
+        Ok(resp) => resp
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e).into()),
+        Err(e) => Err(e.into()),
     }
 }
 
 #[tauri::command]
 pub async fn set_rule(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
     rule_data: serde_json::Value,
-) -> Result<serde_json::Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let is_new_api = check_api_version(database.clone()).await.unwrap_or(false);
+) -> Result<serde_json::Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
-    let url = build_api_url(
-        &api_info,
-        &format!("/api/firewall/filter/set_rule/{}", uuid),
-    );
+    let endpoint = format!("/api/firewall/filter/set_rule/{}", uuid);
 
     let actual_payload = match rule_data {
         serde_json::Value::Object(ref map) => {
@@ -460,24 +360,23 @@ pub async fn set_rule(
             } else {
                 serde_json::json!({ "rule": map })
             }
-        },
-        _ => serde_json::json!({ "rule": rule_data }) 
+        }
+        _ => serde_json::json!({ "rule": rule_data }),
     };
 
-    println!("Setting rule {} with URL: {}", uuid, url);
-    println!("Raw payload: {}", serde_json::to_string_pretty(&rule_data).unwrap_or_default());
-    println!("Actual payload sent: {}", serde_json::to_string_pretty(&actual_payload).unwrap_or_default());
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(actual_payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let before = fetch_rule(&client, &uuid).await.ok();
+
+    println!("Setting rule {} with URL: {}", uuid, client.url(&endpoint));
+    println!(
+        "Raw payload: {}",
+        serde_json::to_string_pretty(&rule_data).unwrap_or_default()
+    );
+    println!(
+        "Actual payload sent: {}",
+        serde_json::to_string_pretty(&actual_payload).unwrap_or_default()
+    );
+
+    let response = client.post(&endpoint, actual_payload).await?;
 
     let response_text = response
         .text()
@@ -489,15 +388,39 @@ pub async fn set_rule(
     let result = match serde_json::from_str::<serde_json::Value>(&response_text) {
         Ok(value) => value,
         Err(e) => {
-            return Err(format!("Failed to parse set rule response as JSON: {}. Raw response: {}", e, response_text));
+            return Err(format!(
+                "Failed to parse set rule response as JSON: {}. Raw response: {}",
+                e, response_text
+            )
+            .into());
         }
     };
 
     if let Some(result_field) = result.get("result") {
         if result_field.as_str() == Some("saved") {
-            apply_firewall_changes(database).await?;
+            Change::new(Module::Firewall, "set")
+                .target(&uuid)
+                .before(before)
+                .after(fetch_rule(&client, &uuid).await.ok())
+                .record(&database, &client);
+
+            apply_firewall_changes(profile, database, clients).await?;
         }
     }
 
     Ok(result)
 }
+
+async fn fetch_rule(
+    client: &OpnsenseClient,
+    uuid: &str,
+) -> Result<serde_json::Value, CommandError> {
+    let endpoint = format!("/api/firewall/filter/get_rule/{}", uuid);
+
+    let response = client.get(&endpoint).await?;
+
+    response
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
+}
@@ -1,5 +1,8 @@
+use crate::active_profile::SelectedProfile;
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ApiRequest, ClientManager};
+use crate::rate_limit::Priority;
 use log::error;
 use reqwest::header::{HeaderMap, ACCEPT};
 use serde::{Deserialize, Serialize};
@@ -98,96 +101,68 @@ pub fn register_log_cache(app: &mut tauri::App) -> Result<(), Box<dyn std::error
 }
 
 #[tauri::command]
-pub async fn get_log_filters(database: State<'_, Database>) -> Result<LogFilters, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/firewall/log_filters",
-        api_info.api_url, api_info.port
-    );
+pub async fn get_log_filters(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<LogFilters, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
     let mut headers = HeaderMap::new();
     headers.insert(ACCEPT, "application/json".parse().unwrap());
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        Some(headers),
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .send(ApiRequest::get("/api/diagnostics/firewall/log_filters").headers(headers))
+        .await?;
 
     response
         .json::<LogFilters>()
         .await
-        .map_err(|e| format!("Failed to parse log filters: {}", e))
+        .map_err(|e| format!("Failed to parse log filters: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_interface_names(database: State<'_, Database>) -> Result<InterfaceNames, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/interface/getInterfaceNames",
-        api_info.api_url, api_info.port
-    );
+pub async fn get_interface_names(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<InterfaceNames, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
     let mut headers = HeaderMap::new();
     headers.insert(ACCEPT, "application/json".parse().unwrap());
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        Some(headers),
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .send(ApiRequest::get("/api/diagnostics/interface/getInterfaceNames").headers(headers))
+        .await?;
 
     response
         .json::<InterfaceNames>()
         .await
-        .map_err(|e| format!("Failed to parse interface names: {}", e))
+        .map_err(|e| format!("Failed to parse interface names: {}", e).into())
 }
 
 async fn fetch_firewall_logs(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     digest: &str,
-) -> Result<Vec<FirewallLog>, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/firewall/log/?digest={}&limit=500",
-        api_info.api_url, api_info.port, digest
-    );
+    priority: Priority,
+) -> Result<Vec<FirewallLog>, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/diagnostics/firewall/log/?digest={}&limit=500", digest);
 
     let mut headers = HeaderMap::new();
     headers.insert(ACCEPT, "application/json".parse().unwrap());
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        Some(headers),
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .send(
+            ApiRequest::get(&endpoint)
+                .headers(headers)
+                .priority(priority),
+        )
+        .await?;
 
     let response_text = response
         .text()
@@ -206,22 +181,26 @@ async fn fetch_firewall_logs(
                 "Failed to parse logs: {}. First 1000 chars of response: {}",
                 e,
                 &response_text.chars().take(1000).collect::<String>()
-            ))
+            )
+            .into())
         }
     }
 }
 
 #[tauri::command]
 pub async fn get_firewall_logs(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     log_cache: State<'_, Arc<Mutex<LogCache>>>,
-) -> Result<Vec<FirewallLog>, String> {
+) -> Result<Vec<FirewallLog>, CommandError> {
     let digest;
     {
         let cache = log_cache.lock().unwrap();
         digest = cache.last_digest.clone();
     }
-    let new_logs = fetch_firewall_logs(database, &digest).await?;
+    let new_logs =
+        fetch_firewall_logs(profile, database, clients, &digest, Priority::Interactive).await?;
 
     let mut cache = log_cache.lock().unwrap();
 
@@ -296,7 +275,7 @@ pub fn update_log_filters(
     interface: String,
     direction: String,
     limit: Option<usize>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let mut cache = log_cache.lock().unwrap();
 
     cache.filter_criteria = LogFilterCriteria {
@@ -311,9 +290,10 @@ pub fn update_log_filters(
 
 #[tauri::command]
 pub fn start_log_polling(
+    profile: SelectedProfile,
     window: Window,
     log_cache: State<'_, Arc<Mutex<LogCache>>>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     {
         let mut cache = log_cache.lock().unwrap();
         cache.active_listeners += 1;
@@ -326,6 +306,7 @@ pub fn start_log_polling(
 
     let _poll_task = tauri::async_runtime::spawn(async move {
         let database = window_clone.state::<Database>();
+        let clients = window_clone.state::<ClientManager>();
 
         // Track consecutive empty responses to dynamically adjust polling rate
         let mut consecutive_empty_responses = 0;
@@ -354,7 +335,15 @@ pub fn start_log_polling(
             }
 
             // Fetch new logs using the latest digest
-            match fetch_firewall_logs(database.clone(), &digest).await {
+            match fetch_firewall_logs(
+                profile,
+                database.clone(),
+                clients.clone(),
+                &digest,
+                Priority::Background,
+            )
+            .await
+            {
                 Ok(new_logs) => {
                     if !new_logs.is_empty() {
                         // We have new logs, process them
@@ -486,7 +475,7 @@ pub fn start_log_polling(
 }
 
 #[tauri::command]
-pub fn stop_log_polling(log_cache: State<'_, Arc<Mutex<LogCache>>>) -> Result<(), String> {
+pub fn stop_log_polling(log_cache: State<'_, Arc<Mutex<LogCache>>>) -> Result<(), CommandError> {
     // Acquire the lock and decrement active_listeners
     let mut cache = log_cache.lock().unwrap();
 
@@ -513,7 +502,7 @@ pub fn stop_log_polling(log_cache: State<'_, Arc<Mutex<LogCache>>>) -> Result<()
 }
 
 #[tauri::command]
-pub fn clear_log_cache(log_cache: State<'_, Arc<Mutex<LogCache>>>) -> Result<(), String> {
+pub fn clear_log_cache(log_cache: State<'_, Arc<Mutex<LogCache>>>) -> Result<(), CommandError> {
     let mut cache = log_cache.lock().unwrap();
     cache.logs.clear();
     cache.last_digest = String::new();
@@ -0,0 +1,212 @@
+use crate::active_profile::SelectedProfile;
+use crate::dashboard::{self, GatewayStatus};
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::system_resources::{self, Memory};
+use crate::update_checker;
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+/// How long one firewall may take to answer before it's reported as unreachable
+pub const DEFAULT_FLEET_TIMEOUT_SECS: u64 = 15;
+
+/// Firmware, gateways, services, CPU, memory and uptime
+const STATUS_SECTIONS: usize = 6;
+
+#[derive(Serialize, Debug)]
+pub struct FleetOverview {
+    pub firewalls: Vec<FleetMember>,
+    pub reachable: usize,
+    pub unreachable: usize,
+}
+
+/// One saved profile in the overview; `status` is empty when `error` explains why
+#[derive(Serialize, Debug)]
+pub struct FleetMember {
+    pub profile_id: i64,
+    pub profile_name: String,
+    pub is_default: bool,
+    pub status: Option<FirewallStatus>,
+    pub error: Option<String>,
+}
+
+/// What one firewall reported; sections it couldn't answer are `None` and listed in `errors`
+#[derive(Serialize, Debug, Default)]
+pub struct FirewallStatus {
+    pub firmware_version: Option<String>,
+    pub updates_available: Option<bool>,
+    pub gateways: Option<GatewayStatus>,
+    pub stopped_services: Option<Vec<String>>,
+    pub cpu_usage_pct: Option<f64>,
+    pub memory: Option<Memory>,
+    pub uptime: Option<String>,
+    pub errors: Vec<String>,
+}
+
+/// Busy share of the CPU from the `CPU: ... 97.5% idle` line of `top`
+fn cpu_usage_pct(activity: &Value) -> Option<f64> {
+    let cpu_line = activity["headers"]
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .find(|line| line.trim_start().starts_with("CPU:"))?;
+
+    let idle = cpu_line
+        .split(',')
+        .find_map(|field| field.trim().strip_suffix("% idle"))?
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+
+    Some(((100.0 - idle) * 10.0).round() / 10.0)
+}
+
+async fn fetch_cpu_usage(client: &OpnsenseClient) -> Result<f64, CommandError> {
+    let activity: Value = client
+        .get("/api/diagnostics/activity/getActivity")
+        .await?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse activity: {}", e))?;
+
+    cpu_usage_pct(&activity).ok_or_else(|| "Activity has no CPU summary".into())
+}
+
+fn keep<T>(result: Result<T, CommandError>, section: &str, errors: &mut Vec<String>) -> Option<T> {
+    result
+        .map_err(|e| errors.push(format!("{}: {}", section, e)))
+        .ok()
+}
+
+/// Asks one firewall for everything in the overview at once
+///
+/// Fails only when no section could be read, so a missing plugin or endpoint
+/// doesn't hide the rest.
+async fn firewall_status(client: Arc<OpnsenseClient>) -> Result<FirewallStatus, CommandError> {
+    let (firmware, gateways, services, cpu, resources, time) = tokio::join!(
+        update_checker::firmware_status(&client),
+        dashboard::fetch_gateway_status(&client),
+        dashboard::fetch_services(&client),
+        fetch_cpu_usage(&client),
+        system_resources::fetch_system_resources(&client),
+        dashboard::fetch_system_time(&client),
+    );
+
+    let mut status = FirewallStatus::default();
+    let errors = &mut status.errors;
+
+    let firmware = keep(firmware, "firmware", errors);
+    status.firmware_version = firmware
+        .as_ref()
+        .and_then(|firmware| firmware["product_version"].as_str())
+        .map(str::to_string);
+    status.updates_available = firmware.as_ref().map(|firmware| {
+        firmware["updates_available"] == true
+            || firmware["has_major_upgrade"] == true
+            || firmware["has_minor_upgrade"] == true
+    });
+    status.gateways = keep(gateways, "gateways", errors);
+    status.stopped_services = keep(services, "services", errors).map(|services| services.stopped());
+    status.cpu_usage_pct = keep(cpu, "cpu", errors);
+    status.memory = keep(resources, "memory", errors).map(|resources| resources.into_memory());
+    status.uptime = keep(time, "uptime", errors).map(|time| time.uptime().to_string());
+
+    if status.errors.len() == STATUS_SECTIONS {
+        return Err(status.errors.remove(0).into());
+    }
+    Ok(status)
+}
+
+/// Connects to one profile and reads its status, both within `timeout`
+///
+/// Building the client can itself hang on the certificate probe of a firewall
+/// that is down, so it runs inside the timeout and the spawned task.
+async fn firewall_status_within<R: Runtime>(
+    app: AppHandle<R>,
+    profile_id: i64,
+    timeout: Duration,
+) -> Result<FirewallStatus, CommandError> {
+    let query = async {
+        let client = app
+            .state::<ClientManager>()
+            .client(
+                &app.state::<Database>(),
+                SelectedProfile::new(Some(profile_id)),
+            )
+            .await?;
+        firewall_status(client).await
+    };
+
+    tokio::time::timeout(timeout, query)
+        .await
+        .map_err(|_| format!("No answer within {} seconds", timeout.as_secs()))?
+}
+
+/// Status of every saved firewall, queried concurrently
+///
+/// Each firewall gets `timeout_secs` on its own; one that is down or slow is
+/// reported in its `error` instead of failing the overview.
+#[tauri::command]
+pub async fn get_fleet_overview<R: Runtime>(
+    timeout_secs: Option<u64>,
+    app: AppHandle<R>,
+    database: State<'_, Database>,
+) -> Result<FleetOverview, CommandError> {
+    let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_FLEET_TIMEOUT_SECS).max(1));
+    let profiles = database
+        .list_api_profiles()
+        .map_err(|e| format!("Failed to list API profiles: {}", e))?;
+
+    info!(
+        "Collecting fleet overview for {} profiles ({}s timeout)",
+        profiles.len(),
+        timeout.as_secs()
+    );
+
+    let queries: Vec<_> = profiles
+        .iter()
+        .map(|profile| {
+            tauri::async_runtime::spawn(firewall_status_within(app.clone(), profile.id, timeout))
+        })
+        .collect();
+
+    let mut firewalls = Vec::with_capacity(profiles.len());
+    for (profile, query) in profiles.into_iter().zip(queries) {
+        let result = query
+            .await
+            .unwrap_or_else(|e| Err(format!("Status query failed: {}", e).into()));
+        if let Err(e) = &result {
+            warn!(
+                "Fleet overview: {} is unreachable: {}",
+                profile.profile_name, e
+            );
+        }
+
+        let (status, error) = match result {
+            Ok(status) => (Some(status), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        firewalls.push(FleetMember {
+            profile_id: profile.id,
+            profile_name: profile.profile_name,
+            is_default: profile.is_default,
+            status,
+            error,
+        });
+    }
+
+    let reachable = firewalls
+        .iter()
+        .filter(|member| member.status.is_some())
+        .count();
+    Ok(FleetOverview {
+        unreachable: firewalls.len() - reachable,
+        reachable,
+        firewalls,
+    })
+}
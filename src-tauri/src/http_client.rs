@@ -1,272 +1,907 @@
 use base64::{engine::general_purpose, Engine as _};
-use log::{error, info};
+use log::{error, info, warn};
 use reqwest::{
-    header::{HeaderMap, AUTHORIZATION, HeaderValue, CONTENT_TYPE},
-    Client, Response,
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    Client, Method, Response,
 };
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 use serde_json::Value;
-use std::cmp::min;
-use std::time::Duration;
-
-/// Makes an HTTP request with a JSON payload
-pub async fn make_http_request(
-    request_type: &str,
-    url: &str,
-    payload: Option<Value>,
-    headers: Option<HeaderMap>,
-    timeout_seconds: Option<u64>,
-    api_key: Option<&str>,
-    api_secret: Option<&str>,
-) -> Result<Response, String> {
-    info!("Making a {} request to {}", request_type, url);
-
-    let client_builder = Client::builder().danger_accept_invalid_certs(true);
-    let client = if let Some(timeout_sec) = timeout_seconds {
-        client_builder
-            .timeout(Duration::from_secs(timeout_sec))
-            .build()
-    } else {
-        client_builder.build()
-    }
-    .map_err(|e| {
-        let error_message = format!("Failed to build HTTP client: {}", e);
-        error!("{}", error_message);
-        error_message
-    })?;
-
-    let mut request_builder = match request_type {
-        "GET" => client.get(url),
-        "POST" => client.post(url),
-        "PATCH" => client.patch(url),
-        "PUT" => client.put(url),
-        _ => {
-            let error_message = "Invalid request type".to_string();
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+use url::Url;
+use zeroize::Zeroizing;
+
+use crate::active_profile::SelectedProfile;
+use crate::audit_log::{AuditEntry, AuditLog};
+use crate::capabilities::{self, Capabilities};
+use crate::cassette::{Cassette, CassetteMode};
+use crate::db::{ApiInfo, Database};
+use crate::proxy::ProxySettings;
+use crate::rate_limit::{Priority, RateLimits, RequestLimiter, DEFAULT_LIMITS};
+use crate::read_only;
+use crate::retry::{self, RetryBudget};
+use crate::ssh_tunnel::{PinHostKey, SshTunnel, SshTunnelSettings};
+use crate::tls;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// Errors returned by the OPNsense HTTP layer
+///
+/// Serialized to the frontend as `{ "kind": "...", "message": "..." }` so the UI can
+/// branch on `kind` instead of matching on message text.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Authentication failed (HTTP 401): Your API key or secret is incorrect")]
+    Auth,
+    #[error(
+        "Permission denied (HTTP 403): Your API credentials don't have sufficient permissions"
+    )]
+    Permission,
+    #[error("API endpoint not found (HTTP 404): {hint}")]
+    NotFound { endpoint: String, hint: String },
+    #[error("Request to {url} failed with status {status}: {body}")]
+    Status {
+        url: String,
+        status: u16,
+        body: String,
+    },
+    #[error("Connection timed out: Server at {url} is unreachable or not responding. This may be due to high load on the firewall or network congestion.")]
+    Timeout { url: String },
+    #[error("{message}")]
+    Connection { url: String, message: String },
+    #[error("{message}")]
+    Tls { url: String, message: String },
+    #[error("The certificate presented by {url} has changed (pinned SHA-256 {pinned}, presented {presented}). If you replaced the firewall's certificate, re-trust it for this profile to continue.")]
+    CertificateChanged {
+        url: String,
+        pinned: String,
+        presented: String,
+    },
+    #[error("DNS resolution error: Could not resolve hostname in URL {url}. Please check your DNS settings and verify the hostname is correct.")]
+    Dns { url: String },
+    #[error("Failed to parse response: {0}")]
+    Parse(String),
+    /// `endpoint` is `None` when a command was refused before choosing a request
+    #[error("Profile '{profile}' is read-only and can't make changes on the firewall{}", not_sent(.endpoint))]
+    ReadOnly {
+        profile: String,
+        endpoint: Option<String>,
+    },
+    #[error("{0}")]
+    Profile(String),
+    #[error("{0}")]
+    Client(String),
+}
+
+fn not_sent(endpoint: &Option<String>) -> String {
+    endpoint
+        .as_ref()
+        .map(|endpoint| format!(" ({} was not sent)", endpoint))
+        .unwrap_or_default()
+}
+
+impl ApiError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ApiError::Auth => "auth",
+            ApiError::Permission => "permission",
+            ApiError::NotFound { .. } => "not_found",
+            ApiError::Status { .. } => "status",
+            ApiError::Timeout { .. } => "timeout",
+            ApiError::Connection { .. } => "connection",
+            ApiError::Tls { .. } => "tls",
+            ApiError::CertificateChanged { .. } => "certificate_changed",
+            ApiError::Dns { .. } => "dns",
+            ApiError::Parse(_) => "parse",
+            ApiError::ReadOnly { .. } => "read_only",
+            ApiError::Profile(_) => "profile",
+            ApiError::Client(_) => "client",
+        }
+    }
+
+    fn from_status(url: &str, endpoint: &str, status: u16, body: String) -> Self {
+        match status {
+            401 => ApiError::Auth,
+            403 => ApiError::Permission,
+            404 => ApiError::NotFound {
+                endpoint: endpoint.to_string(),
+                hint: "Check your firewall URL and port".to_string(),
+            },
+            _ => ApiError::Status {
+                url: url.to_string(),
+                status,
+                body,
+            },
+        }
+    }
+
+    fn from_reqwest(e: reqwest::Error, url: &str) -> Self {
+        let text = e.to_string();
+        let url = url.to_string();
+
+        if e.is_timeout() {
+            ApiError::Timeout { url }
+        } else if e.is_connect() {
+            let message = if text.contains("proxy") {
+                format!("Proxy connection error: Unable to connect through proxy to {}. Check your proxy settings.", url)
+            } else if text.contains("refused") {
+                format!("Connection refused: The server at {} actively refused the connection. Please verify the port is correct and any firewall rules allow this connection.", url)
+            } else if text.contains("reset") {
+                format!("Connection reset: The connection to {} was reset. This may indicate network instability or an intermediate firewall blocking the connection.", url)
+            } else if text.contains("dns error") || text.contains("not resolve") {
+                return ApiError::Dns { url };
+            } else {
+                format!("Connection error: Unable to connect to server at {}. Check your network connectivity, firewall settings, and verify the server is running.", url)
+            };
+            ApiError::Connection { url, message }
+        } else if e.is_status() {
+            ApiError::Connection {
+                message: format!("Invalid status: The server at {} returned an unexpected response. This may indicate API changes or incompatibility.", url),
+                url,
+            }
+        } else if text.contains("dns error") || text.contains("not resolve") {
+            ApiError::Dns { url }
+        } else if text.contains("certificate") || text.contains("SSL") || text.contains("TLS") {
+            ApiError::Tls {
+                message: format!("SSL/TLS error: There was a problem with the server's security certificate at {}. This is expected for self-signed certificates and doesn't affect functionality.", url),
+                url,
+            }
+        } else if text.contains("handshake") {
+            ApiError::Tls {
+                message: format!("TLS handshake error: Failed to establish secure connection to {}. This may be due to protocol incompatibility or firewall restrictions.", url),
+                url,
+            }
+        } else if e.is_decode() {
+            ApiError::Parse(text)
+        } else {
+            ApiError::Connection {
+                message: format!("Request to {} failed: {} - Please check your network connectivity and firewall configuration.", url, e),
+                url,
+            }
+        }
+    }
+}
+
+impl Serialize for ApiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ApiError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// Request body sent with an [`ApiRequest`]
+#[derive(Clone)]
+pub enum RequestBody {
+    Empty,
+    Json(Value),
+    Form(String),
+}
+
+/// A single call against the OPNsense API, relative to the profile's base URL
+#[derive(Clone)]
+pub struct ApiRequest {
+    pub method: Method,
+    pub endpoint: String,
+    pub body: RequestBody,
+    pub headers: Option<HeaderMap>,
+    pub timeout: Option<Duration>,
+    /// Overrides the endpoint-based retry classification when set
+    pub retry: Option<bool>,
+    pub priority: Priority,
+}
+
+impl ApiRequest {
+    pub fn new(method: Method, endpoint: impl Into<String>) -> Self {
+        Self {
+            method,
+            endpoint: endpoint.into(),
+            body: RequestBody::Empty,
+            headers: None,
+            timeout: None,
+            retry: None,
+            priority: Priority::Interactive,
+        }
+    }
+
+    pub fn get(endpoint: impl Into<String>) -> Self {
+        Self::new(Method::GET, endpoint)
+    }
+
+    pub fn post(endpoint: impl Into<String>, payload: Value) -> Self {
+        Self::new(Method::POST, endpoint).json(payload)
+    }
+
+    pub fn json(mut self, payload: Value) -> Self {
+        self.body = RequestBody::Json(payload);
+        self
+    }
+
+    /// Sends the body as application/x-www-form-urlencoded instead of JSON
+    pub fn form(mut self, form_data: String) -> Self {
+        self.body = RequestBody::Form(form_data);
+        self
+    }
+
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = Some(headers);
+        self
+    }
+
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.timeout = Some(Duration::from_secs(seconds));
+        self
+    }
+
+    /// Marks the request as safe (or unsafe) to replay after a transient failure
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.retry = Some(idempotent);
+        self
+    }
+
+    /// Pollers use `Priority::Background` to queue behind user actions
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Long-lived API client for a single firewall profile
+///
+/// Owns a pooled `reqwest::Client` so TLS sessions and keep-alive connections are
+/// reused across dashboard refreshes instead of renegotiated for every call.
+pub struct OpnsenseClient {
+    client: Client,
+    profile_id: i64,
+    profile_name: String,
+    base_url: String,
+    api_key: Zeroizing<String>,
+    api_secret: Zeroizing<String>,
+    pinned_fingerprint: Option<String>,
+    presented_fingerprint: Arc<Mutex<Option<String>>>,
+    tls_digest: String,
+    proxy: Option<ProxySettings>,
+    ssh_settings: Option<SshTunnelSettings>,
+    tunnel: Option<SshTunnel>,
+    read_only: bool,
+    retry_budget: RetryBudget,
+    cassette: Arc<Cassette>,
+    limiter: Arc<RequestLimiter>,
+    audit_log: Option<Arc<AuditLog>>,
+}
+
+impl OpnsenseClient {
+    pub fn new(api_info: &ApiInfo) -> Result<Self, ApiError> {
+        let tls = tls::build_tls_settings(api_info).map_err(|e| {
+            error!("{}", e);
+            ApiError::Client(e)
+        })?;
+
+        if api_info.proxy.is_some() && api_info.ssh_tunnel.is_some() {
+            return Err(ApiError::Client(
+                "A profile can use a proxy or an SSH tunnel, not both".to_string(),
+            ));
+        }
+
+        let mut builder = Client::builder()
+            .use_preconfigured_tls(tls.config)
+            .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .pool_idle_timeout(Duration::from_secs(POOL_IDLE_TIMEOUT_SECS));
+
+        if let Some(proxy) = &api_info.proxy {
+            info!(
+                "Routing profile {} through proxy {}",
+                api_info.profile_name,
+                proxy.url()
+            );
+            builder = builder.proxy(proxy.to_reqwest().map_err(ApiError::Client)?);
+        }
+
+        // The URL is left alone, so TLS verification and SNI still see the firewall's name
+        let mut tunnel = None;
+        if let Some(settings) = &api_info.ssh_tunnel {
+            let started = SshTunnel::start(settings, api_info.port).map_err(ApiError::Client)?;
+            builder = builder.proxy(started.proxy().map_err(ApiError::Client)?);
+            tunnel = Some(started);
+        }
+
+        let client = builder.build().map_err(|e| {
+            let error_message = format!("Failed to build HTTP client: {}", e);
             error!("{}", error_message);
-            return Err(error_message);
+            ApiError::Client(error_message)
+        })?;
+
+        Ok(Self {
+            client,
+            profile_id: api_info.id,
+            profile_name: api_info.profile_name.clone(),
+            base_url: format!("{}:{}", api_info.api_url, api_info.port),
+            api_key: api_info.api_key.clone(),
+            api_secret: api_info.api_secret.clone(),
+            pinned_fingerprint: api_info.cert_fingerprint.clone(),
+            presented_fingerprint: tls.observed,
+            tls_digest: tls::settings_digest(api_info),
+            proxy: api_info.proxy.clone(),
+            ssh_settings: api_info.ssh_tunnel.clone(),
+            tunnel,
+            read_only: api_info.read_only,
+            retry_budget: RetryBudget::new(),
+            cassette: Arc::new(Cassette::new()),
+            limiter: Arc::new(RequestLimiter::new(DEFAULT_LIMITS)),
+            audit_log: None,
+        })
+    }
+
+    /// Routes traffic through a shared record/replay cassette
+    pub fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = cassette;
+        self
+    }
+
+    /// Shares the profile's request limiter across rebuilt clients
+    pub fn with_limiter(mut self, limiter: Arc<RequestLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    pub fn profile_id(&self) -> i64 {
+        self.profile_id
+    }
+
+    pub fn profile_name(&self) -> &str {
+        &self.profile_name
+    }
+
+    pub fn url(&self, endpoint: &str) -> String {
+        format!("{}{}", self.base_url, endpoint)
+    }
+
+    /// Fingerprint of the certificate the firewall presented on the last handshake
+    pub fn presented_fingerprint(&self) -> Option<String> {
+        self.presented_fingerprint.lock().unwrap().clone()
+    }
+
+    /// Host key fingerprint the SSH server presented when the tunnel last connected
+    pub fn ssh_host_key(&self) -> Option<String> {
+        self.tunnel.as_ref()?.observed_host_key()
+    }
+
+    /// Has a tunnelled client save the SSH host key on first connect, before logging in
+    pub fn pin_ssh_host_key_with(&self, pin_host_key: PinHostKey) {
+        if let Some(tunnel) = &self.tunnel {
+            tunnel.pin_host_key_with(pin_host_key);
         }
-    };
+    }
 
-    if let (Some(key), Some(secret)) = (api_key, api_secret) {
-        let auth_string = format!("{}:{}", key, secret);
-        let auth = general_purpose::STANDARD.encode(auth_string.as_bytes());
-        request_builder = request_builder.header(AUTHORIZATION, format!("Basic {}", auth));
+    /// Whether this client was built from the same connection settings as `api_info`
+    fn matches(&self, api_info: &ApiInfo) -> bool {
+        self.base_url == format!("{}:{}", api_info.api_url, api_info.port)
+            && self.api_key == api_info.api_key
+            && self.api_secret == api_info.api_secret
+            && self.pinned_fingerprint == api_info.cert_fingerprint
+            && self.tls_digest == tls::settings_digest(api_info)
+            && self.proxy == api_info.proxy
+            && self.ssh_settings == api_info.ssh_tunnel
+            && self.read_only == api_info.read_only
+    }
 
-        info!(
-            "Using auth header: Basic {}...{}",
-            &auth[..min(6, auth.len())],
-            &auth[auth.len().saturating_sub(4)..]
-        );
+    /// Reports the tunnel failure behind a request that never reached the firewall
+    fn tunnel_failed(&self, url: &str) -> Option<ApiError> {
+        let tunnel = self.tunnel.as_ref()?;
+        Some(ApiError::Connection {
+            url: url.to_string(),
+            message: tunnel.last_error()?,
+        })
     }
 
-    if let Some(headers) = headers {
-        request_builder = request_builder.headers(headers);
+    /// Returns `CertificateChanged` when a failed request was caused by the pin check
+    fn certificate_changed(&self, url: &str) -> Option<ApiError> {
+        let pinned = self.pinned_fingerprint.as_ref()?;
+        let presented = self.presented_fingerprint()?;
+
+        if tls::fingerprints_match(pinned, &presented) {
+            return None;
+        }
+
+        Some(ApiError::CertificateChanged {
+            url: url.to_string(),
+            pinned: pinned.clone(),
+            presented,
+        })
     }
 
-    if let Some(payload) = payload {
-        request_builder = request_builder.json(&payload);
+    /// Completes a TLS handshake with the firewall and returns the certificate fingerprint
+    ///
+    /// Returns `None` for plain `http://` profiles, which have no certificate to pin.
+    pub async fn probe_certificate(&self) -> Result<Option<String>, ApiError> {
+        let result = self
+            .client
+            .get(&self.base_url)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await;
+
+        // The status code doesn't matter, only that the handshake got far enough
+        if let Some(presented) = self.presented_fingerprint() {
+            return Ok(Some(presented));
+        }
+
+        match result {
+            Ok(_) => Ok(None),
+            Err(e) => Err(ApiError::from_reqwest(e, &self.base_url)),
+        }
     }
 
-    info!("Request build is finalized: {:?}", &request_builder);
+    pub async fn get(&self, endpoint: &str) -> Result<Response, ApiError> {
+        self.send(ApiRequest::get(endpoint)).await
+    }
 
-    match request_builder.send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                info!("Request to {} successful", url);
-                Ok(response)
-            } else {
-                let status = response.status();
-                let body = response.text().await.unwrap_or_else(|_| "".to_string());
-                let error_message = match status.as_u16() {
-                    401 => "Authentication failed (HTTP 401): Your API key or secret is incorrect".to_string(),
-                    403 => "Permission denied (HTTP 403): Your API credentials don't have sufficient permissions".to_string(),
-                    404 => {
-                        if url.contains("/api/core/tunables/") {
-                            "API endpoint not found (HTTP 404): Tunables API requires OPNsense 25.x or newer".to_string()
-                        } else {
-                            "API endpoint not found (HTTP 404): Check your firewall URL and port".to_string()
-                        }
-                    },
-                    _ => format!("Request to {} failed with status {}: {}", url, status, body)
-                };
+    pub async fn post(&self, endpoint: &str, payload: Value) -> Result<Response, ApiError> {
+        self.send(ApiRequest::post(endpoint, payload)).await
+    }
 
-                error!("{}", error_message);
-                Err(error_message)
-            }
+    /// Sends a request, retrying transient failures of reads and searches with
+    /// exponential backoff. Mutating calls are attempted exactly once.
+    ///
+    /// On a read-only profile, mutating calls are refused without leaving the device.
+    pub async fn send(&self, request: ApiRequest) -> Result<Response, ApiError> {
+        if self.read_only && read_only::is_mutating(&request.endpoint) {
+            let error = ApiError::ReadOnly {
+                profile: self.profile_name.clone(),
+                endpoint: request.endpoint.split('?').next().map(str::to_string),
+            };
+            warn!("{}", error);
+            return Err(error);
         }
-        Err(e) => {
-            let error_message = if e.is_timeout() {
-                format!(
-                    "Connection timed out: Server at {} is unreachable or not responding. This may be due to high load on the firewall or network congestion.",
-                    url
-                )
-            } else if e.is_connect() {
-                // More detailed connection error message
-                if e.to_string().contains("proxy") {
-                    format!("Proxy connection error: Unable to connect through proxy to {}. Check your proxy settings.", url)
-                } else if e.to_string().contains("refused") {
-                    format!("Connection refused: The server at {} actively refused the connection. Please verify the port is correct and any firewall rules allow this connection.", url)
-                } else if e.to_string().contains("reset") {
-                    format!("Connection reset: The connection to {} was reset. This may indicate network instability or an intermediate firewall blocking the connection.", url)
-                } else {
-                    format!("Connection error: Unable to connect to server at {}. Check your network connectivity, firewall settings, and verify the server is running.", url)
+
+        let policy = &retry::DEFAULT_POLICY;
+        let retry_safe = request
+            .retry
+            .unwrap_or_else(|| retry::is_retry_safe(&request.method, &request.endpoint));
+
+        self.retry_budget.deposit();
+
+        let mut attempt = 1;
+        loop {
+            match self.send_once(request.clone()).await {
+                Err(e)
+                    if retry_safe
+                        && attempt < policy.max_attempts
+                        && retry::is_transient(&e)
+                        && self.retry_budget.try_withdraw() =>
+                {
+                    let delay = policy.delay(attempt);
+                    attempt += 1;
+                    warn!(
+                        "Retrying {} in {:?} (attempt {}/{}): {}",
+                        request.endpoint, delay, attempt, policy.max_attempts, e
+                    );
+                    tokio::time::sleep(delay).await;
                 }
-            } else if e.is_status() {
-                format!(
-                    "Invalid status: The server at {} returned an unexpected response. This may indicate API changes or incompatibility.",
-                    url
-                )
-            } else if e.to_string().contains("dns error") || e.to_string().contains("not resolve") {
-                format!(
-                    "DNS resolution error: Could not resolve hostname in URL {}. Please check your DNS settings and verify the hostname is correct.",
-                    url
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_once(&self, request: ApiRequest) -> Result<Response, ApiError> {
+        let url = self.url(&request.endpoint);
+
+        if self.cassette.mode() == CassetteMode::Replay {
+            info!("Replaying recorded {} {}", request.method, request.endpoint);
+            let response = self.cassette.replay(&request.method, &request.endpoint)?;
+            return self.check_status(&url, &request.endpoint, response).await;
+        }
+
+        // Held until the whole body has been read, so large downloads count too
+        let _permit = self
+            .limiter
+            .acquire(
+                request.priority,
+                &format!("{} {}", request.method, request.endpoint),
+            )
+            .await;
+
+        info!("Making a {} request to {}", request.method, url);
+
+        let has_credentials = !self.api_key.is_empty() || !self.api_secret.is_empty();
+        let recording = (self.cassette.mode() == CassetteMode::Record).then(|| request.clone());
+
+        let mut request_builder = self.client.request(request.method, &url);
+
+        if has_credentials {
+            let auth_string = Zeroizing::new(format!(
+                "{}:{}",
+                self.api_key.as_str(),
+                self.api_secret.as_str()
+            ));
+            let auth = Zeroizing::new(general_purpose::STANDARD.encode(auth_string.as_bytes()));
+            let value = Zeroizing::new(format!("Basic {}", auth.as_str()));
+            let mut header = HeaderValue::from_str(&value)
+                .map_err(|e| ApiError::Client(format!("Invalid API credentials: {}", e)))?;
+            header.set_sensitive(true);
+            request_builder = request_builder.header(AUTHORIZATION, header);
+        }
+
+        if let Some(headers) = request.headers {
+            request_builder = request_builder.headers(headers);
+        }
+
+        if let Some(timeout) = request.timeout {
+            request_builder = request_builder.timeout(timeout);
+        }
+
+        request_builder = match request.body {
+            RequestBody::Empty => request_builder,
+            RequestBody::Json(payload) => request_builder.json(&payload),
+            RequestBody::Form(form_data) => request_builder
+                .header(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/x-www-form-urlencoded; charset=UTF-8"),
                 )
-            } else if e.to_string().contains("certificate")
-                || e.to_string().contains("SSL")
-                || e.to_string().contains("TLS")
-            {
-                format!("SSL/TLS error: There was a problem with the server's security certificate at {}. This is expected for self-signed certificates and doesn't affect functionality.", url)
-            } else if e.to_string().contains("handshake") {
-                format!("TLS handshake error: Failed to establish secure connection to {}. This may be due to protocol incompatibility or firewall restrictions.", url)
-            } else {
-                format!("Request to {} failed: {} - Please check your network connectivity and firewall configuration.", url, e)
-            };
+                .body(form_data),
+        };
 
-            error!("{}", error_message);
-            Err(error_message)
+        let http_request = request_builder
+            .build()
+            .map_err(|e| ApiError::from_reqwest(e, &url))?;
+        let request_bytes = http_request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map_or(0, |bytes| bytes.len() as u64);
+        let method = http_request.method().to_string();
+
+        let started = Instant::now();
+        let result = match self.client.execute(http_request).await {
+            Ok(response) => read_body(response).await,
+            Err(e) => Err(e),
+        };
+        let latency = started.elapsed();
+
+        match result {
+            Ok(response) => {
+                self.audit(
+                    &method,
+                    &request.endpoint,
+                    latency,
+                    request_bytes,
+                    Ok(&response),
+                );
+
+                let response = match &recording {
+                    Some(recorded) => {
+                        let secrets = [self.api_key.as_str(), self.api_secret.as_str()];
+                        self.cassette
+                            .record(recorded, has_credentials, &secrets, response)
+                            .await?
+                    }
+                    None => response,
+                };
+                self.check_status(&url, &request.endpoint, response).await
+            }
+            Err(e) => {
+                let api_error = self
+                    .certificate_changed(&url)
+                    .or_else(|| self.tunnel_failed(&url))
+                    .unwrap_or_else(|| ApiError::from_reqwest(e, &url));
+                error!("{}", api_error);
+                self.audit(
+                    &method,
+                    &request.endpoint,
+                    latency,
+                    request_bytes,
+                    Err(&api_error),
+                );
+                Err(api_error)
+            }
         }
     }
-}
 
-/// Makes an HTTP request with form data
-/// This is used for endpoints that expect application/x-www-form-urlencoded content
-/// instead of JSON
-pub async fn make_http_request_with_form_data(
-    request_type: &str,
-    url: &str,
-    form_data: String,
-    headers: Option<HeaderMap>,
-    timeout_seconds: Option<u64>,
-    api_key: Option<&str>,
-    api_secret: Option<&str>,
-) -> Result<Response, String> {
-    info!("Making a {} form data request to {}", request_type, url);
-
-    let client_builder = Client::builder().danger_accept_invalid_certs(true);
-    let client = if let Some(timeout_sec) = timeout_seconds {
-        client_builder
-            .timeout(Duration::from_secs(timeout_sec))
-            .build()
-    } else {
-        client_builder.build()
-    }
-    .map_err(|e| {
-        let error_message = format!("Failed to build HTTP client: {}", e);
-        error!("{}", error_message);
-        error_message
-    })?;
-
-    let mut request_builder = match request_type {
-        "GET" => client.get(url),
-        "POST" => client.post(url),
-        "PATCH" => client.patch(url),
-        "PUT" => client.put(url),
-        _ => {
-            let error_message = "Invalid request type".to_string();
-            error!("{}", error_message);
-            return Err(error_message);
+    /// Adds a request to the audit log with only the path of the endpoint, as query
+    /// strings can carry search phrases and identifiers
+    fn audit(
+        &self,
+        method: &str,
+        endpoint: &str,
+        latency: Duration,
+        request_bytes: u64,
+        outcome: Result<&Response, &ApiError>,
+    ) {
+        let Some(audit_log) = &self.audit_log else {
+            return;
+        };
+
+        let (status, error, response_bytes) = match outcome {
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                None,
+                response.content_length(),
+            ),
+            Err(e) => (None, Some(e.kind().to_string()), None),
+        };
+
+        audit_log.record(&AuditEntry {
+            id: 0,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            profile_id: self.profile_id,
+            profile_name: self.profile_name.clone(),
+            method: method.to_string(),
+            endpoint: endpoint.split('?').next().unwrap_or(endpoint).to_string(),
+            status,
+            error,
+            latency_ms: latency.as_millis() as u64,
+            request_bytes,
+            response_bytes,
+        });
+    }
+
+    async fn check_status(
+        &self,
+        url: &str,
+        endpoint: &str,
+        response: Response,
+    ) -> Result<Response, ApiError> {
+        if response.status().is_success() {
+            info!("Request to {} successful", url);
+            return Ok(response);
         }
+
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        let api_error = ApiError::from_status(url, endpoint, status, body);
+        error!("{}", api_error);
+        Err(api_error)
+    }
+}
+
+/// Has a tunnelled profile without a pinned host key save the one its tunnel first sees
+fn pin_ssh_host_key(database: &Database, client: &OpnsenseClient, api_info: &ApiInfo) {
+    let Some(tunnel) = api_info
+        .ssh_tunnel
+        .clone()
+        .filter(|tunnel| tunnel.host_key_fingerprint.is_none())
+    else {
+        return;
     };
 
-    if let (Some(key), Some(secret)) = (api_key, api_secret) {
-        let auth_string = format!("{}:{}", key, secret);
-        let auth = general_purpose::STANDARD.encode(auth_string.as_bytes());
-        request_builder = request_builder.header(AUTHORIZATION, format!("Basic {}", auth));
+    let database = database.clone();
+    let profile = SelectedProfile::new(Some(api_info.id));
+    client.pin_ssh_host_key_with(Box::new(move |host_key| {
+        let mut api_info = profile.require_api_info(&database)?;
+
+        // Leave the profile alone if its tunnel was changed since this client was built
+        match api_info.ssh_tunnel.as_mut() {
+            Some(saved) if saved.host == tunnel.host && saved.port == tunnel.port => {
+                saved.host_key_fingerprint = Some(host_key.to_string());
+            }
+            _ => return Err("The SSH tunnel settings have changed".to_string()),
+        }
 
         info!(
-            "Using auth header: Basic {}...{}",
-            &auth[..min(6, auth.len())],
-            &auth[auth.len().saturating_sub(4)..]
+            "Pinning SSH host key {} for profile {}",
+            host_key, api_info.profile_name
         );
-    }
+        database.save_api_info(&api_info).map_err(|e| e.to_string())
+    }));
+}
+
+/// Whether a profile still has to pin the certificate its firewall presents
+///
+/// Profiles with a CA chain verify against it instead, and plain HTTP has nothing to pin.
+fn needs_pin(api_info: &ApiInfo) -> bool {
+    api_info.cert_fingerprint.is_none()
+        && api_info.ca_chain_pem.is_none()
+        && Url::parse(&api_info.api_url).is_ok_and(|url| url.scheme() == "https")
+}
 
-    // Set the Content-Type header for form data
-    let mut request_headers = headers.unwrap_or_default();
-    request_headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("application/x-www-form-urlencoded; charset=UTF-8"),
-    );
-    request_builder = request_builder.headers(request_headers);
+/// Downloads the body up front and hands back an equivalent, already buffered response
+async fn read_body(response: Response) -> Result<Response, reqwest::Error> {
+    let status = response.status();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
 
-    // Set the form data as a raw string in the request body
-    request_builder = request_builder.body(form_data);
+    let mut buffered = http::Response::new(body);
+    *buffered.status_mut() = status;
+    *buffered.version_mut() = version;
+    *buffered.headers_mut() = headers;
+    Ok(Response::from(buffered))
+}
 
-    info!("Form data request build is finalized: {:?}", &request_builder);
+/// Tauri state holding one pooled [`OpnsenseClient`] per firewall profile
+pub struct ClientManager {
+    clients: Arc<Mutex<HashMap<i64, Arc<OpnsenseClient>>>>,
+    public_client: Client,
+    cassette: Arc<Cassette>,
+    audit_log: Option<Arc<AuditLog>>,
+    /// Kept across client rebuilds so a settings change can't bypass the limits
+    limiters: Mutex<HashMap<i64, Arc<RequestLimiter>>>,
+    capabilities: Arc<Mutex<HashMap<i64, Arc<OnceCell<Capabilities>>>>>,
+}
 
-    match request_builder.send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                info!("Request to {} successful", url);
-                Ok(response)
-            } else {
-                let status = response.status();
-                let body = response.text().await.unwrap_or_else(|_| "".to_string());
-                let error_message = match status.as_u16() {
-                    401 => "Authentication failed (HTTP 401): Your API key or secret is incorrect".to_string(),
-                    403 => "Permission denied (HTTP 403): Your API credentials don't have sufficient permissions".to_string(),
-                    404 => {
-                        if url.contains("/api/core/tunables/") {
-                            "API endpoint not found (HTTP 404): Tunables API requires OPNsense 25.x or newer".to_string()
-                        } else {
-                            "API endpoint not found (HTTP 404): Check your firewall URL and port".to_string()
-                        }
-                    },
-                    _ => format!("Request to {} failed with status {}: {}", url, status, body)
-                };
+impl ClientManager {
+    pub fn new() -> Self {
+        Self {
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            public_client: Client::new(),
+            cassette: Arc::new(Cassette::new()),
+            audit_log: None,
+            limiters: Mutex::new(HashMap::new()),
+            capabilities: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
 
-                error!("{}", error_message);
-                Err(error_message)
+    /// Records every request made by the pooled clients
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(Arc::new(audit_log));
+        self
+    }
+
+    /// Returns the cached client for a profile, rebuilding it when its
+    /// URL, port, credentials or TLS settings have changed since it was created
+    pub fn client_for(&self, api_info: &ApiInfo) -> Result<Arc<OpnsenseClient>, ApiError> {
+        let mut clients = self.clients.lock().unwrap();
+
+        if let Some(client) = clients.get(&api_info.id) {
+            if client.matches(api_info) {
+                return Ok(client.clone());
             }
         }
-        Err(e) => {
-            let error_message = if e.is_timeout() {
-                format!(
-                    "Connection timed out: Server at {} is unreachable or not responding. This may be due to high load on the firewall or network congestion.",
-                    url
-                )
-            } else if e.is_connect() {
-                // More detailed connection error message
-                if e.to_string().contains("proxy") {
-                    format!("Proxy connection error: Unable to connect through proxy to {}. Check your proxy settings.", url)
-                } else if e.to_string().contains("refused") {
-                    format!("Connection refused: The server at {} actively refused the connection. Please verify the port is correct and any firewall rules allow this connection.", url)
-                } else if e.to_string().contains("reset") {
-                    format!("Connection reset: The connection to {} was reset. This may indicate network instability or an intermediate firewall blocking the connection.", url)
-                } else {
-                    format!("Connection error: Unable to connect to server at {}. Check your network connectivity, firewall settings, and verify the server is running.", url)
-                }
-            } else if e.is_status() {
-                format!(
-                    "Invalid status: The server at {} returned an unexpected response. This may indicate API changes or incompatibility.",
-                    url
-                )
-            } else if e.to_string().contains("dns error") || e.to_string().contains("not resolve") {
-                format!(
-                    "DNS resolution error: Could not resolve hostname in URL {}. Please check your DNS settings and verify the hostname is correct.",
-                    url
-                )
-            } else if e.to_string().contains("certificate")
-                || e.to_string().contains("SSL")
-                || e.to_string().contains("TLS")
-            {
-                format!("SSL/TLS error: There was a problem with the server's security certificate at {}. This is expected for self-signed certificates and doesn't affect functionality.", url)
-            } else if e.to_string().contains("handshake") {
-                format!("TLS handshake error: Failed to establish secure connection to {}. This may be due to protocol incompatibility or firewall restrictions.", url)
-            } else {
-                format!("Request to {} failed: {} - Please check your network connectivity and firewall configuration.", url, e)
+
+        info!("Creating API client for profile {}", api_info.profile_name);
+        let mut client = OpnsenseClient::new(api_info)?
+            .with_cassette(self.cassette.clone())
+            .with_limiter(self.limiter(api_info.id));
+        if let Some(audit_log) = &self.audit_log {
+            client = client.with_audit_log(audit_log.clone());
+        }
+        let client = Arc::new(client);
+        clients.insert(api_info.id, client.clone());
+        Ok(client)
+    }
+
+    /// Client for the profile a command selected, the default one unless it asked otherwise
+    ///
+    /// An HTTPS profile without a pinned certificate is probed and pinned first, so the
+    /// API key is only ever sent to the certificate that ends up pinned. The SSH host key
+    /// of a tunnelled profile is likewise saved before the tunnel logs in.
+    pub async fn client(
+        &self,
+        database: &Database,
+        profile: SelectedProfile,
+    ) -> Result<Arc<OpnsenseClient>, ApiError> {
+        let mut api_info = profile
+            .require_api_info(database)
+            .map_err(ApiError::Profile)?;
+
+        self.load_rate_limits(database, api_info.id)?;
+        let client = self.client_for(&api_info)?;
+        pin_ssh_host_key(database, &client, &api_info);
+
+        // Trust on first use, with an unauthenticated handshake before the first request
+        if needs_pin(&api_info) {
+            let presented = match client.presented_fingerprint() {
+                Some(presented) => Some(presented),
+                None => client.probe_certificate().await?,
             };
+            if let Some(presented) = presented {
+                info!(
+                    "Pinning certificate {} for profile {}",
+                    presented, api_info.profile_name
+                );
+                database
+                    .set_cert_fingerprint(api_info.id, Some(&presented))
+                    .map_err(|e| {
+                        ApiError::Profile(format!("Failed to save certificate fingerprint: {}", e))
+                    })?;
 
-            error!("{}", error_message);
-            Err(error_message)
+                // Reloaded, since probing through a tunnel may have pinned its host key too
+                api_info = profile
+                    .require_api_info(database)
+                    .map_err(ApiError::Profile)?;
+                let client = self.client_for(&api_info)?;
+                pin_ssh_host_key(database, &client, &api_info);
+                return Ok(client);
+            }
         }
+
+        Ok(client)
+    }
+
+    /// Client for third-party lookups that don't talk to a firewall
+    pub fn public_client(&self) -> &Client {
+        &self.public_client
+    }
+
+    pub fn cassette(&self) -> &Cassette {
+        &self.cassette
+    }
+
+    fn limiter(&self, profile_id: i64) -> Arc<RequestLimiter> {
+        self.limiters
+            .lock()
+            .unwrap()
+            .entry(profile_id)
+            .or_insert_with(|| Arc::new(RequestLimiter::new(DEFAULT_LIMITS)))
+            .clone()
+    }
+
+    /// Applies a profile's stored limits the first time it is used
+    fn load_rate_limits(&self, database: &Database, profile_id: i64) -> Result<(), ApiError> {
+        if self.limiters.lock().unwrap().contains_key(&profile_id) {
+            return Ok(());
+        }
+
+        let limits = database
+            .get_rate_limits(profile_id)
+            .map_err(|e| ApiError::Profile(format!("Failed to get rate limits: {}", e)))?
+            .unwrap_or(DEFAULT_LIMITS);
+        self.limiters
+            .lock()
+            .unwrap()
+            .entry(profile_id)
+            .or_insert_with(|| Arc::new(RequestLimiter::new(limits)));
+        Ok(())
+    }
+
+    pub fn set_rate_limits(&self, profile_id: i64, limits: RateLimits) {
+        info!("Limiting profile {} to {:?}", profile_id, limits);
+        self.limiter(profile_id).configure(limits);
+    }
+
+    /// Capabilities of the client's firewall, discovered on first use
+    ///
+    /// Concurrent callers share a single discovery; failed discoveries aren't cached.
+    pub async fn capabilities(&self, client: &OpnsenseClient) -> Result<Capabilities, ApiError> {
+        let cell = self
+            .capabilities
+            .lock()
+            .unwrap()
+            .entry(client.profile_id())
+            .or_default()
+            .clone();
+
+        cell.get_or_try_init(|| capabilities::discover(client))
+            .await
+            .cloned()
+    }
+
+    pub fn forget_capabilities(&self, profile_id: i64) {
+        self.capabilities.lock().unwrap().remove(&profile_id);
+    }
+
+    pub fn invalidate(&self, profile_id: i64) {
+        self.clients.lock().unwrap().remove(&profile_id);
+        self.forget_capabilities(profile_id);
+    }
+
+    pub fn clear(&self) {
+        self.clients.lock().unwrap().clear();
+        self.capabilities.lock().unwrap().clear();
+    }
+
+    /// Drops the pooled clients, and the credentials they hold, whenever the
+    /// database erases its credentials
+    pub fn clear_on_wipe(&self, database: &Database) {
+        let clients = self.clients.clone();
+        let capabilities = self.capabilities.clone();
+        database.on_wipe(move || {
+            clients.lock().unwrap().clear();
+            capabilities.lock().unwrap().clear();
+        });
+    }
+
+    #[cfg(test)]
+    pub fn pooled_clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
     }
 }
@@ -1,6 +1,9 @@
+use crate::active_profile::SelectedProfile;
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ApiError, ApiRequest, ClientManager, OpnsenseClient};
 use log::{error, info, warn};
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
@@ -85,21 +88,19 @@ pub struct IpAddress {
 }
 
 #[tauri::command]
-pub async fn get_interfaces(database: State<'_, Database>) -> Result<Vec<Interface>, String> {
+pub async fn get_interfaces(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Vec<Interface>, CommandError> {
     info!("Fetching interface information");
 
     // Track the start time for performance measurements
     let start_time = std::time::Instant::now();
 
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+    let client = clients.client(&database, profile).await?;
 
-    let url = format!(
-        "{}:{}/api/interfaces/overview/interfacesInfo",
-        api_info.api_url, api_info.port
-    );
+    let url = "/api/interfaces/overview/interfacesInfo";
 
     // Increase page size to get more interfaces in fewer requests
     let page_size = 50; // Increased from 25
@@ -129,15 +130,7 @@ pub async fn get_interfaces(database: State<'_, Database>) -> Result<Vec<Interfa
                 let mut current_timeout = initial_timeout;
 
                 while retry_count < max_retries {
-                    match make_http_request(
-                        "POST",
-                        &url,
-                        Some(payload.clone()),
-                        None,
-                        Some(current_timeout),
-                        Some(&api_info.api_key),
-                        Some(&api_info.api_secret),
-                    ).await {
+                    match client.send(ApiRequest::post(url, payload.clone()).timeout(current_timeout)).await {
                         Ok(response) => {
                             match response.text().await {
                                 Ok(response_text) => {
@@ -250,12 +243,13 @@ pub async fn get_interfaces(database: State<'_, Database>) -> Result<Vec<Interfa
                         },
                         Err(e) => {
                             error!("Request error for interface page {}: {}", current_page, e);
-                            last_error = e;
+                            let timed_out = matches!(e, ApiError::Timeout { .. });
+                            last_error = e.to_string();
                             retry_count += 1;
                             current_timeout += 5;
 
                             // Special handling for common errors
-                            if last_error.contains("timeout") || last_error.contains("timed out") {
+                            if timed_out {
                                 warn!("Timeout detected, increasing timeout for retry");
                                 current_timeout += 10; // Add extra time for timeout errors
                             }
@@ -291,8 +285,13 @@ pub async fn get_interfaces(database: State<'_, Database>) -> Result<Vec<Interfa
         if all_interfaces.is_empty() {
             // If no interfaces were retrieved, try our fallback approach
             info!("Attempting fallback interface retrieval method...");
-            return try_alternative_interface_fetch(&api_info).await
-                .map_err(|e| format!("All interface fetch methods failed. Primary: timeout after 45s. Fallback: {}", e));
+            return try_alternative_interface_fetch(&client).await.map_err(|e| {
+                format!(
+                    "All interface fetch methods failed. Primary: timeout after 45s. Fallback: {}",
+                    e
+                )
+                .into()
+            });
         } else {
             // Return what we have so far with a warning
             info!(
@@ -300,20 +299,21 @@ pub async fn get_interfaces(database: State<'_, Database>) -> Result<Vec<Interfa
                 all_interfaces.len()
             );
         }
-    } else if let Err(e) = timeout.unwrap() {
+    } else if let Ok(Err(e)) = timeout {
         // Internal error occurred and we got no interfaces, try fallback
         if all_interfaces.is_empty() {
             info!(
                 "Primary interface fetch failed: {}. Trying fallback method...",
                 e
             );
-            return try_alternative_interface_fetch(&api_info)
+            return try_alternative_interface_fetch(&client)
                 .await
                 .map_err(|e2| {
                     format!(
                         "All interface fetch methods failed. Primary: {}. Fallback: {}",
                         e, e2
                     )
+                    .into()
                 });
         } else {
             // We have partial data, log the error but return what we have
@@ -495,8 +495,8 @@ pub async fn get_interfaces(database: State<'_, Database>) -> Result<Vec<Interfa
 
 // Alternative interface fetch method that tries a different API endpoint
 async fn try_alternative_interface_fetch(
-    api_info: &crate::db::ApiInfo,
-) -> Result<Vec<Interface>, String> {
+    client: &OpnsenseClient,
+) -> Result<Vec<Interface>, CommandError> {
     info!("Using alternative interface fetch method");
 
     // Try multiple alternative endpoints (in order of preference)
@@ -511,10 +511,9 @@ async fn try_alternative_interface_fetch(
 
     // Try each endpoint until one works
     for endpoint in &endpoints {
-        let url = format!("{}:{}{}", api_info.api_url, api_info.port, endpoint);
         info!("Trying alternative endpoint: {}", endpoint);
 
-        match fetch_interfaces_from_endpoint(&url, api_info).await {
+        match fetch_interfaces_from_endpoint(client, endpoint).await {
             Ok(interfaces) => {
                 info!(
                     "Successfully retrieved {} interfaces from endpoint {}",
@@ -533,7 +532,7 @@ async fn try_alternative_interface_fetch(
 
     // Finally, try one more desperate approach - get any kind of interface data
     info!("Trying desperate measure to get any interface data");
-    match try_extract_any_interfaces(api_info).await {
+    match try_extract_any_interfaces(client).await {
         Ok(interfaces) => {
             info!(
                 "Desperate measure succeeded in getting {} interfaces",
@@ -547,44 +546,40 @@ async fn try_alternative_interface_fetch(
                 "All alternative methods failed. Errors: {}. Last error: {}",
                 endpoint_errors.join("; "),
                 e
-            ))
+            )
+            .into())
         }
     }
 }
 
 // Helper to fetch interfaces from a specific endpoint
 async fn fetch_interfaces_from_endpoint(
-    url: &str,
-    api_info: &crate::db::ApiInfo,
-) -> Result<Vec<Interface>, String> {
+    client: &OpnsenseClient,
+    endpoint: &str,
+) -> Result<Vec<Interface>, CommandError> {
+    let url = client.url(endpoint);
+
     // Try both GET and POST methods
-    let methods = ["GET", "POST"];
+    let methods = [Method::GET, Method::POST];
     let mut last_error = String::new();
 
     for method in &methods {
         info!("Trying {} request to {}", method, url);
 
-        let payload = if *method == "POST" {
-            Some(serde_json::json!({
-                "current": 1,
-                "rowCount": 100,  // Request a large number of interfaces at once
-                "searchPhrase": ""
-            }))
+        let request = if *method == Method::POST {
+            ApiRequest::post(
+                endpoint,
+                serde_json::json!({
+                    "current": 1,
+                    "rowCount": 100,  // Request a large number of interfaces at once
+                    "searchPhrase": ""
+                }),
+            )
         } else {
-            None
+            ApiRequest::get(endpoint)
         };
 
-        match make_http_request(
-            method,
-            url,
-            payload,
-            None,
-            Some(20), // 20 second timeout
-            Some(&api_info.api_key),
-            Some(&api_info.api_secret),
-        )
-        .await
-        {
+        match client.send(request.timeout(20)).await {
             Ok(response) => {
                 match response.text().await {
                     Ok(response_text) => {
@@ -609,7 +604,7 @@ async fn fetch_interfaces_from_endpoint(
         }
     }
 
-    Err(last_error)
+    Err(last_error.into())
 }
 
 // Parse interface response based on its format
@@ -733,24 +728,12 @@ fn get_string_value(json: &serde_json::Value, possible_fields: &[&str]) -> Optio
 
 // Last-resort attempt to get any interface data
 async fn try_extract_any_interfaces(
-    api_info: &crate::db::ApiInfo,
-) -> Result<Vec<Interface>, String> {
+    client: &OpnsenseClient,
+) -> Result<Vec<Interface>, CommandError> {
     // Try to get status page data which usually contains interface information
-    let url = format!(
-        "{}:{}/api/core/system/status",
-        api_info.api_url, api_info.port
-    );
+    let url = "/api/core/system/status";
 
-    if let Ok(response) = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(15),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await {
+    if let Ok(response) = client.send(ApiRequest::get(url).timeout(15)).await {
         if let Ok(text) = response.text().await {
             // Try to extract any interface-looking data
             if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
@@ -764,7 +747,7 @@ async fn try_extract_any_interfaces(
         }
     }
 
-    Err("Unable to retrieve any interface data from the firewall. The API may not be compatible or accessible.".to_string())
+    Err("Unable to retrieve any interface data from the firewall. The API may not be compatible or accessible.".into())
 }
 
 // Recursively search JSON for interface data
@@ -822,15 +805,17 @@ fn extract_interfaces_from_json(json: &serde_json::Value) -> Option<Vec<Interfac
 #[tauri::command]
 pub async fn get_interface_details(
     device: String,
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Interface, String> {
+    clients: State<'_, ClientManager>,
+) -> Result<Interface, CommandError> {
     info!("Getting details for interface: {}", device);
 
     // Get all interfaces and filter for the requested one
-    let interfaces = get_interfaces(database).await?;
+    let interfaces = get_interfaces(profile, database, clients).await?;
 
     interfaces
         .into_iter()
         .find(|iface| iface.device == device)
-        .ok_or_else(|| format!("Interface '{}' not found", device))
+        .ok_or_else(|| format!("Interface '{}' not found", device).into())
 }
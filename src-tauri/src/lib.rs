@@ -1,26 +1,59 @@
+// The library name is set by the mobile project template
+#![allow(non_snake_case)]
+// Commands take every argument the frontend passes as a separate parameter
+#![allow(clippy::too_many_arguments)]
+
+mod active_profile;
 mod alias;
+mod api_key_file;
+mod audit_log;
+mod auto_lock;
+mod capabilities;
+mod cassette;
+mod change_journal;
 mod commands;
+mod confirmation;
 mod dashboard;
 mod db;
 mod devices;
+mod error;
 mod firewall;
 mod firewall_logs;
+mod fleet;
 mod http_client;
 mod interfaces;
+mod local_users;
+mod migrations;
 mod pin_cache;
+mod pin_lockout;
 mod power;
+mod profile_transfer;
+mod proxy;
+mod rate_limit;
+mod read_only;
+mod retry;
 mod routes;
+mod search_grid;
 mod snapshots;
+mod ssh_tunnel;
 mod system_resources;
+mod tls;
 mod traffic;
 mod tunables;
 mod unbound;
+mod undo;
 mod update_checker;
 mod wol;
 
+#[cfg(test)]
+mod tests;
+
+use active_profile::ActiveProfiles;
+use auto_lock::register_idle_lock;
+use confirmation::Confirmations;
 use db::Database;
 use firewall_logs::register_log_cache;
-use pin_cache::PinCache;
+use http_client::ClientManager;
 use tauri::Manager;
 use traffic::register_traffic_cache;
 
@@ -30,34 +63,72 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_log::Builder::new().build())
         .setup(|app| {
-            let pin_cache = PinCache::new();
-            app.manage(pin_cache);
-
             let db = Database::new(app.handle()).expect("Failed to initialize database");
+            let clients = ClientManager::new().with_audit_log(db.audit_log());
+            clients.clear_on_wipe(&db);
             app.manage(db);
+            app.manage(clients);
+            app.manage(ActiveProfiles::new());
+            app.manage(Confirmations::new());
 
             register_log_cache(app).expect("Failed to register log cache");
             register_traffic_cache(app).expect("Failed to register traffic cache");
+            register_idle_lock(app).expect("Failed to register idle lock");
 
             Ok(())
         })
+        .on_window_event(active_profile::forget_closed_window)
         .invoke_handler(tauri::generate_handler![
             commands::check_first_run,
             commands::save_initial_config,
             commands::get_api_info,
             commands::update_api_info,
+            commands::set_profile_proxy,
+            commands::set_profile_ssh_tunnel,
+            commands::set_profile_read_only,
+            commands::get_profile_rate_limits,
+            commands::set_profile_rate_limits,
+            audit_log::get_api_audit_log,
+            audit_log::clear_api_audit_log,
+            change_journal::get_change_journal,
+            change_journal::export_change_journal,
+            undo::undo_last_change,
+            capabilities::get_capabilities,
+            capabilities::refresh_capabilities,
             commands::get_api_profiles,
+            active_profile::set_active_profile,
+            active_profile::get_active_profile,
             commands::update_pin,
+            local_users::get_signed_in_user,
+            local_users::list_local_users,
+            local_users::add_local_user,
+            local_users::set_local_user_role,
+            local_users::remove_local_user,
+            confirmation::request_confirmation,
             commands::get_vendor_info,
             commands::add_api_profile,
             commands::delete_api_profile,
             commands::set_default_profile,
             commands::test_api_connection,
+            commands::get_certificate_status,
+            commands::trust_certificate,
+            cassette::start_api_recording,
+            cassette::start_api_replay,
+            cassette::stop_api_cassette,
+            cassette::get_api_cassette_status,
             commands::get_dashboard_preferences,
             commands::save_dashboard_preferences,
-            pin_cache::set_pin,
             pin_cache::clear_pin,
             pin_cache::verify_pin,
+            pin_lockout::get_pin_lockout_status,
+            pin_lockout::set_pin_wipe_threshold,
+            pin_lockout::reset_locked_app,
+            auto_lock::record_activity,
+            auto_lock::get_idle_lock_minutes,
+            auto_lock::set_idle_lock_minutes,
+            profile_transfer::export_profiles,
+            profile_transfer::preview_profile_import,
+            profile_transfer::import_profiles,
             devices::get_devices,
             devices::get_ndp_devices,
             devices::get_combined_devices,
@@ -93,6 +164,7 @@ pub fn run() {
             firewall_logs::start_log_polling,
             firewall_logs::stop_log_polling,
             firewall_logs::clear_log_cache,
+            fleet::get_fleet_overview,
             routes::get_routes,
             routes::get_route_info,
             routes::add_route,
@@ -0,0 +1,239 @@
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tauri::State;
+
+use crate::db::Database;
+use crate::error::CommandError;
+
+/// Name the PIN from first-run setup signs in as; it is always an admin
+pub const OWNER_NAME: &str = "owner";
+
+/// What a signed-in user may do, each role including the ones before it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Dashboards, logs and settings, without changing anything on a firewall
+    Viewer,
+    /// Day-to-day changes: rules, aliases, routes, services, DNS blocklists, WOL
+    Operator,
+    /// Reboots, firmware updates, tunables, snapshot rollbacks, profiles and users
+    Admin,
+}
+
+impl Role {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            other => Err(rusqlite::Error::FromSqlConversionFailure(
+                1,
+                rusqlite::types::Type::Text,
+                format!("Unknown role '{}'", other).into(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whoever unlocked the app with their PIN
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SignedInUser {
+    pub name: String,
+    pub role: Role,
+    pub is_owner: bool,
+}
+
+impl SignedInUser {
+    pub fn owner() -> Self {
+        Self {
+            name: OWNER_NAME.to_string(),
+            role: Role::Admin,
+            is_owner: true,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LocalUser {
+    pub name: String,
+    pub role: Role,
+}
+
+/// Ciphertext and nonce
+pub type Sealed = (Vec<u8>, Vec<u8>);
+
+/// A local user as stored, next to the owner in `app_settings`
+///
+/// Credentials stay encrypted with the owner's key. Each user has a random key of
+/// their own that unwraps it: `user_key_by_pin` lets the user recover it from their
+/// PIN, and `user_key_by_owner` lets the owner re-wrap a new credential key when the
+/// owner PIN changes.
+pub struct LocalUserRecord {
+    pub name: String,
+    pub role: Role,
+    pub password_hash: String,
+    pub pin_salt: String,
+    pub user_key_by_pin: Sealed,
+    pub user_key_by_owner: Sealed,
+    pub credential_key: Sealed,
+}
+
+impl LocalUserRecord {
+    pub fn create_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS local_users (
+                name TEXT PRIMARY KEY,
+                role TEXT NOT NULL,
+                password_hash TEXT NOT NULL,
+                pin_salt TEXT NOT NULL,
+                user_key_by_pin BLOB NOT NULL,
+                user_key_by_pin_nonce BLOB NOT NULL,
+                user_key_by_owner BLOB NOT NULL,
+                user_key_by_owner_nonce BLOB NOT NULL,
+                credential_key BLOB NOT NULL,
+                credential_key_nonce BLOB NOT NULL
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn list(conn: &Connection) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT name, role, password_hash, pin_salt,
+                    user_key_by_pin, user_key_by_pin_nonce,
+                    user_key_by_owner, user_key_by_owner_nonce,
+                    credential_key, credential_key_nonce
+             FROM local_users ORDER BY name",
+        )?;
+        let users = stmt
+            .query_map([], |row| {
+                Ok(LocalUserRecord {
+                    name: row.get(0)?,
+                    role: Role::parse(&row.get::<_, String>(1)?)?,
+                    password_hash: row.get(2)?,
+                    pin_salt: row.get(3)?,
+                    user_key_by_pin: (row.get(4)?, row.get(5)?),
+                    user_key_by_owner: (row.get(6)?, row.get(7)?),
+                    credential_key: (row.get(8)?, row.get(9)?),
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(users)
+    }
+
+    pub fn insert(&self, conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT INTO local_users (
+                name, role, password_hash, pin_salt,
+                user_key_by_pin, user_key_by_pin_nonce,
+                user_key_by_owner, user_key_by_owner_nonce,
+                credential_key, credential_key_nonce
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                self.name,
+                self.role.as_str(),
+                self.password_hash,
+                self.pin_salt,
+                self.user_key_by_pin.0,
+                self.user_key_by_pin.1,
+                self.user_key_by_owner.0,
+                self.user_key_by_owner.1,
+                self.credential_key.0,
+                self.credential_key.1,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Stores the wrapping of a new owner credential key
+    pub fn rewrap(
+        conn: &Connection,
+        name: &str,
+        user_key_by_owner: &Sealed,
+        credential_key: &Sealed,
+    ) -> Result<()> {
+        conn.execute(
+            "UPDATE local_users
+             SET user_key_by_owner = ?2, user_key_by_owner_nonce = ?3,
+                 credential_key = ?4, credential_key_nonce = ?5
+             WHERE name = ?1",
+            params![
+                name,
+                user_key_by_owner.0,
+                user_key_by_owner.1,
+                credential_key.0,
+                credential_key.1,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_role(conn: &Connection, name: &str, role: Role) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE local_users SET role = ?2 WHERE name = ?1",
+            params![name, role.as_str()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn delete(conn: &Connection, name: &str) -> Result<bool> {
+        let deleted = conn.execute("DELETE FROM local_users WHERE name = ?1", params![name])?;
+        Ok(deleted > 0)
+    }
+}
+
+#[tauri::command]
+pub fn get_signed_in_user(database: State<Database>) -> Option<SignedInUser> {
+    database.signed_in_user()
+}
+
+#[tauri::command]
+pub fn list_local_users(database: State<Database>) -> Result<Vec<LocalUser>, CommandError> {
+    database.require_role(Role::Admin)?;
+    Ok(database.list_local_users()?)
+}
+
+/// Adds a user who unlocks the app with their own PIN and gets only `role`'s permissions
+#[tauri::command]
+pub fn add_local_user(
+    name: String,
+    pin: String,
+    role: Role,
+    database: State<Database>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    let pin = zeroize::Zeroizing::new(pin);
+    Ok(database.add_local_user(name.trim(), &pin, role)?)
+}
+
+#[tauri::command]
+pub fn set_local_user_role(
+    name: String,
+    role: Role,
+    database: State<Database>,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    Ok(database.set_local_user_role(&name, role)?)
+}
+
+#[tauri::command]
+pub fn remove_local_user(name: String, database: State<Database>) -> Result<(), CommandError> {
+    database.require_role(Role::Admin)?;
+    Ok(database.remove_local_user(&name)?)
+}
@@ -0,0 +1,319 @@
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use log::{info, warn};
+use rusqlite::{params, Connection, Result, Transaction};
+
+use crate::audit_log::AuditLog;
+use crate::auto_lock::DEFAULT_IDLE_LOCK_MINUTES;
+use crate::change_journal::ChangeJournal;
+use crate::local_users::LocalUserRecord;
+use crate::pin_lockout::PinLockout;
+
+/// One schema change, committed together with the `PRAGMA user_version` it brings the database to
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&Transaction) -> Result<()>,
+}
+
+/// Every schema change in the order it shipped
+///
+/// Databases from releases before `user_version` was tracked start at 0 and may
+/// already contain any of these changes, so the early steps check before altering.
+/// Only ever append: a shipped migration must not be edited or reordered.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base tables",
+        apply: create_base_tables,
+    },
+    Migration {
+        version: 2,
+        description: "PIN salt",
+        apply: add_pin_salt,
+    },
+    Migration {
+        version: 3,
+        description: "encrypted profiles with certificate pinning, TLS material and transports",
+        apply: create_api_info,
+    },
+    Migration {
+        version: 4,
+        description: "per-profile rate limits",
+        apply: create_rate_limits,
+    },
+    Migration {
+        version: 5,
+        description: "API audit log",
+        apply: create_audit_log,
+    },
+    Migration {
+        version: 6,
+        description: "credential key version",
+        apply: add_key_version,
+    },
+    Migration {
+        version: 7,
+        description: "PIN lockout",
+        apply: add_pin_lockout,
+    },
+    Migration {
+        version: 8,
+        description: "idle lock",
+        apply: add_idle_lock,
+    },
+    Migration {
+        version: 9,
+        description: "local users",
+        apply: create_local_users,
+    },
+    Migration {
+        version: 10,
+        description: "read-only profiles",
+        apply: add_read_only,
+    },
+    Migration {
+        version: 11,
+        description: "change journal",
+        apply: create_change_journal,
+    },
+    Migration {
+        version: 12,
+        description: "undo tracking",
+        apply: add_undone_at,
+    },
+];
+
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Applies every migration newer than the database, each in its own transaction
+///
+/// A failing migration is rolled back and stops the upgrade, leaving the database
+/// at the last version that applied cleanly.
+pub fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<()> {
+    let current = schema_version(conn)?;
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+
+    if current > latest {
+        warn!(
+            "Database schema {} is newer than this release supports ({})",
+            current, latest
+        );
+        return Ok(());
+    }
+
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > current)
+    {
+        info!(
+            "Applying database migration {}: {}",
+            migration.version, migration.description
+        );
+
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+pub fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
+        params![table],
+        |row| {
+            let count: i64 = row.get(0)?;
+            Ok(count > 0)
+        },
+    )
+}
+
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name=?1",
+            table
+        ),
+        params![column],
+        |row| {
+            let count: i64 = row.get(0)?;
+            Ok(count > 0)
+        },
+    )
+}
+
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        info!("Adding {} column to {}", column, table);
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn create_base_tables(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS first_run (
+            id INTEGER PRIMARY KEY,
+            has_run BOOLEAN NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS app_settings (
+            id INTEGER PRIMARY KEY,
+            password_hash TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS dashboard_preferences (
+            id INTEGER PRIMARY KEY,
+            profile_id INTEGER NOT NULL,
+            widget_key TEXT NOT NULL,
+            visible BOOLEAN NOT NULL DEFAULT 1,
+            position INTEGER NOT NULL,
+            FOREIGN KEY(profile_id) REFERENCES api_info(id)
+        );",
+    )
+}
+
+/// Salts from before key derivation are filled in here, so unlocking never has to write one
+fn add_pin_salt(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(tx, "app_settings", "pin_salt", "TEXT NOT NULL DEFAULT ''")?;
+    tx.execute(
+        "UPDATE app_settings SET pin_salt = ?1 WHERE pin_salt = ''",
+        params![SaltString::generate(&mut OsRng).to_string()],
+    )?;
+    Ok(())
+}
+
+const API_INFO_TABLE: &str = "CREATE TABLE IF NOT EXISTS api_info (
+    id INTEGER PRIMARY KEY,
+    profile_name TEXT NOT NULL UNIQUE,
+    encrypted_api_key BLOB NOT NULL,
+    api_key_nonce BLOB NOT NULL,
+    encrypted_api_secret BLOB NOT NULL,
+    api_secret_nonce BLOB NOT NULL,
+    api_url TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    is_default BOOLEAN NOT NULL DEFAULT 0,
+    cert_fingerprint TEXT,
+    encrypted_ca_chain BLOB,
+    ca_chain_nonce BLOB,
+    encrypted_client_cert BLOB,
+    client_cert_nonce BLOB,
+    encrypted_client_key BLOB,
+    client_key_nonce BLOB,
+    encrypted_proxy BLOB,
+    proxy_nonce BLOB,
+    encrypted_ssh_tunnel BLOB,
+    ssh_tunnel_nonce BLOB
+)";
+
+/// Creates `api_info` in its encrypted layout, bringing older layouts up to it
+fn create_api_info(tx: &Transaction) -> Result<()> {
+    // Older releases staged the encrypted table before the PIN was known. If they
+    // stopped between dropping `api_info` and renaming, the staged copy is the data.
+    if has_table(tx, "api_info_new")? {
+        if has_table(tx, "api_info")? {
+            tx.execute("DROP TABLE api_info_new", [])?;
+        } else {
+            tx.execute("ALTER TABLE api_info_new RENAME TO api_info", [])?;
+        }
+    }
+
+    // Setup used to store the first profile in plaintext until the first unlock. The
+    // PIN isn't known here to encrypt it, so that setup is discarded and runs again.
+    if has_column(tx, "api_info", "api_key")? {
+        warn!("Discarding profiles that were never encrypted, setup has to run again");
+        tx.execute_batch(
+            "DELETE FROM dashboard_preferences;
+             DROP TABLE api_info;
+             DELETE FROM first_run;",
+        )?;
+    }
+
+    tx.execute(API_INFO_TABLE, [])?;
+
+    add_column_if_missing(tx, "api_info", "cert_fingerprint", "TEXT")?;
+    for column in [
+        "encrypted_ca_chain",
+        "ca_chain_nonce",
+        "encrypted_client_cert",
+        "client_cert_nonce",
+        "encrypted_client_key",
+        "client_key_nonce",
+        "encrypted_proxy",
+        "proxy_nonce",
+        "encrypted_ssh_tunnel",
+        "ssh_tunnel_nonce",
+    ] {
+        add_column_if_missing(tx, "api_info", column, "BLOB")?;
+    }
+
+    Ok(())
+}
+
+fn create_rate_limits(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS rate_limits (
+            profile_id INTEGER PRIMARY KEY,
+            max_concurrent INTEGER NOT NULL,
+            requests_per_second REAL NOT NULL,
+            burst INTEGER NOT NULL,
+            FOREIGN KEY(profile_id) REFERENCES api_info(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_audit_log(tx: &Transaction) -> Result<()> {
+    AuditLog::create_table(tx)
+}
+
+/// Existing credentials were encrypted with the SHA-256 key and are upgraded on unlock
+fn add_key_version(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(
+        tx,
+        "app_settings",
+        "key_version",
+        "INTEGER NOT NULL DEFAULT 1",
+    )
+}
+
+fn add_pin_lockout(tx: &Transaction) -> Result<()> {
+    PinLockout::add_columns(tx)
+}
+
+fn add_idle_lock(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(
+        tx,
+        "app_settings",
+        "idle_lock_minutes",
+        &format!("INTEGER NOT NULL DEFAULT {}", DEFAULT_IDLE_LOCK_MINUTES),
+    )
+}
+
+fn create_local_users(tx: &Transaction) -> Result<()> {
+    LocalUserRecord::create_table(tx)
+}
+
+fn add_read_only(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(tx, "api_info", "read_only", "BOOLEAN NOT NULL DEFAULT 0")
+}
+
+fn create_change_journal(tx: &Transaction) -> Result<()> {
+    ChangeJournal::create_table(tx)
+}
+
+fn add_undone_at(tx: &Transaction) -> Result<()> {
+    add_column_if_missing(tx, "change_journal", "undone_at", "TEXT")
+}
@@ -1,54 +1,27 @@
-use std::sync::Mutex;
-
-pub struct PinCache {
-    pin: Mutex<Option<String>>,
-}
-
-impl PinCache {
-    pub fn new() -> Self {
-        PinCache {
-            pin: Mutex::new(None),
-        }
-    }
-
-    pub fn set_pin(&self, pin: String) {
-        let mut cache = self.pin.lock().unwrap();
-        *cache = Some(pin);
-    }
-
-    pub fn get_pin(&self) -> Option<String> {
-        let cache = self.pin.lock().unwrap();
-        cache.clone()
-    }
-
-    pub fn clear_pin(&self) {
-        let mut cache = self.pin.lock().unwrap();
-        *cache = None;
-    }
-}
+use crate::error::CommandError;
+use zeroize::Zeroizing;
 
 #[tauri::command]
-pub fn set_pin(pin: String, pin_cache: tauri::State<'_, PinCache>) {
-    pin_cache.set_pin(pin);
-}
-
-#[tauri::command]
-pub fn clear_pin(pin_cache: tauri::State<'_, PinCache>) {
-    pin_cache.clear_pin();
+pub fn clear_pin(
+    database: tauri::State<'_, crate::db::Database>,
+    clients: tauri::State<'_, crate::http_client::ClientManager>,
+) {
+    // Pooled clients hold decrypted credentials; drop them with the derived key
+    crate::auto_lock::lock(&database, &clients);
 }
 
+/// Unlocks the app; only the key derived from the PIN is kept, in the database
 #[tauri::command]
 pub fn verify_pin(
     pin: String,
     database: tauri::State<'_, crate::db::Database>,
-    pin_cache: tauri::State<'_, PinCache>,
-) -> Result<bool, String> {
-    let is_valid = database
-        .verify_pin(&pin)
-        .map_err(|e| format!("Failed to verify PIN: {}", e))?;
+    idle_lock: tauri::State<'_, crate::auto_lock::IdleLock>,
+) -> Result<bool, CommandError> {
+    let pin = Zeroizing::new(pin);
+    let is_valid = database.verify_pin(&pin)?;
 
     if is_valid {
-        pin_cache.set_pin(pin);
+        idle_lock.touch();
     }
 
     Ok(is_valid)
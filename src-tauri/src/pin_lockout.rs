@@ -0,0 +1,169 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::local_users::Role;
+use crate::migrations;
+
+/// Failures after which the PIN is no longer checked at all
+pub const MAX_PIN_ATTEMPTS: u32 = 10;
+/// Failures allowed before each further attempt has to wait
+const FREE_PIN_ATTEMPTS: u32 = 4;
+
+/// How long the next attempt has to wait after this many consecutive failures
+fn retry_delay_secs(failures: u32) -> i64 {
+    match failures {
+        0..=FREE_PIN_ATTEMPTS => 0,
+        5 => 30,
+        6 => 60,
+        7 => 5 * 60,
+        8 => 15 * 60,
+        _ => 60 * 60,
+    }
+}
+
+/// Failed PIN attempts, persisted so restarting the app doesn't reset them
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PinLockout {
+    pub failed_attempts: u32,
+    /// Unix time before which no attempt is checked, 0 when there is no delay
+    pub retry_after: i64,
+    /// Failures that erase all stored credentials, when enabled
+    pub wipe_after: Option<u32>,
+}
+
+impl PinLockout {
+    pub fn is_locked_out(&self) -> bool {
+        self.failed_attempts >= MAX_PIN_ATTEMPTS
+    }
+
+    pub fn should_wipe(&self) -> bool {
+        self.wipe_after
+            .is_some_and(|threshold| self.failed_attempts >= threshold)
+    }
+
+    /// Refuses the attempt while locked out or before the retry delay has passed
+    pub fn check(&self, now: i64) -> Result<(), String> {
+        if self.is_locked_out() {
+            return Err(
+                "Too many failed PIN attempts. Reset the app to set it up again.".to_string(),
+            );
+        }
+        if now < self.retry_after {
+            return Err(format!(
+                "Too many failed PIN attempts. Try again in {} seconds.",
+                self.retry_after - now
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn add_columns(conn: &Connection) -> Result<()> {
+        for (column, definition) in [
+            ("failed_pin_attempts", "INTEGER NOT NULL DEFAULT 0"),
+            ("pin_retry_after", "INTEGER NOT NULL DEFAULT 0"),
+            ("pin_wipe_after", "INTEGER"),
+        ] {
+            migrations::add_column_if_missing(conn, "app_settings", column, definition)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let lockout = conn
+            .query_row(
+                "SELECT failed_pin_attempts, pin_retry_after, pin_wipe_after
+                 FROM app_settings WHERE id = 1",
+                [],
+                |row| {
+                    Ok(PinLockout {
+                        failed_attempts: row.get(0)?,
+                        retry_after: row.get(1)?,
+                        wipe_after: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(lockout.unwrap_or(PinLockout {
+            failed_attempts: 0,
+            retry_after: 0,
+            wipe_after: None,
+        }))
+    }
+
+    pub fn record_failure(conn: &Connection, now: i64) -> Result<Self> {
+        let mut lockout = Self::load(conn)?;
+        lockout.failed_attempts += 1;
+        lockout.retry_after = now + retry_delay_secs(lockout.failed_attempts);
+
+        conn.execute(
+            "UPDATE app_settings SET failed_pin_attempts = ?1, pin_retry_after = ?2 WHERE id = 1",
+            params![lockout.failed_attempts, lockout.retry_after],
+        )?;
+        Ok(lockout)
+    }
+
+    pub fn reset(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "UPDATE app_settings SET failed_pin_attempts = 0, pin_retry_after = 0 WHERE id = 1",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_wipe_after(conn: &Connection, threshold: Option<u32>) -> Result<()> {
+        conn.execute(
+            "UPDATE app_settings SET pin_wipe_after = ?1 WHERE id = 1",
+            params![threshold],
+        )?;
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn get_pin_lockout_status(database: State<Database>) -> Result<PinLockout, CommandError> {
+    Ok(database.pin_lockout()?)
+}
+
+/// Enables or, with `None`, disables erasing credentials after repeated failures
+///
+/// Takes the PIN so that whoever is guessing it can't switch the wipe off.
+#[tauri::command]
+pub fn set_pin_wipe_threshold(
+    pin: String,
+    threshold: Option<u32>,
+    database: State<Database>,
+) -> Result<(), CommandError> {
+    if let Some(threshold) = threshold {
+        if threshold == 0 || threshold > MAX_PIN_ATTEMPTS {
+            return Err(format!(
+                "Wipe threshold must be between 1 and {} attempts",
+                MAX_PIN_ATTEMPTS
+            )
+            .into());
+        }
+    }
+
+    database.require_role(Role::Admin)?;
+    let user = database
+        .signed_in_user()
+        .ok_or_else(|| "User needs to authenticate first".to_string())?;
+    if !database.check_user_pin(&user.name, &pin)? {
+        return Err("PIN is incorrect".into());
+    }
+
+    Ok(database.set_pin_wipe_threshold(threshold)?)
+}
+
+/// Erases all profiles and the PIN once the lockout is permanent, so setup can run again
+#[tauri::command]
+pub fn reset_locked_app(database: State<Database>) -> Result<(), CommandError> {
+    if !database.pin_lockout()?.is_locked_out() {
+        return Err("The app is not locked out".into());
+    }
+
+    Ok(database.wipe_credentials()?)
+}
@@ -1,43 +1,49 @@
+use crate::active_profile::SelectedProfile;
+use crate::confirmation::{Confirmations, DestructiveAction};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ApiRequest, ClientManager};
+use crate::local_users::Role;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use tauri::State;
+use zeroize::Zeroizing;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RebootResponse {
     status: String,
 }
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
-
 #[tauri::command]
-pub async fn reboot_firewall(database: State<'_, Database>) -> Result<RebootResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/core/system/reboot");
+pub async fn reboot_firewall(
+    confirmation_token: String,
+    pin: String,
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<RebootResponse, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let pin = Zeroizing::new(pin);
+    confirmations.confirm_selected(
+        &database,
+        &confirmation_token,
+        &pin,
+        &DestructiveAction::RebootFirewall,
+        profile,
+    )?;
+    let client = clients.client(&database, profile).await?;
 
     let mut headers = HeaderMap::new();
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(serde_json::json!({})),
-        Some(headers),
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .send(ApiRequest::post("/api/core/system/reboot", serde_json::json!({})).headers(headers))
+        .await?;
 
     response
         .json::<RebootResponse>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
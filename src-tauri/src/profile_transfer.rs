@@ -0,0 +1,376 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use log::info;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::State;
+use zeroize::Zeroizing;
+
+use crate::db::{ApiInfo, DashboardWidgetPref, Database};
+use crate::error::CommandError;
+use crate::http_client::ClientManager;
+use crate::local_users::Role;
+
+const EXPORT_FORMAT: &str = "opnmanager-profiles";
+const EXPORT_VERSION: u32 = 1;
+const MIN_PASSPHRASE_LEN: usize = 10;
+
+/// Export files leave the device, so their key costs more than the one for app.db
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+const KDF_ITERATIONS: u32 = 3;
+const KDF_PARALLELISM: u32 = 1;
+
+/// Costs accepted from an import file, so a crafted one can't make the key trivial to
+/// guess or exhaust memory while deriving it
+const MIN_KDF_MEMORY_KIB: u32 = KDF_MEMORY_KIB / 4;
+const MAX_KDF_MEMORY_KIB: u32 = KDF_MEMORY_KIB * 8;
+const MAX_KDF_ITERATIONS: u32 = KDF_ITERATIONS * 10;
+const MAX_KDF_PARALLELISM: u32 = 8;
+
+/// Encrypted export file; the KDF cost is stored so older files still open if it changes
+#[derive(Serialize, Deserialize)]
+struct ExportFile {
+    format: String,
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+}
+
+/// Decrypted contents of an export file
+#[derive(Serialize, Deserialize)]
+struct ProfileBundle {
+    profiles: Vec<ExportedProfile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedProfile {
+    api_info: ApiInfo,
+    #[serde(default)]
+    dashboard: Vec<DashboardWidgetPref>,
+}
+
+/// A profile found in an export file, as shown before importing
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportCandidate {
+    pub profile_name: String,
+    pub api_url: String,
+    pub port: u16,
+    /// A profile with this name already exists and needs a [`CollisionAction`]
+    pub exists: bool,
+}
+
+/// What to do with an imported profile whose name is already taken
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum CollisionAction {
+    /// Replace the existing profile's connection settings, keeping its id and default flag
+    Merge,
+    Rename {
+        name: String,
+    },
+    Skip,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub imported: Vec<String>,
+    pub merged: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<Zeroizing<Vec<u8>>, String> {
+    let salt = general_purpose::STANDARD
+        .decode(&kdf.salt)
+        .map_err(|e| format!("Invalid export file salt: {}", e))?;
+    if !(MIN_KDF_MEMORY_KIB..=MAX_KDF_MEMORY_KIB).contains(&kdf.memory_kib)
+        || !(1..=MAX_KDF_ITERATIONS).contains(&kdf.iterations)
+        || !(1..=MAX_KDF_PARALLELISM).contains(&kdf.parallelism)
+    {
+        return Err("Unsupported export file key parameters".to_string());
+    }
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| format!("Invalid export file key parameters: {}", e))?;
+
+    let mut key = Zeroizing::new(vec![0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| format!("Failed to derive export key: {}", e))?;
+    Ok(key)
+}
+
+fn encrypt_bundle(bundle: &ProfileBundle, passphrase: &str) -> Result<String, String> {
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(bundle).map_err(|e| format!("Failed to serialize profiles: {}", e))?,
+    );
+
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    thread_rng().fill(&mut salt);
+    thread_rng().fill(&mut nonce);
+
+    let kdf = KdfParams {
+        memory_kib: KDF_MEMORY_KIB,
+        iterations: KDF_ITERATIONS,
+        parallelism: KDF_PARALLELISM,
+        salt: general_purpose::STANDARD.encode(salt),
+    };
+    let key = derive_key(passphrase, &kdf)?;
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    serde_json::to_string_pretty(&ExportFile {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        kdf,
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    })
+    .map_err(|e| format!("Failed to write export file: {}", e))
+}
+
+fn decrypt_bundle(contents: &str, passphrase: &str) -> Result<ProfileBundle, String> {
+    let file: ExportFile = serde_json::from_str(contents)
+        .map_err(|_| "This is not an OPNManager profile export".to_string())?;
+    if file.format != EXPORT_FORMAT {
+        return Err("This is not an OPNManager profile export".to_string());
+    }
+    if file.version > EXPORT_VERSION {
+        return Err(format!(
+            "This export was made by a newer version of OPNManager (format {})",
+            file.version
+        ));
+    }
+
+    let nonce = general_purpose::STANDARD
+        .decode(&file.nonce)
+        .map_err(|e| format!("Invalid export file nonce: {}", e))?;
+    let ciphertext = general_purpose::STANDARD
+        .decode(&file.ciphertext)
+        .map_err(|e| format!("Invalid export file contents: {}", e))?;
+    if nonce.len() != 12 {
+        return Err("Invalid export file nonce".to_string());
+    }
+
+    let key = derive_key(passphrase, &file.kdf)?;
+    let plaintext = Zeroizing::new(
+        ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| "Wrong passphrase or damaged export file".to_string())?,
+    );
+
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| format!("Failed to read exported profiles: {}", e))
+}
+
+fn require_unlocked(database: &Database) -> Result<(), String> {
+    if database.is_unlocked() {
+        Ok(())
+    } else {
+        Err("PIN authentication required. Please login again.".to_string())
+    }
+}
+
+fn existing_profile_names(database: &Database) -> Result<HashSet<String>, String> {
+    Ok(database
+        .list_api_profiles()
+        .map_err(|e| format!("Failed to get API profiles: {}", e))?
+        .into_iter()
+        .map(|profile| profile.profile_name)
+        .collect())
+}
+
+/// Encrypts the named profiles and their dashboard layouts with `passphrase`
+///
+/// Returns the file contents for the frontend to save or share.
+#[tauri::command]
+pub fn export_profiles(
+    profile_names: Vec<String>,
+    passphrase: String,
+    database: State<Database>,
+) -> Result<String, CommandError> {
+    database.require_role(Role::Admin)?;
+    let passphrase = Zeroizing::new(passphrase);
+    require_unlocked(&database)?;
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )
+        .into());
+    }
+    if profile_names.is_empty() {
+        return Err("Select at least one profile to export".into());
+    }
+
+    let mut profiles = Vec::new();
+    for profile_name in &profile_names {
+        let api_info = database
+            .get_api_info(Some(profile_name))
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("API profile '{}' not found", profile_name))?;
+        if api_info.api_key.is_empty() && api_info.api_secret.is_empty() {
+            return Err("PIN authentication required. Please login again.".into());
+        }
+
+        let mut dashboard: Vec<DashboardWidgetPref> = database
+            .get_dashboard_preferences(api_info.id)
+            .map_err(|e| format!("Failed to get dashboard preferences: {}", e))?
+            .into_values()
+            .collect();
+        dashboard.sort_by_key(|pref| pref.position);
+
+        profiles.push(ExportedProfile {
+            api_info,
+            dashboard,
+        });
+    }
+
+    info!("Exporting {} profiles", profiles.len());
+    Ok(encrypt_bundle(&ProfileBundle { profiles }, &passphrase)?)
+}
+
+/// Lists the profiles in an export file and which of them collide with existing names
+#[tauri::command]
+pub fn preview_profile_import(
+    contents: String,
+    passphrase: String,
+    database: State<Database>,
+) -> Result<Vec<ImportCandidate>, CommandError> {
+    let passphrase = Zeroizing::new(passphrase);
+    require_unlocked(&database)?;
+
+    let bundle = decrypt_bundle(&contents, &passphrase)?;
+    let existing = existing_profile_names(&database)?;
+
+    Ok(bundle
+        .profiles
+        .into_iter()
+        .map(|profile| ImportCandidate {
+            exists: existing.contains(&profile.api_info.profile_name),
+            profile_name: profile.api_info.profile_name,
+            api_url: profile.api_info.api_url,
+            port: profile.api_info.port,
+        })
+        .collect())
+}
+
+/// Imports every profile in an export file
+///
+/// Profiles whose name is already taken need an entry in `collisions`; the import is
+/// refused up front when one is missing or a name appears twice in the file.
+#[tauri::command]
+pub fn import_profiles(
+    contents: String,
+    passphrase: String,
+    collisions: HashMap<String, CollisionAction>,
+    database: State<Database>,
+    clients: State<ClientManager>,
+) -> Result<ImportSummary, CommandError> {
+    database.require_role(Role::Admin)?;
+    let passphrase = Zeroizing::new(passphrase);
+    require_unlocked(&database)?;
+
+    let bundle = decrypt_bundle(&contents, &passphrase)?;
+    let existing = existing_profile_names(&database)?;
+
+    let mut bundle_names = HashSet::new();
+    for profile in &bundle.profiles {
+        if !bundle_names.insert(profile.api_info.profile_name.clone()) {
+            return Err(format!(
+                "The export file contains '{}' more than once",
+                profile.api_info.profile_name
+            )
+            .into());
+        }
+    }
+
+    let mut summary = ImportSummary::default();
+    let mut unresolved = Vec::new();
+    let mut renamed = HashSet::new();
+    let mut profiles = Vec::new();
+    let mut merged = Vec::new();
+    for ExportedProfile {
+        mut api_info,
+        dashboard,
+    } in bundle.profiles
+    {
+        let name = api_info.profile_name.clone();
+        let mut existing_profile = None;
+        if existing.contains(&name) {
+            match collisions.get(&name) {
+                None => {
+                    unresolved.push(name);
+                    continue;
+                }
+                Some(CollisionAction::Skip) => {
+                    summary.skipped.push(name);
+                    continue;
+                }
+                Some(CollisionAction::Rename { name: new_name }) => {
+                    let new_name = new_name.trim();
+                    if new_name.is_empty() {
+                        return Err(format!("Choose a new name for '{}'", name).into());
+                    }
+                    if existing.contains(new_name)
+                        || bundle_names.contains(new_name)
+                        || !renamed.insert(new_name.to_string())
+                    {
+                        return Err(format!("A profile named '{}' already exists", new_name).into());
+                    }
+                    api_info.profile_name = new_name.to_string();
+                }
+                Some(CollisionAction::Merge) => {
+                    existing_profile = database
+                        .get_api_info(Some(&name))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+
+        // Only a merged profile keeps being the default; imports never take it over
+        api_info.is_default = existing_profile
+            .as_ref()
+            .is_some_and(|profile| profile.is_default);
+        merged.push(existing_profile.is_some());
+        profiles.push((api_info, dashboard));
+    }
+    if !unresolved.is_empty() {
+        return Err(format!(
+            "Choose whether to merge, rename or skip: {}",
+            unresolved.join(", ")
+        )
+        .into());
+    }
+
+    // All profiles are saved in one transaction, so a failure leaves nothing half-imported
+    let ids = database.import_profiles(&profiles)?;
+    for (((api_info, _), id), merged) in profiles.into_iter().zip(ids).zip(merged) {
+        if merged {
+            clients.invalidate(id);
+            summary.merged.push(api_info.profile_name);
+        } else {
+            summary.imported.push(api_info.profile_name);
+        }
+    }
+
+    info!(
+        "Imported {} profiles, merged {}, skipped {}",
+        summary.imported.len(),
+        summary.merged.len(),
+        summary.skipped.len()
+    );
+    Ok(summary)
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyKind {
+    /// HTTP proxy, tunnelling TLS with CONNECT
+    Http,
+    /// SOCKS5 proxy, with the firewall hostname resolved by the proxy
+    Socks5,
+}
+
+/// Proxy a profile's API traffic is routed through, e.g. a SOCKS5 bastion
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProxySettings {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<Zeroizing<String>>,
+}
+
+impl ProxySettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let host = self.host.trim();
+        if host.is_empty() {
+            return Err("Proxy host is required".to_string());
+        }
+        if host.contains("://") || host.contains('/') {
+            return Err(
+                "Proxy host must be a hostname or IP address, without a scheme".to_string(),
+            );
+        }
+        if self.port == 0 {
+            return Err("Proxy port is required".to_string());
+        }
+        if self.password.is_some() && self.username.is_none() {
+            return Err("A proxy password requires a username".to_string());
+        }
+        Ok(())
+    }
+
+    /// Proxy URL without credentials, safe to log
+    pub fn url(&self) -> String {
+        let scheme = match self.kind {
+            ProxyKind::Http => "http",
+            ProxyKind::Socks5 => "socks5h",
+        };
+        let host = self.host.trim();
+        if host.contains(':') && !host.starts_with('[') {
+            format!("{}://[{}]:{}", scheme, host, self.port)
+        } else {
+            format!("{}://{}:{}", scheme, host, self.port)
+        }
+    }
+
+    pub fn to_reqwest(&self) -> Result<reqwest::Proxy, String> {
+        self.validate()?;
+
+        let proxy = reqwest::Proxy::all(self.url())
+            .map_err(|e| format!("Invalid proxy settings: {}", e))?;
+
+        Ok(match &self.username {
+            Some(username) => proxy.basic_auth(
+                username,
+                self.password.as_deref().map_or("", String::as_str),
+            ),
+            None => proxy,
+        })
+    }
+}
@@ -0,0 +1,178 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Queueing shorter than this is normal scheduling noise and isn't logged
+const LOG_WAIT_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// How hard a single firewall may be hit, tuned down for small appliances
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimits {
+    /// Requests allowed in flight at the same time
+    pub max_concurrent: u32,
+    /// Sustained request rate the token bucket refills at
+    pub requests_per_second: f64,
+    /// Requests that may go out back to back before the rate applies
+    pub burst: u32,
+}
+
+pub const DEFAULT_LIMITS: RateLimits = RateLimits {
+    max_concurrent: 4,
+    requests_per_second: 10.0,
+    burst: 20,
+};
+
+impl RateLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_concurrent == 0 {
+            return Err("At least one concurrent request must be allowed".to_string());
+        }
+        if !self.requests_per_second.is_finite() || self.requests_per_second <= 0.0 {
+            return Err("Requests per second must be greater than zero".to_string());
+        }
+        if self.burst == 0 {
+            return Err("Burst size must be at least one request".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Who is waiting on a request, so user actions aren't stuck behind pollers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Triggered by the user, served first
+    Interactive,
+    /// Periodic refreshes such as log tailing and traffic graphs
+    Background,
+}
+
+struct LimiterState {
+    limits: RateLimits,
+    in_flight: u32,
+    interactive_waiting: u32,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl LimiterState {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limits.requests_per_second).min(self.limits.burst as f64);
+        self.refilled_at = now;
+    }
+}
+
+/// Per-profile concurrency cap and token bucket in front of every API request
+///
+/// Background requests only get a slot when no interactive request is queued.
+pub struct RequestLimiter {
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+/// A request slot, returned to the limiter on drop
+pub struct RequestPermit<'a> {
+    limiter: &'a RequestLimiter,
+}
+
+impl Drop for RequestPermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().in_flight -= 1;
+        self.limiter.released.notify_waiters();
+    }
+}
+
+/// Counts a queued interactive request for as long as it waits, even if it is cancelled
+struct InteractiveWaiting<'a> {
+    limiter: &'a RequestLimiter,
+}
+
+impl<'a> InteractiveWaiting<'a> {
+    fn new(limiter: &'a RequestLimiter) -> Self {
+        limiter.state.lock().unwrap().interactive_waiting += 1;
+        Self { limiter }
+    }
+}
+
+impl Drop for InteractiveWaiting<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().interactive_waiting -= 1;
+        self.limiter.released.notify_waiters();
+    }
+}
+
+impl RequestLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                limits,
+                in_flight: 0,
+                interactive_waiting: 0,
+                tokens: limits.burst as f64,
+                refilled_at: Instant::now(),
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Applies new limits to queued and future requests
+    pub fn configure(&self, limits: RateLimits) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.refill();
+            state.limits = limits;
+            state.tokens = state.tokens.min(limits.burst as f64);
+        }
+        self.released.notify_waiters();
+    }
+
+    /// Waits for a free slot and a token, logging how long `label` was queued
+    pub async fn acquire(&self, priority: Priority, label: &str) -> RequestPermit<'_> {
+        let started = Instant::now();
+        let _waiting = (priority == Priority::Interactive).then(|| InteractiveWaiting::new(self));
+
+        loop {
+            // Registered before checking so a release between the check and the wait isn't missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            let token_wait = {
+                let mut state = self.state.lock().unwrap();
+                let slot_free = state.in_flight < state.limits.max_concurrent;
+                let yields = priority == Priority::Background && state.interactive_waiting > 0;
+
+                if !slot_free || yields {
+                    None
+                } else {
+                    state.refill();
+                    if state.tokens >= 1.0 {
+                        state.tokens -= 1.0;
+                        state.in_flight += 1;
+
+                        let waited = started.elapsed();
+                        if waited >= LOG_WAIT_THRESHOLD {
+                            info!(
+                                "{} waited {:?} in the request queue ({} in flight)",
+                                label, waited, state.in_flight
+                            );
+                        }
+                        return RequestPermit { limiter: self };
+                    }
+                    Some(Duration::from_secs_f64(
+                        (1.0 - state.tokens) / state.limits.requests_per_second,
+                    ))
+                }
+            };
+
+            match token_wait {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => released.await,
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::retry;
+
+/// OPNsense command prefixes that change the firewall, whatever their HTTP method
+const MUTATING_COMMAND_PREFIXES: &[&str] = &[
+    "set",
+    "add",
+    "del",
+    "toggle",
+    "apply",
+    "reconfigure",
+    "reboot",
+    "restart",
+    "start",
+    "stop",
+    "flush",
+    "wake",
+    "install",
+    "update",
+    "upgrade",
+    "activate",
+    "remove",
+];
+
+/// Whether a request could change the firewall, and so is refused on read-only profiles
+///
+/// Decided by the OPNsense command alone, since OPNsense also uses POST for commands
+/// that only read, such as `firmware/check` and `firmware/changelog`. Searches, getters
+/// and status polls are never mutating, even when their name starts like one that is.
+pub fn is_mutating(endpoint: &str) -> bool {
+    let command = retry::endpoint_command(endpoint);
+
+    // e.g. firmware/upgradestatus only polls a running upgrade
+    if retry::is_read_command(&command) || command.ends_with("status") {
+        return false;
+    }
+
+    MUTATING_COMMAND_PREFIXES
+        .iter()
+        .any(|prefix| command.starts_with(prefix))
+}
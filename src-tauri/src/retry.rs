@@ -0,0 +1,123 @@
+use rand::Rng;
+use reqwest::Method;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::http_client::ApiError;
+
+/// Endpoints that change firewall state in a way that must never be replayed,
+/// whatever their HTTP method
+const NEVER_RETRY: &[&str] = &[
+    "/reboot",
+    "/apply",
+    "/reconfigure",
+    "/restart",
+    "/firmware/update",
+    "/firmware/upgrade",
+    "/firmware/install",
+    "/wake",
+];
+
+/// OPNsense command prefixes that only read data even when sent as POST
+const READ_COMMAND_PREFIXES: &[&str] = &["search", "get", "list", "status"];
+
+/// Exponential backoff with jitter for transient failures
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+pub const DEFAULT_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_millis(250),
+    max_delay: Duration::from_secs(4),
+};
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (starting at 1), picked uniformly from the
+    /// upper half of the exponential window so concurrent retries spread out
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let ceiling = exponential.min(self.max_delay).as_millis() as u64;
+        let jittered = rand::thread_rng().gen_range(ceiling / 2..=ceiling);
+        Duration::from_millis(jittered)
+    }
+}
+
+/// Caps retries to a fraction of recent traffic so a dead firewall isn't hammered
+///
+/// Every request deposits a fraction of a token and every retry withdraws a whole one.
+pub struct RetryBudget {
+    tokens: Mutex<f64>,
+}
+
+const BUDGET_MAX_TOKENS: f64 = 10.0;
+const BUDGET_DEPOSIT_PER_REQUEST: f64 = 0.2;
+
+impl RetryBudget {
+    pub fn new() -> Self {
+        Self {
+            tokens: Mutex::new(BUDGET_MAX_TOKENS),
+        }
+    }
+
+    pub fn deposit(&self) {
+        let mut tokens = self.tokens.lock().unwrap();
+        *tokens = (*tokens + BUDGET_DEPOSIT_PER_REQUEST).min(BUDGET_MAX_TOKENS);
+    }
+
+    pub fn try_withdraw(&self) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Whether replaying `endpoint` is harmless: reads, searches and status calls
+pub fn is_retry_safe(method: &Method, endpoint: &str) -> bool {
+    let path = endpoint.split('?').next().unwrap_or(endpoint);
+
+    // Match whole segments so e.g. firmware/upgradestatus stays retryable
+    let never_retry = NEVER_RETRY
+        .iter()
+        .any(|marker| path.ends_with(marker) || path.contains(&format!("{}/", marker)));
+    if never_retry {
+        return false;
+    }
+
+    *method == Method::GET || is_read_command(&endpoint_command(endpoint))
+}
+
+/// Lowercased command of `/api/<module>/<controller>/<command>[/params]`
+pub fn endpoint_command(endpoint: &str) -> String {
+    let path = endpoint.split('?').next().unwrap_or(endpoint);
+    path.trim_start_matches('/')
+        .split('/')
+        .nth(3)
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Whether an OPNsense command only reads data, whatever the HTTP method
+pub fn is_read_command(command: &str) -> bool {
+    READ_COMMAND_PREFIXES
+        .iter()
+        .any(|prefix| command.starts_with(prefix))
+        || command.ends_with("info")
+}
+
+/// Whether an error is transient and worth another attempt
+pub fn is_transient(error: &ApiError) -> bool {
+    match error {
+        ApiError::Timeout { .. } | ApiError::Connection { .. } | ApiError::Dns { .. } => true,
+        ApiError::Status { status, .. } => matches!(status, 502..=504),
+        _ => false,
+    }
+}
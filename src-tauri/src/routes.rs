@@ -1,7 +1,13 @@
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::search_grid::{self, SearchPage, SearchQuery, SortOrder};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use tauri::State;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -13,15 +19,6 @@ pub struct Route {
     descr: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RoutesResponse {
-    rows: Vec<Route>,
-    #[serde(rename = "rowCount")]
-    row_count: u32,
-    total: u32,
-    current: u32,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GatewayOption {
     value: String,
@@ -71,83 +68,56 @@ pub struct RouteTableEntry {
     intf_description: String,
 }
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
-
 #[tauri::command]
-pub async fn get_routes(database: State<'_, Database>) -> Result<RoutesResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/routes/routes/searchroute");
-
-    let payload = json!({
-        "current": 1,
-        "rowCount": -1,
-        "sort": {},
-        "searchPhrase": ""
-    });
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-
-    response
-        .json::<RoutesResponse>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+pub async fn get_routes(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    current_page: u32,
+    row_count: u32,
+    sort: Option<BTreeMap<String, SortOrder>>,
+    search_phrase: String,
+) -> Result<SearchPage<Route>, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let query = SearchQuery::new()
+        .page(current_page)
+        .rows(row_count)
+        .sorted_by(sort.unwrap_or_default())
+        .search(search_phrase);
+
+    Ok(search_grid::search(&client, "/api/routes/routes/searchroute", &query).await?)
 }
 
 #[tauri::command]
-pub async fn get_route_info(database: State<'_, Database>) -> Result<RouteInfoResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/routes/routes/getroute");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_route_info(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<RouteInfoResponse, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/routes/routes/getroute").await?;
 
     response
         .json::<RouteInfoResponse>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn add_route(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     network: String,
     gateway: String,
     description: String,
     disabled: bool,
-) -> Result<AddRouteResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/routes/routes/addroute");
+) -> Result<AddRouteResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     let payload = json!({
         "route": {
@@ -160,16 +130,7 @@ pub async fn add_route(
 
     println!("Sending payload: {:?}", payload);
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post("/api/routes/routes/addroute", payload).await?;
 
     let response_text = response
         .text()
@@ -178,133 +139,132 @@ pub async fn add_route(
 
     println!("Received response: {}", response_text);
 
-    let result = serde_json::from_str(&response_text).map_err(|e| {
+    let result: AddRouteResponse = serde_json::from_str(&response_text).map_err(|e| {
         format!(
             "Failed to parse response: {} (Response was: {})",
             e, response_text
         )
     })?;
 
-    apply_changes(database).await?;
+    if result.result == "saved" {
+        Change::new(Module::Routes, "add")
+            .target(&result.uuid)
+            .after(get_route_item(&client, &result.uuid).await.ok())
+            .record(&database, &client);
+    }
+
+    apply_changes(profile, database, clients).await?;
 
     Ok(result)
 }
 
 #[tauri::command]
-pub async fn delete_route(database: State<'_, Database>, uuid: String) -> Result<(), String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, &format!("/api/routes/routes/delroute/{}", uuid));
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn delete_route(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<(), CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/routes/routes/delroute/{}", uuid);
+
+    let before = get_route_item(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await?;
 
     if !response.status().is_success() {
-        return Err(format!("Failed to delete route: {}", response.status()));
+        return Err(format!("Failed to delete route: {}", response.status()).into());
     }
 
-    apply_changes(database).await?;
+    Change::new(Module::Routes, "delete")
+        .target(&uuid)
+        .before(before)
+        .record(&database, &client);
+
+    apply_changes(profile, database, clients).await?;
 
     Ok(())
 }
 
 #[tauri::command]
 pub async fn toggle_route(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
-) -> Result<ToggleResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(
-        &api_info,
-        &format!("/api/routes/routes/toggleroute/{}", uuid),
-    );
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+) -> Result<ToggleResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/routes/routes/toggleroute/{}", uuid);
+
+    let before = get_route_item(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await?;
 
     let result = response
         .json::<ToggleResponse>()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-    apply_changes(database).await?;
+    Change::new(Module::Routes, "toggle")
+        .target(&uuid)
+        .before(before)
+        .after(get_route_item(&client, &uuid).await.ok())
+        .record(&database, &client);
+
+    apply_changes(profile, database, clients).await?;
 
     Ok(result)
 }
 
 #[tauri::command]
-pub async fn apply_changes(database: State<'_, Database>) -> Result<ReconfigureResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/routes/routes/reconfigure");
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn apply_changes(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<ReconfigureResponse, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .post("/api/routes/routes/reconfigure", json!({}))
+        .await?;
 
     response
         .json::<ReconfigureResponse>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
+}
+
+async fn get_route_item(client: &OpnsenseClient, uuid: &str) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/routes/routes/getroute/{}", uuid);
+
+    let response = client.get(&endpoint).await?;
+
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn get_route_table(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Vec<RouteTableEntry>, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/diagnostics/interface/getRoutes");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<Vec<RouteTableEntry>, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/diagnostics/interface/getRoutes").await?;
 
     response
         .json::<Vec<RouteTableEntry>>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
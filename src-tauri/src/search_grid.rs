@@ -0,0 +1,165 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::http_client::{ApiError, ApiRequest, OpnsenseClient};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// Page size when walking a whole grid, small enough for slow appliances to answer quickly
+pub const STREAM_PAGE_SIZE: u32 = 500;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Request body understood by OPNsense `search*` grid endpoints
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchQuery {
+    /// 1-based page number
+    pub current: u32,
+    #[serde(rename = "rowCount")]
+    pub row_count: u32,
+    #[serde(default)]
+    pub sort: BTreeMap<String, SortOrder>,
+    #[serde(rename = "searchPhrase", default)]
+    pub search_phrase: String,
+    /// Endpoint-specific fields sent alongside, such as the rule interface
+    #[serde(flatten)]
+    pub filters: Map<String, Value>,
+}
+
+impl SearchQuery {
+    pub fn new() -> Self {
+        Self {
+            current: 1,
+            row_count: DEFAULT_PAGE_SIZE,
+            sort: BTreeMap::new(),
+            search_phrase: String::new(),
+            filters: Map::new(),
+        }
+    }
+
+    pub fn page(mut self, current: u32) -> Self {
+        self.current = current.max(1);
+        self
+    }
+
+    pub fn rows(mut self, row_count: u32) -> Self {
+        self.row_count = row_count.max(1);
+        self
+    }
+
+    pub fn sort_by(mut self, column: impl Into<String>, order: SortOrder) -> Self {
+        self.sort.insert(column.into(), order);
+        self
+    }
+
+    /// Adds the column order a grid asked for, as `{ column: "asc" | "desc" }`
+    pub fn sorted_by(mut self, sort: BTreeMap<String, SortOrder>) -> Self {
+        self.sort.extend(sort);
+        self
+    }
+
+    pub fn search(mut self, phrase: impl Into<String>) -> Self {
+        self.search_phrase = phrase.into();
+        self
+    }
+
+    pub fn filter(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.filters.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// One page of grid results, serialized back in the shape OPNsense returns it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchPage<T> {
+    pub rows: Vec<T>,
+    #[serde(rename = "rowCount", default)]
+    pub row_count: u32,
+    #[serde(default)]
+    pub total: u32,
+    #[serde(default)]
+    pub current: u32,
+}
+
+/// Fetches a single page of a grid
+pub async fn search<T: DeserializeOwned>(
+    client: &OpnsenseClient,
+    endpoint: &str,
+    query: &SearchQuery,
+) -> Result<SearchPage<T>, ApiError> {
+    let payload = serde_json::to_value(query)
+        .map_err(|e| ApiError::Client(format!("Failed to build search query: {}", e)))?;
+
+    // Searching doesn't change anything, so a failed attempt is safe to repeat
+    let response = client
+        .send(ApiRequest::post(endpoint, payload).idempotent(true))
+        .await?;
+
+    response
+        .json::<SearchPage<T>>()
+        .await
+        .map_err(|e| ApiError::Parse(format!("Failed to parse {} results: {}", endpoint, e)))
+}
+
+/// Walks every row of a grid, requesting the next page only once the buffered one is used up
+pub struct SearchPager<'a, T> {
+    client: &'a OpnsenseClient,
+    endpoint: String,
+    query: SearchQuery,
+    buffered: VecDeque<T>,
+    seen: u32,
+    exhausted: bool,
+}
+
+impl<'a, T: DeserializeOwned> SearchPager<'a, T> {
+    pub fn new(
+        client: &'a OpnsenseClient,
+        endpoint: impl Into<String>,
+        query: SearchQuery,
+    ) -> Self {
+        Self {
+            client,
+            endpoint: endpoint.into(),
+            query,
+            buffered: VecDeque::new(),
+            seen: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Next page of rows, or `None` after the last one
+    pub async fn next_page(&mut self) -> Result<Option<Vec<T>>, ApiError> {
+        if !self.buffered.is_empty() {
+            return Ok(Some(self.buffered.drain(..).collect()));
+        }
+        if self.exhausted {
+            return Ok(None);
+        }
+
+        let page = search::<T>(self.client, &self.endpoint, &self.query).await?;
+        let received = page.rows.len() as u32;
+        self.seen += received;
+        self.query.current += 1;
+
+        // Short pages end the walk even when `total` is missing or stale
+        self.exhausted =
+            received < self.query.row_count || (page.total > 0 && self.seen >= page.total);
+
+        Ok((received > 0).then_some(page.rows))
+    }
+
+    /// Next row, fetching another page when needed
+    pub async fn next_row(&mut self) -> Result<Option<T>, ApiError> {
+        if self.buffered.is_empty() {
+            if let Some(rows) = self.next_page().await? {
+                self.buffered.extend(rows);
+            }
+        }
+        Ok(self.buffered.pop_front())
+    }
+}
@@ -1,9 +1,16 @@
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
+use crate::confirmation::{Confirmations, DestructiveAction};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::search_grid::{self, SearchPage, SearchQuery};
 use log::info;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tauri::State;
+use zeroize::Zeroizing;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Snapshot {
@@ -16,167 +23,89 @@ pub struct Snapshot {
     pub created: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct SnapshotSearchResponse {
-    pub total: u32,
-    pub rowCount: u32,
-    pub current: u32,
-    pub rows: Vec<Snapshot>,
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NewSnapshotResponse {
     pub name: String,
     pub uuid: String,
 }
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
-
 #[tauri::command]
-pub async fn is_snapshots_supported(database: State<'_, Database>) -> Result<bool, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/core/snapshots/is_supported/");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-
-    let result = response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+pub async fn is_snapshots_supported(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<bool, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
-    if let Some(supported) = result.get("supported").and_then(|v| v.as_bool()) {
-        Ok(supported)
-    } else {
-        Ok(false)
-    }
+    Ok(clients.capabilities(&client).await?.snapshots)
 }
 
 #[tauri::command]
 pub async fn get_snapshots(
     current_page: u32,
     rows_per_page: u32,
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<SnapshotSearchResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+    clients: State<'_, ClientManager>,
+) -> Result<SearchPage<Snapshot>, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
-    let url = build_api_url(&api_info, "/api/core/snapshots/search");
+    let query = SearchQuery::new().page(current_page).rows(rows_per_page);
 
-    let payload = json!({
-        "current": current_page,
-        "rowCount": rows_per_page,
-        "sort": {},
-        "searchPhrase": ""
-    });
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-
-    response
-        .json::<SnapshotSearchResponse>()
-        .await
-        .map_err(|e| format!("Failed to parse snapshots: {}", e))
+    Ok(search_grid::search(&client, "/api/core/snapshots/search", &query).await?)
 }
 
 #[tauri::command]
 pub async fn get_new_snapshot(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<NewSnapshotResponse, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/core/snapshots/get/");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<NewSnapshotResponse, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/core/snapshots/get/").await?;
 
     response
         .json::<NewSnapshotResponse>()
         .await
-        .map_err(|e| format!("Failed to get new snapshot info: {}", e))
+        .map_err(|e| format!("Failed to get new snapshot info: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn get_snapshot(
     uuid: String,
     fetch_mode: Option<String>,
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Snapshot, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+    clients: State<'_, ClientManager>,
+) -> Result<Snapshot, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
-    let mut url = build_api_url(&api_info, &format!("/api/core/snapshots/get/{}", uuid));
+    let mut endpoint = format!("/api/core/snapshots/get/{}", uuid);
 
     if let Some(mode) = fetch_mode {
-        url = format!("{}?fetchmode={}", url, mode);
+        endpoint = format!("{}?fetchmode={}", endpoint, mode);
     }
 
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.get(&endpoint).await?;
 
     response
         .json::<Snapshot>()
         .await
-        .map_err(|e| format!("Failed to get snapshot: {}", e))
+        .map_err(|e| format!("Failed to get snapshot: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn add_snapshot(
     name: String,
     uuid: Option<String>,
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/core/snapshots/add/");
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     let payload = match uuid {
         Some(id) => json!({
@@ -191,90 +120,114 @@ pub async fn add_snapshot(
 
     info!("Creating snapshot with payload: {:?}", payload);
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .post("/api/core/snapshots/add/", payload.clone())
+        .await?;
 
-    response
+    let result = response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to add snapshot: {}", e))
+        .map_err(|e| format!("Failed to add snapshot: {}", e))?;
+
+    // The firewall doesn't return the new snapshot, so journal what was asked for
+    if succeeded(&result) {
+        Change::new(Module::Snapshots, "add")
+            .after(Some(payload))
+            .record(&database, &client);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
-pub async fn delete_snapshot(uuid: String, database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, &format!("/api/core/snapshots/del/{}", uuid));
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn delete_snapshot(
+    uuid: String,
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
-    response
+    let endpoint = format!("/api/core/snapshots/del/{}", uuid);
+
+    let before = fetch_snapshot(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await?;
+
+    let result = response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to delete snapshot: {}", e))
+        .map_err(|e| format!("Failed to delete snapshot: {}", e))?;
+
+    if succeeded(&result) {
+        Change::new(Module::Snapshots, "delete")
+            .target(&uuid)
+            .before(before)
+            .record(&database, &client);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
 pub async fn activate_snapshot(
     uuid: String,
+    confirmation_token: String,
+    pin: String,
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, &format!("/api/core/snapshots/activate/{}", uuid));
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let pin = Zeroizing::new(pin);
+    confirmations.confirm_selected(
+        &database,
+        &confirmation_token,
+        &pin,
+        &DestructiveAction::ActivateSnapshot { uuid: uuid.clone() },
+        profile,
+    )?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/core/snapshots/activate/{}", uuid);
+
+    let before = fetch_snapshot(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await?;
 
-    response
+    let result = response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to activate snapshot: {}", e))
+        .map_err(|e| format!("Failed to activate snapshot: {}", e))?;
+
+    if succeeded(&result) {
+        Change::new(Module::Snapshots, "activate")
+            .target(&uuid)
+            .before(before)
+            .after(fetch_snapshot(&client, &uuid).await.ok())
+            .record(&database, &client);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
 pub async fn update_snapshot(
     uuid: String,
     name: String,
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
-    let url = build_api_url(&api_info, &format!("/api/core/snapshots/set/{}", uuid));
+    let endpoint = format!("/api/core/snapshots/set/{}", uuid);
 
     let payload = json!({
         "uuid": uuid,
@@ -283,19 +236,37 @@ pub async fn update_snapshot(
 
     info!("Updating snapshot with payload: {:?}", payload);
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let before = fetch_snapshot(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, payload).await?;
+
+    let result = response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to update snapshot: {}", e))?;
+
+    if succeeded(&result) {
+        Change::new(Module::Snapshots, "set")
+            .target(&uuid)
+            .before(before)
+            .after(fetch_snapshot(&client, &uuid).await.ok())
+            .record(&database, &client);
+    }
+
+    Ok(result)
+}
+
+fn succeeded(result: &Value) -> bool {
+    result["status"].as_str() == Some("ok")
+}
+
+async fn fetch_snapshot(client: &OpnsenseClient, uuid: &str) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/core/snapshots/get/{}", uuid);
+
+    let response = client.get(&endpoint).await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to update snapshot: {}", e))
+        .map_err(|e| format!("Failed to get snapshot: {}", e).into())
 }
@@ -0,0 +1,515 @@
+use async_trait::async_trait;
+use log::{error, info, warn};
+use rand::{thread_rng, Rng};
+use russh::client::{self, Handle};
+use russh_keys::key::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, TcpListener as StdTcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::task::JoinSet;
+use zeroize::Zeroizing;
+
+const CONNECT_TIMEOUT_SECS: u64 = 15;
+const KEEPALIVE_INTERVAL_SECS: u64 = 30;
+
+/// User name the HTTP client presents to the tunnel's SOCKS listener; the password is
+/// a secret generated for each tunnel
+const SOCKS_USERNAME: &str = "opnmanager";
+
+/// Saves the host key presented on first connect; an error aborts the connection
+pub type PinHostKey = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+fn default_remote_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+/// SSH server a profile's API traffic is forwarded through
+///
+/// Usually the firewall itself, with the web GUI reached on its loopback address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SshTunnelSettings {
+    pub host: String,
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<Zeroizing<String>>,
+    /// OpenSSH or PEM private key, preferred over the password when both are set
+    #[serde(default)]
+    pub private_key: Option<Zeroizing<String>>,
+    #[serde(default)]
+    pub passphrase: Option<Zeroizing<String>>,
+    /// Address the SSH server forwards to, as seen from the SSH server
+    #[serde(default = "default_remote_host")]
+    pub remote_host: String,
+    /// SHA-256 host key fingerprint, pinned on first connect
+    #[serde(default)]
+    pub host_key_fingerprint: Option<String>,
+}
+
+impl SshTunnelSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() {
+            return Err("SSH host is required".to_string());
+        }
+        if self.port == 0 {
+            return Err("SSH port is required".to_string());
+        }
+        if self.username.trim().is_empty() {
+            return Err("SSH username is required".to_string());
+        }
+        if self.password.is_none() && self.private_key.is_none() {
+            return Err("SSH tunnel needs a password or a private key".to_string());
+        }
+        if let Some(key) = &self.private_key {
+            russh_keys::decode_secret_key(key, self.passphrase())
+                .map_err(|e| format!("Invalid SSH private key: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn passphrase(&self) -> Option<&str> {
+        self.passphrase.as_deref().map(String::as_str)
+    }
+}
+
+/// Trust-on-first-use host key check, mirroring certificate pinning
+struct HostKeyCheck {
+    pinned: Option<String>,
+    observed: Arc<Mutex<Option<String>>>,
+}
+
+#[async_trait]
+impl client::Handler for HostKeyCheck {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        let presented = format!("SHA256:{}", server_public_key.fingerprint());
+        *self.observed.lock().unwrap() = Some(presented.clone());
+
+        Ok(match &self.pinned {
+            Some(pinned) => pinned == &presented,
+            None => true,
+        })
+    }
+}
+
+struct TunnelState {
+    /// Starts out as the configured fingerprint and is filled in on first connect
+    pinned_host_key: Mutex<Option<String>>,
+    pin_host_key: Mutex<Option<PinHostKey>>,
+    observed_host_key: Arc<Mutex<Option<String>>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl TunnelState {
+    /// Trusts `host_key` from now on, saving it first when someone asked to
+    fn pin(&self, host_key: &str) -> Result<(), String> {
+        if let Some(pin_host_key) = self.pin_host_key.lock().unwrap().as_ref() {
+            pin_host_key(host_key)?;
+        }
+        *self.pinned_host_key.lock().unwrap() = Some(host_key.to_string());
+        Ok(())
+    }
+}
+
+/// Local port-forward to the firewall API over SSH
+///
+/// The listener is bound immediately so the HTTP client knows where to connect, but the
+/// SSH session is only opened when the first request arrives and is re-established on
+/// the next request after it drops. Dropping the tunnel closes the session.
+///
+/// Any local process can reach the listener, so it speaks SOCKS5 and only forwards
+/// connections that log in with the tunnel's secret, which only [`Self::proxy`] hands out.
+pub struct SshTunnel {
+    local_addr: SocketAddr,
+    secret: Arc<Zeroizing<String>>,
+    settings: SshTunnelSettings,
+    state: Arc<TunnelState>,
+    task: JoinHandle<()>,
+}
+
+impl SshTunnel {
+    pub fn start(settings: &SshTunnelSettings, remote_port: u16) -> Result<Self, String> {
+        settings.validate()?;
+
+        let listener = StdTcpListener::bind("127.0.0.1:0")
+            .map_err(|e| format!("Failed to open local port for SSH tunnel: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to open local port for SSH tunnel: {}", e))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to open local port for SSH tunnel: {}", e))?;
+
+        let secret = Arc::new(Zeroizing::new(format!(
+            "{:032x}",
+            thread_rng().gen::<u128>()
+        )));
+        let state = Arc::new(TunnelState {
+            pinned_host_key: Mutex::new(settings.host_key_fingerprint.clone()),
+            pin_host_key: Mutex::new(None),
+            observed_host_key: Arc::new(Mutex::new(None)),
+            last_error: Mutex::new(None),
+        });
+
+        info!(
+            "Forwarding {} to {}:{} via SSH {}@{}:{}",
+            local_addr,
+            settings.remote_host,
+            remote_port,
+            settings.username,
+            settings.host,
+            settings.port
+        );
+
+        let task = tauri::async_runtime::spawn(serve(
+            listener,
+            secret.clone(),
+            settings.clone(),
+            remote_port,
+            state.clone(),
+        ));
+
+        Ok(Self {
+            local_addr,
+            secret,
+            settings: settings.clone(),
+            state,
+            task,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn local_port(&self) -> u16 {
+        self.local_addr.port()
+    }
+
+    /// Proxy that sends a client's connections through this tunnel
+    ///
+    /// Uses `socks5h` so the firewall's hostname is never resolved locally; the tunnel
+    /// forwards every connection to the configured remote host anyway.
+    pub fn proxy(&self) -> Result<reqwest::Proxy, String> {
+        reqwest::Proxy::all(format!("socks5h://{}", self.local_addr))
+            .map(|proxy| proxy.basic_auth(SOCKS_USERNAME, &self.secret))
+            .map_err(|e| format!("Failed to route requests through SSH tunnel: {}", e))
+    }
+
+    /// Has the host key of the first connect saved before any credentials are sent
+    pub fn pin_host_key_with(&self, pin_host_key: PinHostKey) {
+        *self.state.pin_host_key.lock().unwrap() = Some(pin_host_key);
+    }
+
+    /// Host key fingerprint the SSH server presented on the last connect
+    pub fn observed_host_key(&self) -> Option<String> {
+        self.state.observed_host_key.lock().unwrap().clone()
+    }
+
+    /// Why the tunnel couldn't carry the last request, if it failed
+    pub fn last_error(&self) -> Option<String> {
+        self.state.last_error.lock().unwrap().clone()
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("{}:{}", self.settings.host, self.settings.port)
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        info!("Closing SSH tunnel to {}", self.endpoint());
+        self.task.abort();
+    }
+}
+
+/// SSH session shared by every forwarded connection, reopened when it has dropped
+struct SharedSession {
+    settings: SshTunnelSettings,
+    state: Arc<TunnelState>,
+    handle: AsyncMutex<Option<Arc<Handle<HostKeyCheck>>>>,
+}
+
+impl SharedSession {
+    /// The open session, connecting first if there is none
+    ///
+    /// Connections that arrive during a handshake wait for it rather than starting their own.
+    async fn get(&self) -> Result<Arc<Handle<HostKeyCheck>>, String> {
+        let mut handle = self.handle.lock().await;
+        if let Some(open) = handle.as_ref().filter(|handle| !handle.is_closed()) {
+            return Ok(open.clone());
+        }
+        if handle.is_some() {
+            info!(
+                "SSH session to {}:{} dropped, reconnecting",
+                self.settings.host, self.settings.port
+            );
+        }
+
+        match connect(&self.settings, &self.state).await {
+            Ok(connected) => {
+                *self.state.last_error.lock().unwrap() = None;
+                let connected = Arc::new(connected);
+                *handle = Some(connected.clone());
+                Ok(connected)
+            }
+            Err(e) => {
+                error!("{}", e);
+                *self.state.last_error.lock().unwrap() = Some(e.clone());
+                *handle = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+async fn serve(
+    listener: StdTcpListener,
+    secret: Arc<Zeroizing<String>>,
+    settings: SshTunnelSettings,
+    remote_port: u16,
+    state: Arc<TunnelState>,
+) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to start SSH tunnel listener: {}", e);
+            return;
+        }
+    };
+
+    // Owned by this task, so aborting it also tears down every forwarded connection
+    let mut connections = JoinSet::new();
+    let session = Arc::new(SharedSession {
+        settings,
+        state,
+        handle: AsyncMutex::new(None),
+    });
+
+    loop {
+        let (local, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("SSH tunnel failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        while connections.try_join_next().is_some() {}
+
+        // Connecting happens in the connection's task so a slow handshake never holds up accepting
+        connections.spawn(forward(
+            local,
+            peer,
+            secret.clone(),
+            session.clone(),
+            remote_port,
+        ));
+    }
+}
+
+/// Reply codes of a SOCKS5 connect request
+const SOCKS_SUCCEEDED: u8 = 0x00;
+const SOCKS_GENERAL_FAILURE: u8 = 0x01;
+
+/// Compares without returning early, so the secret can't be guessed a byte at a time
+fn secret_matches(presented: &[u8], secret: &str) -> bool {
+    presented.len() == secret.len()
+        && presented
+            .iter()
+            .zip(secret.as_bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// SOCKS5 greeting, username/password login (RFC 1929) and connect request
+///
+/// The requested destination is read and ignored: the tunnel only ever forwards to
+/// the profile's firewall.
+async fn accept_socks(local: &mut TcpStream, secret: &str) -> std::io::Result<bool> {
+    let mut header = [0u8; 2];
+    local.read_exact(&mut header).await?;
+    if header[0] != 5 {
+        local.write_all(&[5, 0xff]).await?;
+        return Ok(false);
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    local.read_exact(&mut methods).await?;
+    if !methods.contains(&0x02) {
+        local.write_all(&[5, 0xff]).await?;
+        return Ok(false);
+    }
+    local.write_all(&[5, 0x02]).await?;
+
+    let mut version = [0u8; 2];
+    local.read_exact(&mut version).await?;
+    let mut username = vec![0u8; version[1] as usize];
+    local.read_exact(&mut username).await?;
+    let mut password = Zeroizing::new(vec![0u8; local.read_u8().await? as usize]);
+    local.read_exact(&mut password).await?;
+    if username != SOCKS_USERNAME.as_bytes() || !secret_matches(&password, secret) {
+        local.write_all(&[1, 0x01]).await?;
+        return Ok(false);
+    }
+    local.write_all(&[1, 0x00]).await?;
+
+    let mut request = [0u8; 4];
+    local.read_exact(&mut request).await?;
+    let address_len = match request[3] {
+        0x01 => 4,
+        0x03 => local.read_u8().await? as usize,
+        0x04 => 16,
+        _ => return Ok(false),
+    };
+    let mut destination = vec![0u8; address_len + 2];
+    local.read_exact(&mut destination).await?;
+    Ok(request[1] == 0x01)
+}
+
+async fn reply_socks(local: &mut TcpStream, reply: u8) -> std::io::Result<()> {
+    local.write_all(&[5, reply, 0, 1, 0, 0, 0, 0, 0, 0]).await
+}
+
+async fn forward(
+    mut local: TcpStream,
+    peer: SocketAddr,
+    secret: Arc<Zeroizing<String>>,
+    session: Arc<SharedSession>,
+    remote_port: u16,
+) {
+    match accept_socks(&mut local, &secret).await {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!(
+                "SSH tunnel refused a connection from {} without its secret",
+                peer
+            );
+            return;
+        }
+        Err(e) => {
+            warn!("SSH tunnel dropped a connection from {}: {}", peer, e);
+            return;
+        }
+    }
+
+    // Dropping the local socket fails the pending HTTP request
+    let Ok(handle) = session.get().await else {
+        let _ = reply_socks(&mut local, SOCKS_GENERAL_FAILURE).await;
+        return;
+    };
+
+    let remote_host = &session.settings.remote_host;
+    match handle
+        .channel_open_direct_tcpip(
+            remote_host.clone(),
+            remote_port as u32,
+            peer.ip().to_string(),
+            peer.port() as u32,
+        )
+        .await
+    {
+        Ok(channel) => {
+            if reply_socks(&mut local, SOCKS_SUCCEEDED).await.is_err() {
+                return;
+            }
+            let mut remote = channel.into_stream();
+            let _ = tokio::io::copy_bidirectional(&mut local, &mut remote).await;
+        }
+        Err(e) => {
+            warn!(
+                "SSH server refused forwarding to {}:{}: {}",
+                remote_host, remote_port, e
+            );
+            let _ = reply_socks(&mut local, SOCKS_GENERAL_FAILURE).await;
+        }
+    }
+}
+
+async fn connect(
+    settings: &SshTunnelSettings,
+    state: &TunnelState,
+) -> Result<Handle<HostKeyCheck>, String> {
+    let endpoint = format!("{}:{}", settings.host, settings.port);
+    let config = Arc::new(client::Config {
+        keepalive_interval: Some(Duration::from_secs(KEEPALIVE_INTERVAL_SECS)),
+        ..Default::default()
+    });
+    let pinned = state.pinned_host_key.lock().unwrap().clone();
+    let handler = HostKeyCheck {
+        pinned: pinned.clone(),
+        observed: state.observed_host_key.clone(),
+    };
+
+    let connected = tokio::time::timeout(
+        Duration::from_secs(CONNECT_TIMEOUT_SECS),
+        client::connect(config, (settings.host.as_str(), settings.port), handler),
+    )
+    .await
+    .map_err(|_| format!("SSH connection to {} timed out", endpoint))?;
+
+    let mut handle = match connected {
+        Ok(handle) => handle,
+        Err(russh::Error::UnknownKey) => {
+            let presented = state.observed_host_key.lock().unwrap().clone();
+            return Err(format!(
+                "The SSH host key of {} has changed (pinned {}, presented {}). Update the tunnel settings if the firewall was reinstalled.",
+                endpoint,
+                pinned.as_deref().unwrap_or("none"),
+                presented.as_deref().unwrap_or("unknown")
+            ));
+        }
+        Err(e) => return Err(format!("SSH connection to {} failed: {}", endpoint, e)),
+    };
+
+    // Trust on first use, settled before the server sees any credentials
+    if pinned.is_none() {
+        let presented = state
+            .observed_host_key
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| format!("SSH server {} presented no host key", endpoint))?;
+        info!("Pinning SSH host key {} of {}", presented, endpoint);
+        state
+            .pin(&presented)
+            .map_err(|e| format!("Failed to save SSH host key of {}: {}", endpoint, e))?;
+    }
+
+    let authenticated = match (&settings.private_key, &settings.password) {
+        (Some(key), _) => {
+            let key = russh_keys::decode_secret_key(key, settings.passphrase())
+                .map_err(|e| format!("Invalid SSH private key: {}", e))?;
+            handle
+                .authenticate_publickey(&settings.username, Arc::new(key))
+                .await
+        }
+        (None, Some(password)) => {
+            handle
+                .authenticate_password(&settings.username, password.as_str())
+                .await
+        }
+        (None, None) => return Err("SSH tunnel needs a password or a private key".to_string()),
+    }
+    .map_err(|e| format!("SSH authentication with {} failed: {}", endpoint, e))?;
+
+    if !authenticated {
+        return Err(format!(
+            "SSH authentication failed for {}@{}",
+            settings.username, endpoint
+        ));
+    }
+
+    info!("SSH tunnel to {} established", endpoint);
+    Ok(handle)
+}
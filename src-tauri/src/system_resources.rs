@@ -1,5 +1,7 @@
+use crate::active_profile::SelectedProfile;
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
@@ -16,6 +18,12 @@ pub struct SystemResources {
     memory: Memory,
 }
 
+impl SystemResources {
+    pub fn into_memory(self) -> Memory {
+        self.memory
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DiskDevice {
     device: String,
@@ -50,88 +58,54 @@ pub struct SystemTemperature {
 
 #[tauri::command]
 pub async fn get_system_resources(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<SystemResources, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/system/systemResources",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<SystemResources, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    fetch_system_resources(&client).await
+}
+
+pub async fn fetch_system_resources(
+    client: &OpnsenseClient,
+) -> Result<SystemResources, CommandError> {
+    let response = client
+        .get("/api/diagnostics/system/systemResources")
+        .await?;
 
     response
         .json::<SystemResources>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
-pub async fn get_system_disk(database: State<'_, Database>) -> Result<SystemDisk, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/system/systemDisk",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_system_disk(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<SystemDisk, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/diagnostics/system/systemDisk").await?;
 
     response
         .json::<SystemDisk>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command(rename_all = "snake_case")]
 pub async fn get_system_temperature(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<SystemTemperature, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/system/systemTemperature",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<SystemTemperature, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .get("/api/diagnostics/system/systemTemperature")
+        .await?;
 
     // Handle empty array response for systems without temperature sensors
     let response_text = response
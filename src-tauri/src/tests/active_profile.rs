@@ -0,0 +1,122 @@
+use super::{run, to_json, MockOpnsense, TestApp, TEST_PIN};
+use crate::active_profile::{ActiveProfiles, SelectedProfile};
+use crate::commands;
+use crate::confirmation::DestructiveAction;
+use crate::dashboard;
+
+fn respond_with_time(server: &MockOpnsense, datetime: &str) {
+    server.respond(
+        "GET",
+        "/api/diagnostics/system/systemTime",
+        200,
+        &format!(
+            r#"{{"uptime":"1 day","datetime":"{}","config":"","loadavg":"0.10"}}"#,
+            datetime
+        ),
+    );
+}
+
+#[test]
+fn selected_profile_talks_to_its_own_firewall() {
+    let app = TestApp::new();
+    let branch = MockOpnsense::start();
+    let branch_id = app.add_profile("branch", branch.port());
+    respond_with_time(&app.server, "Sat Oct 17 10:00:00 UTC 2026");
+    respond_with_time(&branch, "Sat Oct 17 11:00:00 UTC 2026");
+
+    let default_time = run(dashboard::get_system_time(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    let branch_time = run(dashboard::get_system_time(
+        SelectedProfile::new(Some(branch_id)),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+        to_json(default_time)["datetime"],
+        "Sat Oct 17 10:00:00 UTC 2026"
+    );
+    assert_eq!(
+        to_json(branch_time)["datetime"],
+        "Sat Oct 17 11:00:00 UTC 2026"
+    );
+    assert_eq!(
+        app.server
+            .requests_to("/api/diagnostics/system/systemTime")
+            .len(),
+        1
+    );
+    assert_eq!(
+        branch
+            .requests_to("/api/diagnostics/system/systemTime")
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn unknown_profile_id_is_an_error() {
+    let app = TestApp::new();
+
+    let result = run(dashboard::get_system_time(
+        SelectedProfile::new(Some(4242)),
+        app.database(),
+        app.clients(),
+    ));
+
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "API profile 4242 not found"
+    );
+    assert!(app
+        .server
+        .requests_to("/api/diagnostics/system/systemTime")
+        .is_empty());
+}
+
+#[test]
+fn windows_keep_separate_profiles() {
+    let active = ActiveProfiles::new();
+
+    active.set("main", Some(2));
+    active.set("branch-office", Some(3));
+    assert_eq!(active.get("main"), Some(2));
+    assert_eq!(active.get("branch-office"), Some(3));
+    assert_eq!(active.get("settings"), None);
+
+    active.set("main", None);
+    assert_eq!(active.get("main"), None);
+    assert_eq!(active.get("branch-office"), Some(3));
+}
+
+#[test]
+fn deleting_a_profile_resets_windows_using_it() {
+    let app = TestApp::new();
+    let branch = MockOpnsense::start();
+    let branch_id = app.add_profile("branch", branch.port());
+    app.active_profiles().set("main", Some(branch_id));
+    let token = app.confirmation_token(
+        DestructiveAction::DeleteApiProfile {
+            profile_name: "branch".to_string(),
+        },
+        app.profile(),
+    );
+
+    commands::delete_api_profile(
+        "branch".to_string(),
+        token,
+        TEST_PIN.to_string(),
+        app.database(),
+        app.clients(),
+        app.active_profiles(),
+        app.confirmations(),
+    )
+    .unwrap();
+
+    assert_eq!(app.active_profiles().get("main"), None);
+}
@@ -0,0 +1,95 @@
+use super::{run, TestApp};
+use crate::alias;
+
+#[test]
+fn network_aliases_are_listed() {
+    let app = TestApp::new();
+    app.server.fixture(
+        "GET",
+        "/api/firewall/alias/listNetworkAliases",
+        "network_aliases",
+    );
+
+    let aliases = run(alias::list_network_aliases(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(aliases["__lan_network"], "LAN net");
+}
+
+#[test]
+fn saved_alias_is_applied() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "POST",
+            "/api/firewall/alias/addItem/",
+            200,
+            r#"{"result":"saved","uuid":"f00d"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/set",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    let result = run(alias::add_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "blocklist".to_string(),
+        "host".to_string(),
+        "192.0.2.1, 192.0.2.2".to_string(),
+        "Blocked hosts".to_string(),
+        true,
+    ))
+    .unwrap();
+
+    assert_eq!(result["uuid"], "f00d");
+
+    let added = app.server.requests_to("/api/firewall/alias/addItem/");
+    assert_eq!(added[0].json()["alias"]["content"], "192.0.2.1\n192.0.2.2");
+    assert_eq!(app.server.requests_to("/api/firewall/alias/set").len(), 1);
+    assert_eq!(
+        app.server
+            .requests_to("/api/firewall/alias/reconfigure")
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn rejected_alias_is_not_applied() {
+    let app = TestApp::new();
+    app.server.respond(
+        "POST",
+        "/api/firewall/alias/addItem/",
+        200,
+        r#"{"result":"failed","validations":{"alias.name":"An alias with this name already exists."}}"#,
+    );
+
+    let result = run(alias::add_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "blocklist".to_string(),
+        "host".to_string(),
+        "192.0.2.1".to_string(),
+        String::new(),
+        true,
+    ))
+    .unwrap();
+
+    assert_eq!(result["result"], "failed");
+    assert!(app.server.requests_to("/api/firewall/alias/set").is_empty());
+}
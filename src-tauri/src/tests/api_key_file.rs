@@ -0,0 +1,93 @@
+use super::{run, TestApp};
+use crate::api_key_file::ApiKeyFile;
+use crate::commands::{self, NewApiProfile};
+use serde_json::json;
+
+const KEY: &str =
+    "w86XNZob/8Oq8aC5r0kbNarNtdpoQU781fyoeaOBQsBwkXUt+Xw4DbvzJbBl0Cg9RA7Q6jjdyfBGfFOw";
+const SECRET: &str =
+    "XeD26XVrJ5ilAc/EmglCRC+0j2e57tRsjHwFepOseySWLM53pJASeTA3DdsM7arYBVgw/Xm+r3Ij6wG8";
+
+fn new_profile(app: &TestApp, key_file: &str) -> NewApiProfile {
+    serde_json::from_value(json!({
+        "profile_name": "branch",
+        "api_key_file": key_file,
+        "api_url": app.server.api_url(),
+        "port": app.server.port(),
+    }))
+    .unwrap()
+}
+
+#[test]
+fn downloaded_key_file_is_parsed() {
+    let file = ApiKeyFile::parse(&format!("key={}\r\nsecret={}\r\n", KEY, SECRET)).unwrap();
+
+    assert_eq!(file.key.as_str(), KEY);
+    assert_eq!(file.secret.as_str(), SECRET);
+}
+
+#[test]
+fn pasted_key_file_on_one_line_is_parsed() {
+    let file = ApiKeyFile::parse(&format!("  secret=\"{}\" key={} ", SECRET, KEY)).unwrap();
+
+    assert_eq!(file.key.as_str(), KEY);
+    assert_eq!(file.secret.as_str(), SECRET);
+}
+
+#[test]
+fn incomplete_or_mangled_key_files_are_rejected() {
+    assert!(ApiKeyFile::parse(&format!("key={}", KEY)).is_err());
+    assert!(ApiKeyFile::parse(&format!("key={}\nsecret=", KEY)).is_err());
+    assert!(ApiKeyFile::parse(&format!("key={}\nsecret={}…", KEY, SECRET)).is_err());
+    assert!(ApiKeyFile::parse(&format!("key={0}\nkey={0}\nsecret={1}", KEY, SECRET)).is_err());
+}
+
+#[test]
+fn key_file_profile_is_saved_after_a_successful_connection() {
+    let app = TestApp::new();
+    app.server.respond(
+        "GET",
+        "/api/diagnostics/system/systemTime",
+        200,
+        r#"{"datetime":"Sat Oct 17 10:00:00 UTC 2026"}"#,
+    );
+
+    let key_file = format!("key={}\nsecret={}\n", KEY, SECRET);
+    run(commands::add_api_profile(
+        new_profile(&app, &key_file),
+        app.database(),
+    ))
+    .unwrap();
+
+    let saved = app
+        .database()
+        .get_api_info(Some("branch"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.api_key.as_str(), KEY);
+    assert_eq!(saved.api_secret.as_str(), SECRET);
+}
+
+#[test]
+fn key_file_for_another_firewall_is_not_saved() {
+    let app = TestApp::new();
+    app.server.respond(
+        "GET",
+        "/api/diagnostics/system/systemTime",
+        401,
+        r#"{"status":401,"message":"Authentication Failed"}"#,
+    );
+
+    let key_file = format!("key={}\nsecret={}\n", KEY, SECRET);
+    let result = run(commands::add_api_profile(
+        new_profile(&app, &key_file),
+        app.database(),
+    ));
+
+    assert!(result.is_err());
+    assert!(app
+        .database()
+        .get_api_info(Some("branch"))
+        .unwrap()
+        .is_none());
+}
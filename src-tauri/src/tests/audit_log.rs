@@ -0,0 +1,105 @@
+use super::{run, TestApp, TEST_API_KEY, TEST_API_SECRET};
+use crate::audit_log::{self, AuditEntry};
+use crate::dashboard;
+use serde_json::json;
+
+#[test]
+fn api_calls_are_logged_without_secrets() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status");
+    app.server.respond(
+        "POST",
+        "/api/core/service/restart/unbound",
+        200,
+        r#"{"result":"ok"}"#,
+    );
+
+    run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    run(client.post(
+        "/api/core/service/restart/unbound",
+        json!({ "password": "hunter2" }),
+    ))
+    .unwrap();
+
+    let entries = audit_log::get_api_audit_log(None, None, app.database()).unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // Newest first
+    let restart = &entries[0];
+    assert_eq!(restart.method, "POST");
+    assert_eq!(restart.endpoint, "/api/core/service/restart/unbound");
+    assert_eq!(restart.profile_name, "mock");
+    assert_eq!(restart.status, Some(200));
+    assert!(restart.request_bytes > 0);
+    assert_eq!(entries[1].endpoint, "/api/routes/gateway/status");
+
+    let stored = serde_json::to_string(&entries).unwrap();
+    for secret in [TEST_API_KEY, TEST_API_SECRET, "hunter2"] {
+        assert!(!stored.contains(secret), "audit log leaked {}", secret);
+    }
+}
+
+#[test]
+fn query_strings_are_dropped_and_failures_recorded() {
+    let app = TestApp::new();
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let _ = run(client.get("/api/diagnostics/firewall/log/?digest=abc&limit=500"));
+
+    let entries = audit_log::get_api_audit_log(None, None, app.database()).unwrap();
+    assert_eq!(entries[0].endpoint, "/api/diagnostics/firewall/log/");
+    assert_eq!(entries[0].status, Some(404));
+}
+
+#[test]
+fn connection_failures_record_the_error_kind() {
+    let app = TestApp::new();
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    // Nothing listens on the discard port
+    api_info.port = 9;
+    app.database().save_api_info(&api_info).unwrap();
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let _ = run(client.post("/api/core/service/restart/unbound", json!({})));
+
+    let entries = audit_log::get_api_audit_log(None, None, app.database()).unwrap();
+    assert_eq!(entries[0].status, None);
+    assert_eq!(entries[0].error.as_deref(), Some("connection"));
+}
+
+#[test]
+fn audit_log_is_bounded() {
+    let app = TestApp::new();
+    let log = app.database().audit_log();
+
+    for i in 0..2010 {
+        log.record(&AuditEntry {
+            id: 0,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            profile_id: 1,
+            profile_name: "mock".to_string(),
+            method: "GET".to_string(),
+            endpoint: format!("/api/test/{}", i),
+            status: Some(200),
+            error: None,
+            latency_ms: 1,
+            request_bytes: 0,
+            response_bytes: Some(2),
+        });
+    }
+
+    let entries = log.recent(None, 5000).unwrap();
+    assert_eq!(entries.len(), 2000);
+    assert_eq!(entries[0].endpoint, "/api/test/2009");
+    assert_eq!(entries[1999].endpoint, "/api/test/10");
+
+    audit_log::clear_api_audit_log(app.database()).unwrap();
+    assert!(log.recent(None, 10).unwrap().is_empty());
+}
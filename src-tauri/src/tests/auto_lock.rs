@@ -0,0 +1,45 @@
+use super::TestApp;
+use crate::auto_lock::{self, IdleLock};
+use std::time::{Duration, Instant};
+
+#[test]
+fn idle_timeout_counts_from_last_activity() {
+    let idle_lock = IdleLock::new(1);
+    let start = Instant::now();
+
+    assert!(!idle_lock.is_idle(start));
+    assert!(idle_lock.is_idle(start + Duration::from_secs(61)));
+
+    idle_lock.touch();
+    assert!(!idle_lock.is_idle(Instant::now() + Duration::from_secs(30)));
+
+    idle_lock.set_minutes(0);
+    assert!(!idle_lock.is_idle(Instant::now() + Duration::from_secs(24 * 60 * 60)));
+}
+
+#[test]
+fn locking_forgets_the_encryption_key() {
+    let app = TestApp::new();
+    app.unlock();
+    assert!(app.database().is_unlocked());
+
+    auto_lock::lock(&app.database(), &app.clients());
+
+    assert!(!app.database().is_unlocked());
+}
+
+#[test]
+fn idle_lock_setting_needs_an_unlocked_app() {
+    let app = TestApp::new();
+    app.database().lock();
+
+    assert!(auto_lock::set_idle_lock_minutes(15, app.database(), app.idle_lock()).is_err());
+
+    app.unlock();
+    auto_lock::set_idle_lock_minutes(15, app.database(), app.idle_lock()).unwrap();
+    assert_eq!(
+        auto_lock::get_idle_lock_minutes(app.database()).unwrap(),
+        15
+    );
+    assert!(auto_lock::set_idle_lock_minutes(100_000, app.database(), app.idle_lock()).is_err());
+}
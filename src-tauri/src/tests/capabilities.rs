@@ -0,0 +1,127 @@
+use super::{run, TestApp};
+use crate::{capabilities, snapshots, tunables, wol};
+
+#[test]
+fn capabilities_come_from_firmware_info() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/core/firmware/info", "firmware_info_plugins");
+
+    let found = run(capabilities::get_capabilities(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(found.firmware_version.as_deref(), Some("24.1.10_8"));
+    assert_eq!(found.plugins, Some(vec!["os-wol".to_string()]));
+    assert!(found.wol);
+    assert!(!found.tunables);
+    assert!(!found.snapshots);
+
+    // Answered by the version and plugin list without probing
+    assert!(app.server.requests_to("/api/wol/wol/getwake").is_empty());
+    assert!(app
+        .server
+        .requests_to("/api/core/snapshots/is_supported/")
+        .is_empty());
+}
+
+#[test]
+fn capabilities_are_discovered_once_per_profile() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/core/firmware/info", "firmware_info");
+
+    run(capabilities::get_capabilities(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    run(snapshots::is_snapshots_supported(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    assert_eq!(app.server.requests_to("/api/core/firmware/info").len(), 1);
+
+    run(capabilities::refresh_capabilities(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    assert_eq!(app.server.requests_to("/api/core/firmware/info").len(), 2);
+}
+
+#[test]
+fn firewall_errors_are_not_cached() {
+    let app = TestApp::new();
+    app.server.respond(
+        "GET",
+        "/api/core/firmware/info",
+        500,
+        "Internal Server Error",
+    );
+
+    assert!(run(capabilities::get_capabilities(
+        app.profile(),
+        app.database(),
+        app.clients()
+    ))
+    .is_err());
+
+    app.server
+        .fixture("GET", "/api/core/firmware/info", "firmware_info");
+    let found = run(capabilities::get_capabilities(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    assert_eq!(found.firmware_version.as_deref(), Some("25.1.2"));
+}
+
+#[test]
+fn unsupported_tunables_are_not_requested() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/core/firmware/info", "firmware_info_plugins");
+
+    let error = run(tunables::search_tunables(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        1,
+        50,
+        String::new(),
+    ))
+    .unwrap_err()
+    .to_string();
+
+    assert!(error.contains("this firewall runs 24.1.10_8"), "{}", error);
+    assert!(app
+        .server
+        .requests_to("/api/core/tunables/search_item/")
+        .is_empty());
+}
+
+#[test]
+fn missing_wol_plugin_skips_the_probe() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/core/firmware/info", "firmware_info");
+
+    let status = run(wol::check_wol_plugin_installed(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(status["installed"], false);
+    assert!(app.server.requests_to("/api/wol/wol/getwake").is_empty());
+}
@@ -0,0 +1,174 @@
+use super::{run, to_json, TestApp, TEST_API_SECRET};
+use crate::cassette::{redact_json, resolve_path, CassetteMode};
+use crate::{devices, firewall};
+use serde_json::json;
+use std::path::PathBuf;
+
+fn cassette_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "opnmanager-cassette-{}-{}.json",
+        std::process::id(),
+        name
+    ))
+}
+
+#[test]
+fn recorded_traffic_replays_without_the_firewall() {
+    let app = TestApp::new();
+    let path = cassette_path("replay");
+    app.server
+        .fixture("GET", "/api/diagnostics/interface/getArp", "arp_table");
+
+    app.clients().cassette().start_recording(&path).unwrap();
+    let live = to_json(
+        run(devices::get_devices(
+            app.profile(),
+            app.database(),
+            app.clients(),
+        ))
+        .unwrap(),
+    );
+    app.clients().cassette().stop();
+
+    app.server
+        .respond("GET", "/api/diagnostics/interface/getArp", 500, "{}");
+    app.clients().cassette().start_replay(&path).unwrap();
+    let replayed = to_json(
+        run(devices::get_devices(
+            app.profile(),
+            app.database(),
+            app.clients(),
+        ))
+        .unwrap(),
+    );
+
+    assert_eq!(live, replayed);
+    assert_eq!(app.clients().cassette().mode(), CassetteMode::Replay);
+    assert_eq!(
+        app.server
+            .requests_to("/api/diagnostics/interface/getArp")
+            .len(),
+        1
+    );
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn recorded_errors_replay_as_errors() {
+    let app = TestApp::new();
+    let path = cassette_path("errors");
+
+    app.clients().cassette().start_recording(&path).unwrap();
+    let live = run(firewall::check_api_version(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    app.clients().cassette().start_replay(&path).unwrap();
+    let replayed = run(firewall::check_api_version(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert!(!live);
+    assert!(!replayed);
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn unrecorded_request_fails_in_replay() {
+    let app = TestApp::new();
+    let path = cassette_path("missing");
+
+    app.clients().cassette().start_recording(&path).unwrap();
+    app.clients().cassette().start_replay(&path).unwrap();
+    let error = run(devices::get_devices(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap_err()
+    .to_string();
+
+    assert!(
+        error.contains("No recorded response"),
+        "unexpected error: {}",
+        error
+    );
+    assert!(app.server.requests().is_empty());
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn cassette_file_contains_no_credentials() {
+    let app = TestApp::new();
+    let path = cassette_path("redaction");
+    app.server.respond(
+        "GET",
+        "/api/core/system/status",
+        200,
+        &format!(
+            r#"{{"ok":true,"echo":"{}","wireguard":{{"privkey":"abc"}}}}"#,
+            TEST_API_SECRET
+        ),
+    );
+
+    app.clients().cassette().start_recording(&path).unwrap();
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    run(client.get("/api/core/system/status")).unwrap();
+    app.clients().cassette().stop();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains(TEST_API_SECRET));
+    assert!(!contents.contains("Basic "));
+    assert!(!contents.contains("abc"));
+    assert!(contents.contains("[REDACTED]"));
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn secret_keys_are_redacted_recursively() {
+    let payload = json!({
+        "user": {"name": "admin", "password": "hunter2"},
+        "tunnels": [{"psk": "shared", "descr": "token is abc123"}],
+        "enabled": "1"
+    });
+
+    let redacted = redact_json(&payload, &["abc123"]);
+
+    assert_eq!(redacted["user"]["name"], "admin");
+    assert_eq!(redacted["user"]["password"], "[REDACTED]");
+    assert_eq!(redacted["tunnels"][0]["psk"], "[REDACTED]");
+    assert_eq!(redacted["tunnels"][0]["descr"], "token is [REDACTED]");
+    assert_eq!(redacted["enabled"], "1");
+}
+
+#[test]
+fn cassette_paths_stay_in_the_cassette_folder() {
+    let dir = cassette_path("folder");
+
+    assert_eq!(
+        resolve_path(&dir, "lab/boot.json").unwrap(),
+        dir.join("lab").join("boot.json")
+    );
+    assert!(dir.join("lab").is_dir());
+
+    for path in [
+        "",
+        "../outside.json",
+        "lab/../../outside.json",
+        "/tmp/outside.json",
+    ] {
+        let error = resolve_path(&dir, path).unwrap_err();
+        assert!(error.contains("Invalid cassette path"), "{}", error);
+    }
+
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,192 @@
+use super::{run, TestApp};
+use crate::change_journal::{self, ChangeEntry, ChangeFilter, ExportFormat, Module};
+use crate::{alias, firewall};
+use serde_json::{json, Value};
+
+fn entry(module: Module, user_name: &str, timestamp: &str) -> ChangeEntry {
+    ChangeEntry {
+        id: 0,
+        timestamp: timestamp.to_string(),
+        user_name: user_name.to_string(),
+        profile_id: 1,
+        profile_name: "mock".to_string(),
+        module,
+        action: "set".to_string(),
+        target_uuid: Some("abc".to_string()),
+        before: Some(json!({ "descr": "old" })),
+        after: Some(json!({ "descr": "new, \"quoted\"" })),
+        undone_at: None,
+    }
+}
+
+#[test]
+fn toggled_alias_is_journaled_with_its_state() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "GET",
+            "/api/firewall/alias/getItem/abc",
+            200,
+            r#"{"alias":{"name":"blocklist","enabled":"1"}}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/toggleItem/abc",
+            200,
+            r#"{"result":"Disabled","changed":true}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/set",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    run(alias::toggle_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "abc".to_string(),
+    ))
+    .unwrap();
+
+    let entries = change_journal::get_change_journal(None, app.database()).unwrap();
+    assert_eq!(entries.len(), 1);
+    let toggle = &entries[0];
+    assert_eq!(toggle.module, Module::Alias);
+    assert_eq!(toggle.action, "toggle");
+    assert_eq!(toggle.target_uuid.as_deref(), Some("abc"));
+    assert_eq!(toggle.user_name, "owner");
+    assert_eq!(toggle.profile_name, "mock");
+    assert_eq!(
+        toggle.before.as_ref().unwrap()["alias"]["name"],
+        "blocklist"
+    );
+    assert!(toggle.after.is_some());
+
+    // Fetched once before and once after the toggle
+    assert_eq!(
+        app.server
+            .requests_to("/api/firewall/alias/getItem/abc")
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn rejected_changes_are_not_journaled() {
+    let app = TestApp::new();
+    app.server.respond(
+        "POST",
+        "/api/firewall/filter/set_rule/abc",
+        200,
+        r#"{"result":"failed","validations":{"rule.interface":"Select an interface"}}"#,
+    );
+
+    run(firewall::set_rule(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "abc".to_string(),
+        json!({ "interface": "" }),
+    ))
+    .unwrap();
+
+    assert!(change_journal::get_change_journal(None, app.database())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn journal_is_filtered() {
+    let app = TestApp::new();
+    let journal = app.database().change_journal();
+    journal.record(&entry(Module::Alias, "owner", "2026-01-01T10:00:00+00:00"));
+    journal.record(&entry(Module::Routes, "noc", "2026-01-02T10:00:00+00:00"));
+    journal.record(&entry(Module::Alias, "noc", "2026-01-03T10:00:00+00:00"));
+
+    let search = |filter: ChangeFilter| {
+        change_journal::get_change_journal(Some(filter), app.database()).unwrap()
+    };
+
+    let aliases = search(ChangeFilter {
+        module: Some(Module::Alias),
+        ..Default::default()
+    });
+    assert_eq!(aliases.len(), 2);
+    // Newest first
+    assert_eq!(aliases[0].user_name, "noc");
+
+    let by_noc = search(ChangeFilter {
+        user_name: Some("noc".to_string()),
+        limit: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(by_noc.len(), 1);
+    assert_eq!(by_noc[0].module, Module::Alias);
+
+    // Other offsets are compared in UTC
+    let second_day = search(ChangeFilter {
+        since: Some("2026-01-02T11:00:00+01:00".to_string()),
+        until: Some("2026-01-03T00:00:00Z".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(second_day.len(), 1);
+    assert_eq!(second_day[0].module, Module::Routes);
+
+    let error = change_journal::get_change_journal(
+        Some(ChangeFilter {
+            since: Some("yesterday".to_string()),
+            ..Default::default()
+        }),
+        app.database(),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("Invalid timestamp"), "{}", error);
+}
+
+#[test]
+fn journal_exports_as_csv_and_json() {
+    let app = TestApp::new();
+    let journal = app.database().change_journal();
+    journal.record(&entry(
+        Module::Tunables,
+        "owner",
+        "2026-01-01T10:00:00+00:00",
+    ));
+    journal.record(&entry(Module::Wol, "owner", "2026-01-02T10:00:00+00:00"));
+
+    let csv =
+        change_journal::export_change_journal(None, ExportFormat::Csv, app.database()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,timestamp,user,"));
+    // Oldest first, with JSON quoted so its commas and quotes survive
+    assert!(lines[1].contains(",tunables,set,abc,"), "{}", lines[1]);
+    assert!(
+        lines[1].ends_with(r#","{""descr"":""new, \""quoted\""""}""#),
+        "{}",
+        lines[1]
+    );
+
+    let exported = change_journal::export_change_journal(
+        Some(ChangeFilter {
+            module: Some(Module::Wol),
+            ..Default::default()
+        }),
+        ExportFormat::Json,
+        app.database(),
+    )
+    .unwrap();
+    let exported: Value = serde_json::from_str(&exported).unwrap();
+    assert_eq!(exported.as_array().unwrap().len(), 1);
+    assert_eq!(exported[0]["module"], "wol");
+    assert_eq!(exported[0]["after"]["descr"], "new, \"quoted\"");
+}
@@ -0,0 +1,158 @@
+use super::{run, MockOpnsense, TestApp, TEST_PIN};
+use crate::active_profile::SelectedProfile;
+use crate::confirmation::{self, Confirmations, DestructiveAction};
+use crate::local_users::{self, Role};
+use crate::{devices, power, snapshots};
+use std::time::Duration;
+
+fn reboot(app: &TestApp, token: String, pin: &str) -> Result<(), String> {
+    run(power::reboot_firewall(
+        token,
+        pin.to_string(),
+        app.profile(),
+        app.database(),
+        app.clients(),
+        app.confirmations(),
+    ))
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+#[test]
+fn reboot_needs_a_fresh_token_and_the_pin() {
+    let app = TestApp::new();
+    app.unlock();
+    app.server
+        .respond("POST", "/api/core/system/reboot", 200, r#"{"status":"ok"}"#);
+
+    let pending = confirmation::request_confirmation(
+        DestructiveAction::RebootFirewall,
+        app.profile(),
+        app.database(),
+        app.confirmations(),
+    )
+    .unwrap();
+    assert_eq!(pending.profile_name, "mock");
+    assert!(pending.summary.contains("'mock'"), "{}", pending.summary);
+
+    let error = reboot(&app, pending.token.clone(), "0000").unwrap_err();
+    assert_eq!(error, "Incorrect PIN");
+    // Each token is good for one attempt
+    let error = reboot(&app, pending.token, TEST_PIN).unwrap_err();
+    assert!(error.contains("already used"), "{}", error);
+    assert!(reboot(&app, "made-up".to_string(), TEST_PIN).is_err());
+    assert!(app.server.requests_to("/api/core/system/reboot").is_empty());
+
+    let token = app.confirmation_token(DestructiveAction::RebootFirewall, app.profile());
+    reboot(&app, token, TEST_PIN).unwrap();
+    assert_eq!(app.server.requests_to("/api/core/system/reboot").len(), 1);
+}
+
+#[test]
+fn token_only_runs_what_it_describes() {
+    let app = TestApp::new();
+    let branch = MockOpnsense::start();
+    let branch_id = app.add_profile("branch", branch.port());
+    let branch_profile = SelectedProfile::new(Some(branch_id));
+
+    let activate = |uuid: &str, token: String, profile: SelectedProfile| {
+        run(snapshots::activate_snapshot(
+            uuid.to_string(),
+            token,
+            TEST_PIN.to_string(),
+            profile,
+            app.database(),
+            app.clients(),
+            app.confirmations(),
+        ))
+    };
+    let snapshot = |uuid: &str| DestructiveAction::ActivateSnapshot {
+        uuid: uuid.to_string(),
+    };
+
+    let token = app.confirmation_token(snapshot("before-upgrade"), branch_profile);
+    let error = activate("something-else", token, branch_profile)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("different operation"), "{}", error);
+
+    let token = app.confirmation_token(snapshot("before-upgrade"), branch_profile);
+    assert!(activate("before-upgrade", token, app.profile()).is_err());
+
+    let token = app.confirmation_token(DestructiveAction::RebootFirewall, branch_profile);
+    assert!(activate("before-upgrade", token, branch_profile).is_err());
+
+    assert!(branch
+        .requests_to("/api/core/snapshots/activate/before-upgrade")
+        .is_empty());
+    assert!(app
+        .server
+        .requests_to("/api/core/snapshots/activate/before-upgrade")
+        .is_empty());
+}
+
+#[test]
+fn expired_token_is_refused() {
+    let app = TestApp::new();
+    app.unlock();
+    let confirmations = Confirmations::new().with_ttl(Duration::ZERO);
+    let pending = confirmations.issue(
+        DestructiveAction::RebootFirewall,
+        1,
+        "mock".to_string(),
+        "owner".to_string(),
+    );
+
+    let error = confirmations
+        .confirm(
+            &app.database(),
+            &pending.token,
+            TEST_PIN,
+            &DestructiveAction::RebootFirewall,
+            1,
+        )
+        .unwrap_err();
+    assert!(error.contains("expired"), "{}", error);
+}
+
+#[test]
+fn only_the_requesting_user_can_confirm() {
+    let app = TestApp::new();
+    app.unlock();
+    local_users::add_local_user(
+        "noc".to_string(),
+        "7777".to_string(),
+        Role::Operator,
+        app.database(),
+    )
+    .unwrap();
+    app.database().lock();
+    assert!(app.database().verify_pin("7777").unwrap());
+
+    // Operators may flush ARP entries but not ask to reboot
+    assert!(confirmation::request_confirmation(
+        DestructiveAction::RebootFirewall,
+        app.profile(),
+        app.database(),
+        app.confirmations(),
+    )
+    .is_err());
+
+    let token = app.confirmation_token(DestructiveAction::FlushArpTable, app.profile());
+    let error = run(devices::flush_arp_table(
+        token,
+        TEST_PIN.to_string(),
+        app.profile(),
+        app.database(),
+        app.clients(),
+        app.confirmations(),
+    ))
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("user who started"), "{}", error);
+    assert!(app
+        .server
+        .requests_to("/api/diagnostics/interface/flushArp")
+        .is_empty());
+    assert_eq!(app.database().signed_in_user().unwrap().name, "noc");
+}
@@ -0,0 +1,85 @@
+use super::{run, to_json, TestApp};
+use crate::dashboard;
+
+#[test]
+fn gateway_status_is_parsed() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status");
+
+    let status = run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    let status = to_json(status);
+
+    assert_eq!(status["status"], "ok");
+    assert_eq!(status["items"][0]["name"], "WAN_DHCP");
+    assert_eq!(status["items"][0]["status_translated"], "Online");
+}
+
+#[test]
+fn services_are_listed_with_basic_auth() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/core/service/search", "services_search");
+
+    let services = run(dashboard::get_services(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    let services = to_json(services);
+
+    assert_eq!(services["rowCount"], 2);
+    assert_eq!(services["rows"][0]["id"], "unbound");
+
+    let requests = app.server.requests_to("/api/core/service/search");
+    assert_eq!(requests.len(), 1);
+    let authorization = requests[0].authorization.as_deref().unwrap_or("");
+    assert!(authorization.starts_with("Basic "));
+}
+
+#[test]
+fn unauthorized_key_is_reported() {
+    let app = TestApp::new();
+    app.server
+        .respond("GET", "/api/routes/gateway/status", 401, "{}");
+
+    let error = run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap_err();
+
+    // The frontend branches on `kind` and shows `message`
+    let error = to_json(error);
+    assert_eq!(error["kind"], "auth");
+    let message = error["message"].as_str().unwrap();
+    assert!(message.contains("401"), "unexpected error: {}", message);
+}
+
+#[test]
+fn local_errors_have_a_generic_kind() {
+    let app = TestApp::new();
+    app.server
+        .respond("GET", "/api/routes/gateway/status", 200, "not json");
+
+    let error = run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap_err();
+
+    let error = to_json(error);
+    assert_eq!(error["kind"], "error");
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Failed to parse response"));
+}
@@ -0,0 +1,161 @@
+use super::temp_database_path;
+use crate::db::{ApiInfo, Database};
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+const PIN: &str = "2468";
+
+/// Encrypts the way releases before key derivation did, with SHA-256 of the PIN
+fn legacy_encrypt(plaintext: &str) -> (Vec<u8>, Vec<u8>) {
+    let key = Sha256::digest(PIN.as_bytes());
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let nonce = [7u8; 12];
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .unwrap();
+    (ciphertext, nonce.to_vec())
+}
+
+fn decrypts_with_legacy_key(conn: &Connection) -> bool {
+    let (ciphertext, nonce): (Vec<u8>, Vec<u8>) = conn
+        .query_row(
+            "SELECT encrypted_api_key, api_key_nonce FROM api_info",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    let key = Sha256::digest(PIN.as_bytes());
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .is_ok()
+}
+
+fn key_version(conn: &Connection) -> i64 {
+    conn.query_row(
+        "SELECT key_version FROM app_settings WHERE id = 1",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+fn profile(name: &str) -> ApiInfo {
+    ApiInfo {
+        id: 0,
+        profile_name: name.to_string(),
+        api_key: "new-key".to_string().into(),
+        api_secret: "new-secret".to_string().into(),
+        api_url: "https://fw.example".to_string(),
+        port: 443,
+        is_default: true,
+        cert_fingerprint: None,
+        ca_chain_pem: None,
+        client_cert_pem: None,
+        client_key_pem: None,
+        proxy: None,
+        ssh_tunnel: None,
+        read_only: false,
+    }
+}
+
+#[test]
+fn legacy_credentials_are_reencrypted_on_unlock() {
+    let path = temp_database_path("legacy-key");
+    {
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app_settings (
+                id INTEGER PRIMARY KEY,
+                password_hash TEXT NOT NULL,
+                pin_salt TEXT NOT NULL DEFAULT ''
+            );
+            CREATE TABLE api_info (
+                id INTEGER PRIMARY KEY,
+                profile_name TEXT NOT NULL UNIQUE,
+                encrypted_api_key BLOB NOT NULL,
+                api_key_nonce BLOB NOT NULL,
+                encrypted_api_secret BLOB NOT NULL,
+                api_secret_nonce BLOB NOT NULL,
+                api_url TEXT NOT NULL,
+                port INTEGER NOT NULL,
+                is_default BOOLEAN NOT NULL DEFAULT 0
+            );",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO app_settings (id, password_hash, pin_salt) VALUES (1, ?1, ?2)",
+            params![Database::hash_password(PIN).unwrap(), "b2xkcmVsZWFzZXNhbHQ"],
+        )
+        .unwrap();
+        let (api_key, api_key_nonce) = legacy_encrypt("legacy-key");
+        let (api_secret, api_secret_nonce) = legacy_encrypt("legacy-secret");
+        conn.execute(
+            "INSERT INTO api_info (profile_name, encrypted_api_key, api_key_nonce,
+             encrypted_api_secret, api_secret_nonce, api_url, port, is_default)
+             VALUES ('home', ?1, ?2, ?3, ?4, 'https://fw.example', 443, 1)",
+            params![api_key, api_key_nonce, api_secret, api_secret_nonce],
+        )
+        .unwrap();
+    }
+
+    let database = Database::open(&path).unwrap();
+    assert!(database.verify_pin(PIN).unwrap());
+
+    let info = database.get_default_api_info().unwrap().unwrap();
+    assert_eq!(info.api_key.as_str(), "legacy-key");
+    assert_eq!(info.api_secret.as_str(), "legacy-secret");
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(key_version(&conn), 2);
+    assert!(!decrypts_with_legacy_key(&conn));
+
+    // The derived key is stable across restarts
+    drop(database);
+    let reopened = Database::open(&path).unwrap();
+    assert!(reopened.verify_pin(PIN).unwrap());
+    let info = reopened.get_default_api_info().unwrap().unwrap();
+    assert_eq!(info.api_key.as_str(), "legacy-key");
+}
+
+#[test]
+fn new_credentials_use_the_derived_key() {
+    let path = temp_database_path("derived-key");
+    let database = Database::open(&path).unwrap();
+    database
+        .update_password_hash(&Database::hash_password(PIN).unwrap())
+        .unwrap();
+
+    assert!(!database.verify_pin("0000").unwrap());
+    assert!(database.verify_pin(PIN).unwrap());
+    database.save_api_info(&profile("home")).unwrap();
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(key_version(&conn), 2);
+    assert!(!decrypts_with_legacy_key(&conn));
+
+    let info = database.get_default_api_info().unwrap().unwrap();
+    assert_eq!(info.api_key.as_str(), "new-key");
+    assert_eq!(info.api_secret.as_str(), "new-secret");
+}
+
+#[test]
+fn changed_pin_reencrypts_credentials() {
+    let path = temp_database_path("changed-pin");
+    let database = Database::open(&path).unwrap();
+    database
+        .update_password_hash(&Database::hash_password(PIN).unwrap())
+        .unwrap();
+    assert!(database.verify_pin(PIN).unwrap());
+    database.save_api_info(&profile("home")).unwrap();
+
+    database.update_pin(PIN, "1357").unwrap();
+    drop(database);
+
+    let reopened = Database::open(&path).unwrap();
+    assert!(!reopened.verify_pin(PIN).unwrap());
+    assert!(reopened.verify_pin("1357").unwrap());
+    let info = reopened.get_default_api_info().unwrap().unwrap();
+    assert_eq!(info.api_key.as_str(), "new-key");
+}
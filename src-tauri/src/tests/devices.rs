@@ -0,0 +1,22 @@
+use super::{run, to_json, TestApp};
+use crate::devices;
+
+#[test]
+fn arp_table_is_parsed() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/diagnostics/interface/getArp", "arp_table");
+
+    let devices = run(devices::get_devices(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    let devices = to_json(devices);
+
+    assert_eq!(devices.as_array().unwrap().len(), 1);
+    assert_eq!(devices[0]["mac"], "00:11:22:33:44:55");
+    assert_eq!(devices[0]["type"], "ethernet");
+    assert_eq!(devices[0]["hostname"], "nas.lan");
+}
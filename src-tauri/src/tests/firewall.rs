@@ -0,0 +1,66 @@
+use super::{run, to_json, TestApp};
+use crate::firewall;
+
+#[test]
+fn rules_are_searched_on_legacy_api() {
+    let app = TestApp::new();
+    app.server
+        .fixture("POST", "/api/firewall/filter/search_rule", "firewall_rules");
+
+    let rules = run(firewall::get_firewall_rules(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        Some("lan".to_string()),
+        1,
+        50,
+        None,
+        String::new(),
+    ))
+    .unwrap();
+    let rules = to_json(rules);
+
+    assert_eq!(rules["total"], 2);
+    assert_eq!(rules["rows"][0]["description"], "Allow LAN to any");
+    assert!(rules["rows"][1]["interface"].is_null());
+
+    let search = app.server.requests_to("/api/firewall/filter/search_rule");
+    assert_eq!(search.len(), 1);
+    assert_eq!(search[0].method, "POST");
+    assert_eq!(search[0].json()["interface"], "lan");
+}
+
+#[test]
+fn api_version_check_tolerates_missing_endpoint() {
+    let app = TestApp::new();
+
+    let is_new_api = run(firewall::check_api_version(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert!(!is_new_api);
+}
+
+#[test]
+fn rule_toggle_posts_uuid() {
+    let app = TestApp::new();
+    app.server.respond(
+        "POST",
+        "/api/firewall/filter/toggleRule/abc",
+        200,
+        r#"{"result":"Disabled","changed":true}"#,
+    );
+
+    let result = run(firewall::toggle_firewall_rule(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "abc".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(to_json(result)["changed"], true);
+}
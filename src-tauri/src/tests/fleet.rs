@@ -0,0 +1,156 @@
+use super::{run, to_json, MockOpnsense, TestApp};
+use crate::fleet;
+use std::net::TcpListener;
+
+fn serve_full_status(server: &MockOpnsense) {
+    server
+        .fixture("GET", "/api/core/firmware/status", "firmware_status_minor")
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status")
+        .fixture("GET", "/api/core/service/search", "services_search")
+        .respond(
+            "GET",
+            "/api/diagnostics/activity/getActivity",
+            200,
+            r#"{"headers":["last pid: 4242;  load averages:  0.21,  0.18,  0.12","CPU:  2.0% user,  0.0% nice,  1.5% system,  0.1% interrupt, 96.4% idle"],"details":[]}"#,
+        )
+        .respond(
+            "GET",
+            "/api/diagnostics/system/systemResources",
+            200,
+            r#"{"memory":{"total":"4096","total_frmt":"4 GB","used":1024,"used_frmt":"1 GB"}}"#,
+        )
+        .respond(
+            "GET",
+            "/api/diagnostics/system/systemTime",
+            200,
+            r#"{"uptime":"3 days, 04:05:06","datetime":"Sat Oct 17 10:00:00 UTC 2026","config":"","loadavg":"0.21, 0.18, 0.12"}"#,
+        );
+}
+
+#[test]
+fn overview_summarizes_each_firewall() {
+    let app = TestApp::new();
+    serve_full_status(&app.server);
+
+    let overview = to_json(
+        run(fleet::get_fleet_overview(
+            None,
+            app.handle(),
+            app.database(),
+        ))
+        .unwrap(),
+    );
+
+    assert_eq!(overview["reachable"], 1);
+    assert_eq!(overview["unreachable"], 0);
+    let status = &overview["firewalls"][0]["status"];
+    assert_eq!(overview["firewalls"][0]["profile_name"], "mock");
+    assert_eq!(status["firmware_version"], "25.1.2");
+    assert_eq!(status["updates_available"], true);
+    assert_eq!(status["gateways"]["items"][0]["name"], "WAN_DHCP");
+    assert_eq!(status["stopped_services"], serde_json::json!(["dhcpd"]));
+    assert_eq!(status["cpu_usage_pct"], 3.6);
+    assert_eq!(status["memory"]["used_frmt"], "1 GB");
+    assert_eq!(status["uptime"], "3 days, 04:05:06");
+    assert_eq!(status["errors"], serde_json::json!([]));
+}
+
+#[test]
+fn missing_sections_are_reported_without_hiding_the_rest() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status");
+
+    let overview = to_json(
+        run(fleet::get_fleet_overview(
+            None,
+            app.handle(),
+            app.database(),
+        ))
+        .unwrap(),
+    );
+
+    let member = &overview["firewalls"][0];
+    assert!(member["error"].is_null());
+    assert_eq!(member["status"]["gateways"]["status"], "ok");
+    assert!(member["status"]["uptime"].is_null());
+    assert_eq!(member["status"]["errors"].as_array().unwrap().len(), 5);
+}
+
+#[test]
+fn unreachable_and_slow_firewalls_do_not_fail_the_overview() {
+    let app = TestApp::new();
+    serve_full_status(&app.server);
+
+    // Accepts connections but never answers
+    let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    app.add_profile("silent", silent.local_addr().unwrap().port());
+
+    // Nothing listens here once the listener is dropped
+    let closed_port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    app.add_profile("offline", closed_port);
+
+    let overview = to_json(
+        run(fleet::get_fleet_overview(
+            Some(1),
+            app.handle(),
+            app.database(),
+        ))
+        .unwrap(),
+    );
+
+    assert_eq!(overview["reachable"], 1);
+    assert_eq!(overview["unreachable"], 2);
+    let firewalls = overview["firewalls"].as_array().unwrap();
+    let by_name = |name: &str| {
+        firewalls
+            .iter()
+            .find(|member| member["profile_name"] == name)
+            .unwrap()
+    };
+    assert!(by_name("mock")["status"].is_object());
+    assert!(by_name("offline")["status"].is_null());
+    assert!(by_name("offline")["error"].is_string());
+    assert_eq!(by_name("silent")["error"], "No answer within 1 seconds");
+}
+
+#[test]
+fn certificate_probe_counts_against_the_timeout() {
+    let app = TestApp::new();
+    serve_full_status(&app.server);
+
+    // An https profile without a pinned certificate is probed before the first request
+    let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    app.add_profile("unpinned", silent.local_addr().unwrap().port());
+    let mut unpinned = app
+        .database()
+        .get_api_info(Some("unpinned"))
+        .unwrap()
+        .unwrap();
+    unpinned.api_url = "https://127.0.0.1".to_string();
+    app.database().save_api_info(&unpinned).unwrap();
+
+    let started = std::time::Instant::now();
+    let overview = to_json(
+        run(fleet::get_fleet_overview(
+            Some(1),
+            app.handle(),
+            app.database(),
+        ))
+        .unwrap(),
+    );
+
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(overview["reachable"], 1);
+    let unpinned = overview["firewalls"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|member| member["profile_name"] == "unpinned")
+        .unwrap();
+    assert_eq!(unpinned["error"], "No answer within 1 seconds");
+}
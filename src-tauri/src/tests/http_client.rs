@@ -0,0 +1,72 @@
+use super::{run, TestApp};
+use crate::http_client::{ApiError, ApiRequest};
+
+#[test]
+fn transient_read_failure_is_retried() {
+    let app = TestApp::new();
+    app.server
+        .respond("GET", "/api/core/firmware/status", 502, "Bad Gateway");
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let error = run(client.get("/api/core/firmware/status")).unwrap_err();
+
+    assert!(matches!(error, ApiError::Status { status: 502, .. }));
+    assert_eq!(
+        app.server.requests_to("/api/core/firmware/status").len(),
+        crate::retry::DEFAULT_POLICY.max_attempts as usize
+    );
+}
+
+#[test]
+fn apply_is_never_retried() {
+    let app = TestApp::new();
+    app.server
+        .respond("POST", "/api/firewall/filter/apply", 503, "Unavailable");
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let error = run(client.post("/api/firewall/filter/apply", serde_json::json!({}))).unwrap_err();
+
+    assert!(matches!(error, ApiError::Status { status: 503, .. }));
+    assert_eq!(
+        app.server.requests_to("/api/firewall/filter/apply").len(),
+        1
+    );
+}
+
+#[test]
+fn explicit_idempotent_post_is_retried() {
+    let app = TestApp::new();
+    app.server
+        .respond("POST", "/api/custom/thing/do", 504, "Timeout");
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let request = ApiRequest::post("/api/custom/thing/do", serde_json::json!({})).idempotent(true);
+    let _ = run(client.send(request));
+
+    assert_eq!(app.server.requests_to("/api/custom/thing/do").len(), 3);
+}
+
+#[test]
+fn client_is_shared_until_invalidated() {
+    let app = TestApp::new();
+
+    let first = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let second = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+
+    app.clients().invalidate(first.profile_id());
+    let third = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&first, &third));
+}
+
+#[test]
+fn plain_http_profile_is_not_probed_or_pinned() {
+    let app = TestApp::new();
+
+    run(app.clients().client(&app.database(), app.profile())).unwrap();
+    run(app.clients().client(&app.database(), app.profile())).unwrap();
+
+    assert!(app.server.requests().is_empty());
+    let api_info = app.profile().require_api_info(&app.database()).unwrap();
+    assert!(api_info.cert_fingerprint.is_none());
+}
@@ -0,0 +1,178 @@
+use super::{run, MockOpnsense, TestApp, TEST_API_KEY, TEST_API_SECRET, TEST_PIN};
+use crate::active_profile::SelectedProfile;
+use crate::cassette::{self, CassetteMode};
+use crate::local_users::{self, Role, SignedInUser};
+use crate::{commands, dashboard, firewall, power};
+
+/// Owner-unlocked app with a second profile whose credentials are encrypted
+fn app_with_branch() -> (TestApp, MockOpnsense, SelectedProfile) {
+    let app = TestApp::new();
+    let branch = MockOpnsense::start();
+    let branch_id = app.add_profile("branch", branch.port());
+    branch
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status")
+        .respond(
+            "POST",
+            "/api/firewall/filter/apply",
+            200,
+            r#"{"status":"OK"}"#,
+        )
+        .respond("POST", "/api/core/system/reboot", 200, r#"{"status":"ok"}"#);
+    (app, branch, SelectedProfile::new(Some(branch_id)))
+}
+
+fn add_user(app: &TestApp, name: &str, pin: &str, role: Role) {
+    local_users::add_local_user(name.to_string(), pin.to_string(), role, app.database()).unwrap();
+}
+
+#[test]
+fn viewer_reads_but_cannot_change_anything() {
+    let (app, branch, profile) = app_with_branch();
+    add_user(&app, "junior", "5555", Role::Viewer);
+    let api_info = commands::get_api_info(profile, app.database())
+        .unwrap()
+        .unwrap();
+    assert_eq!(api_info.api_secret.as_str(), TEST_API_SECRET);
+
+    app.database().lock();
+    assert!(app.database().verify_pin("5555").unwrap());
+    let user = app.database().signed_in_user().unwrap();
+    assert_eq!(user.name, "junior");
+    assert_eq!(user.role, Role::Viewer);
+
+    // The viewer's PIN unlocks the owner's encrypted credentials
+    let gateways = run(dashboard::get_gateway_status(
+        profile,
+        app.database(),
+        app.clients(),
+    ));
+    assert!(gateways.is_ok(), "{:?}", gateways.err());
+    assert_eq!(branch.requests_to("/api/routes/gateway/status").len(), 1);
+
+    let error = run(power::reboot_firewall(
+        "unused".to_string(),
+        TEST_PIN.to_string(),
+        profile,
+        app.database(),
+        app.clients(),
+        app.confirmations(),
+    ))
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("admin role"), "{}", error);
+    assert!(run(firewall::apply_firewall_changes(
+        profile,
+        app.database(),
+        app.clients(),
+    ))
+    .is_err());
+    assert!(local_users::list_local_users(app.database()).is_err());
+    assert!(commands::set_default_profile("branch".to_string(), app.database()).is_err());
+    assert!(cassette::start_api_replay(
+        app.handle(),
+        app.database(),
+        app.clients(),
+        "viewer.json".to_string()
+    )
+    .is_err());
+    assert_eq!(app.clients().cassette().mode(), CassetteMode::Off);
+    assert!(cassette::stop_api_cassette(app.database(), app.clients()).is_err());
+    assert!(commands::save_dashboard_preferences(Vec::new(), profile, app.database()).is_err());
+    assert!(commands::update_pin(
+        "5555".to_string(),
+        "6666".to_string(),
+        "6666".to_string(),
+        app.database()
+    )
+    .is_err());
+
+    // Viewers see the profile's settings, but not its secrets
+    let api_info = commands::get_api_info(profile, app.database())
+        .unwrap()
+        .unwrap();
+    assert_eq!(api_info.api_key.as_str(), TEST_API_KEY);
+    assert!(api_info.api_secret.is_empty());
+
+    assert!(branch.requests_to("/api/core/system/reboot").is_empty());
+    assert!(branch.requests_to("/api/firewall/filter/apply").is_empty());
+}
+
+#[test]
+fn operator_applies_rules_but_cannot_reboot() {
+    let (app, branch, profile) = app_with_branch();
+    add_user(&app, "noc", "7777", Role::Operator);
+
+    app.database().lock();
+    assert!(app.database().verify_pin("7777").unwrap());
+
+    run(firewall::apply_firewall_changes(
+        profile,
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    assert!(run(power::reboot_firewall(
+        "unused".to_string(),
+        TEST_PIN.to_string(),
+        profile,
+        app.database(),
+        app.clients(),
+        app.confirmations(),
+    ))
+    .is_err());
+    assert!(branch.requests_to("/api/core/system/reboot").is_empty());
+
+    app.database()
+        .set_signed_in_user(Some(SignedInUser::owner()));
+    local_users::set_local_user_role("noc".to_string(), Role::Admin, app.database()).unwrap();
+    app.database().lock();
+    assert!(app.database().verify_pin("7777").unwrap());
+    assert_eq!(app.database().signed_in_user().unwrap().role, Role::Admin);
+}
+
+#[test]
+fn users_keep_working_after_the_owner_pin_changes() {
+    let (app, branch, profile) = app_with_branch();
+    add_user(&app, "junior", "5555", Role::Viewer);
+
+    app.database().update_pin(TEST_PIN, "864213").unwrap();
+    app.database().lock();
+
+    assert!(app.database().verify_pin("5555").unwrap());
+    run(dashboard::get_gateway_status(
+        profile,
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    assert_eq!(branch.requests_to("/api/routes/gateway/status").len(), 1);
+
+    // Only the owner PIN can be changed this way
+    assert!(app.database().update_pin("5555", "1111").is_err());
+}
+
+#[test]
+fn pins_and_names_must_identify_one_user() {
+    let (app, _branch, _profile) = app_with_branch();
+    add_user(&app, "junior", "5555", Role::Viewer);
+
+    let database = app.database();
+    assert!(database
+        .add_local_user("junior", "6666", Role::Viewer)
+        .is_err());
+    assert!(database
+        .add_local_user("night", "5555", Role::Viewer)
+        .is_err());
+    assert!(database
+        .add_local_user("night", TEST_PIN, Role::Viewer)
+        .is_err());
+    assert!(database
+        .add_local_user("Owner", "6666", Role::Viewer)
+        .is_err());
+    assert!(database
+        .add_local_user("night", "12ab", Role::Viewer)
+        .is_err());
+
+    local_users::remove_local_user("junior".to_string(), app.database()).unwrap();
+    assert!(!app.database().verify_pin("5555").unwrap());
+}
@@ -0,0 +1,185 @@
+use super::temp_database_path;
+use crate::db::Database;
+use crate::migrations::{self, Migration, MIGRATIONS};
+use rusqlite::{Connection, Transaction};
+use std::path::{Path, PathBuf};
+
+fn latest_version() -> u32 {
+    MIGRATIONS.last().unwrap().version
+}
+
+/// Writes a database from a SQL dump of an older release's app.db
+fn database_from_fixture(name: &str) -> PathBuf {
+    let fixture: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{}.sql", name));
+    let sql = std::fs::read_to_string(&fixture)
+        .unwrap_or_else(|e| panic!("Failed to read fixture {}: {}", fixture.display(), e));
+
+    let path = temp_database_path(name);
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(&sql)
+        .unwrap();
+    path
+}
+
+fn schema(path: &Path) -> Vec<String> {
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = conn
+        .prepare("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name")
+        .unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.collect::<rusqlite::Result<_>>().unwrap()
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+    migrations::has_column(conn, table, column).unwrap()
+}
+
+fn has_table(conn: &Connection, table: &str) -> bool {
+    migrations::has_table(conn, table).unwrap()
+}
+
+#[test]
+fn new_database_starts_at_latest_version() {
+    let path = temp_database_path("migrations-fresh");
+    Database::open(&path).unwrap();
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(migrations::schema_version(&conn).unwrap(), latest_version());
+    for table in [
+        "first_run",
+        "app_settings",
+        "rate_limits",
+        "api_audit_log",
+        "change_journal",
+    ] {
+        assert!(has_table(&conn, table), "missing {}", table);
+    }
+    assert!(has_column(&conn, "app_settings", "idle_lock_minutes"));
+}
+
+#[test]
+fn plaintext_setup_release_is_upgraded() {
+    let path = database_from_fixture("db_plaintext_setup");
+    let database = Database::open(&path).unwrap();
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(migrations::schema_version(&conn).unwrap(), latest_version());
+    assert!(!has_table(&conn, "api_info_new"));
+    for column in ["pin_salt", "key_version", "failed_pin_attempts"] {
+        assert!(
+            has_column(&conn, "app_settings", column),
+            "missing {}",
+            column
+        );
+    }
+    for column in ["encrypted_api_key", "ssh_tunnel_nonce", "read_only"] {
+        assert!(has_column(&conn, "api_info", column), "missing {}", column);
+    }
+
+    // The plaintext credentials can't be encrypted without the PIN, so setup runs again
+    assert!(!has_column(&conn, "api_info", "api_key"));
+    let profiles: i64 = conn
+        .query_row("SELECT COUNT(*) FROM api_info", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(profiles, 0);
+    assert!(database.is_first_run().unwrap());
+
+    let salt: String = conn
+        .query_row(
+            "SELECT pin_salt FROM app_settings WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(!salt.is_empty());
+}
+
+#[test]
+fn encrypted_release_is_upgraded() {
+    let path = database_from_fixture("db_encrypted_unversioned");
+    Database::open(&path).unwrap();
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(migrations::schema_version(&conn).unwrap(), latest_version());
+    for column in ["cert_fingerprint", "encrypted_ca_chain", "ssh_tunnel_nonce"] {
+        assert!(has_column(&conn, "api_info", column), "missing {}", column);
+    }
+    assert!(!has_column(&conn, "api_info", "ca_chain_pem"));
+    assert!(has_table(&conn, "rate_limits"));
+
+    // Credentials are still on the SHA-256 key until the next unlock
+    let (key_version, widgets): (i64, i64) = conn
+        .query_row(
+            "SELECT key_version, (SELECT COUNT(*) FROM dashboard_preferences)
+             FROM app_settings WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(key_version, 1);
+    assert_eq!(widgets, 1);
+}
+
+#[test]
+fn reopening_an_upgraded_database_changes_nothing() {
+    let path = database_from_fixture("db_encrypted_unversioned");
+    Database::open(&path).unwrap();
+    let upgraded = schema(&path);
+
+    Database::open(&path).unwrap();
+
+    assert_eq!(schema(&path), upgraded);
+}
+
+fn create_widgets(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute("CREATE TABLE widgets (id INTEGER PRIMARY KEY)", [])?;
+    Ok(())
+}
+
+fn create_gadgets_then_fail(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute("CREATE TABLE gadgets (id INTEGER PRIMARY KEY)", [])?;
+    tx.execute("INSERT INTO missing_table (id) VALUES (1)", [])?;
+    Ok(())
+}
+
+#[test]
+fn failed_migration_is_rolled_back() {
+    let steps = [
+        Migration {
+            version: 1,
+            description: "widgets",
+            apply: create_widgets,
+        },
+        Migration {
+            version: 2,
+            description: "gadgets",
+            apply: create_gadgets_then_fail,
+        },
+    ];
+    let mut conn = Connection::open_in_memory().unwrap();
+
+    assert!(migrations::migrate(&mut conn, &steps).is_err());
+
+    assert_eq!(migrations::schema_version(&conn).unwrap(), 1);
+    assert!(has_table(&conn, "widgets"));
+    assert!(!has_table(&conn, "gadgets"));
+}
+
+#[test]
+fn newer_schema_is_left_alone() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", latest_version() + 1)
+        .unwrap();
+
+    migrations::migrate(&mut conn, MIGRATIONS).unwrap();
+
+    assert_eq!(
+        migrations::schema_version(&conn).unwrap(),
+        latest_version() + 1
+    );
+    assert!(!has_table(&conn, "app_settings"));
+}
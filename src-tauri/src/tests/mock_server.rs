@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener as StdTcpListener};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request the mock firewall received, kept for assertions
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub proxy_authorization: Option<String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or(serde_json::Value::Null)
+    }
+}
+
+#[derive(Clone)]
+struct MockResponse {
+    status: u16,
+    body: String,
+}
+
+type Routes = Arc<Mutex<HashMap<(String, String), MockResponse>>>;
+
+/// Minimal HTTP/1.1 server that answers `/api/...` calls with JSON fixtures
+///
+/// Unregistered endpoints return 404 like an OPNsense box without the plugin or
+/// controller installed. Every connection is closed after one response.
+pub struct MockOpnsense {
+    addr: SocketAddr,
+    routes: Routes,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockOpnsense {
+    pub fn start() -> Self {
+        let listener = StdTcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+        listener
+            .set_nonblocking(true)
+            .expect("Failed to set mock server non-blocking");
+        let addr = listener.local_addr().unwrap();
+
+        let routes: Routes = Arc::default();
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::default();
+
+        let task_routes = routes.clone();
+        let task_requests = requests.clone();
+        tauri::async_runtime::spawn(async move {
+            let listener = TcpListener::from_std(listener).unwrap();
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let routes = task_routes.clone();
+                let requests = task_requests.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _ = handle_connection(stream, routes, requests).await;
+                });
+            }
+        });
+
+        Self {
+            addr,
+            routes,
+            requests,
+        }
+    }
+
+    pub fn api_url(&self) -> String {
+        "http://127.0.0.1".to_string()
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Serves `tests/fixtures/<name>.json` for `method path`
+    pub fn fixture(&self, method: &str, path: &str, name: &str) -> &Self {
+        self.respond(method, path, 200, &load_fixture(name))
+    }
+
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: &str) -> &Self {
+        self.routes.lock().unwrap().insert(
+            (method.to_string(), path.to_string()),
+            MockResponse {
+                status,
+                body: body.to_string(),
+            },
+        );
+        self
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Requests whose path (without query string) equals `path`
+    pub fn requests_to(&self, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| strip_query(&request.path) == path)
+            .collect()
+    }
+}
+
+pub fn load_fixture(name: &str) -> String {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{}.json", name));
+
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read fixture {}: {}", path.display(), e))
+}
+
+fn strip_query(path: &str) -> &str {
+    path.split('?').next().unwrap_or(path)
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    routes: Routes,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) -> std::io::Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let target = request_line.next().unwrap_or("");
+    // Requests sent through an HTTP proxy use the absolute form
+    let path = match target.strip_prefix("http://") {
+        Some(rest) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+        None => target,
+    }
+    .to_string();
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    let mut proxy_authorization = None;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "authorization" => authorization = Some(value.trim().to_string()),
+                "proxy-authorization" => proxy_authorization = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    while buffer.len() < header_end + content_length {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let body = String::from_utf8_lossy(&buffer[header_end..]).to_string();
+
+    let response = routes
+        .lock()
+        .unwrap()
+        .get(&(method.clone(), strip_query(&path).to_string()))
+        .cloned()
+        .unwrap_or(MockResponse {
+            status: 404,
+            body: r#"{"errorMessage":"Endpoint not found"}"#.to_string(),
+        });
+
+    requests.lock().unwrap().push(RecordedRequest {
+        method,
+        path,
+        authorization,
+        proxy_authorization,
+        body,
+    });
+
+    let reply = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    stream.shutdown().await
+}
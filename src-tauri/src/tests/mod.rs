@@ -0,0 +1,196 @@
+//! Integration tests that run each command module against [`MockOpnsense`]
+
+mod mock_server;
+
+mod active_profile;
+mod alias;
+mod api_key_file;
+mod audit_log;
+mod auto_lock;
+mod capabilities;
+mod cassette;
+mod change_journal;
+mod confirmation;
+mod dashboard;
+mod db;
+mod devices;
+mod firewall;
+mod fleet;
+mod http_client;
+mod local_users;
+mod migrations;
+mod pin_lockout;
+mod power;
+mod profile_transfer;
+mod proxy;
+mod rate_limit;
+mod read_only;
+mod routes;
+mod search_grid;
+mod snapshots;
+mod ssh_tunnel;
+mod system_resources;
+mod traffic;
+mod tunables;
+mod unbound;
+mod undo;
+mod update_checker;
+mod wol;
+
+pub use mock_server::MockOpnsense;
+
+use crate::active_profile::{ActiveProfiles, SelectedProfile};
+use crate::auto_lock::{IdleLock, DEFAULT_IDLE_LOCK_MINUTES};
+use crate::confirmation::{request_confirmation, Confirmations, DestructiveAction};
+use crate::db::{ApiInfo, Database};
+use crate::http_client::ClientManager;
+use std::future::Future;
+use std::path::PathBuf;
+use tauri::test::MockRuntime;
+use tauri::{App, AppHandle, Manager, State};
+
+pub const TEST_API_KEY: &str = "test-key";
+pub const TEST_API_SECRET: &str = "test-secret";
+pub const TEST_PIN: &str = "1357";
+
+/// Mock Tauri app with an in-memory database whose default profile points at a
+/// fresh [`MockOpnsense`]
+pub struct TestApp {
+    app: App<MockRuntime>,
+    pub server: MockOpnsense,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let server = MockOpnsense::start();
+
+        let database = Database::open_in_memory().expect("Failed to open test database");
+        database
+            .update_password_hash(&Database::hash_password(TEST_PIN).unwrap())
+            .unwrap();
+        assert!(database.verify_pin(TEST_PIN).unwrap());
+        database
+            .save_api_info(&ApiInfo {
+                id: 0,
+                profile_name: "mock".to_string(),
+                api_key: TEST_API_KEY.to_string().into(),
+                api_secret: TEST_API_SECRET.to_string().into(),
+                api_url: server.api_url(),
+                port: server.port(),
+                is_default: true,
+                cert_fingerprint: None,
+                ca_chain_pem: None,
+                client_cert_pem: None,
+                client_key_pem: None,
+                proxy: None,
+                ssh_tunnel: None,
+                read_only: false,
+            })
+            .expect("Failed to save test profile");
+
+        // Commands run as the owner unless a test signs in as someone else
+        let app = tauri::test::mock_app();
+        let clients = ClientManager::new().with_audit_log(database.audit_log());
+        clients.clear_on_wipe(&database);
+        app.manage(database);
+        app.manage(clients);
+        app.manage(IdleLock::new(DEFAULT_IDLE_LOCK_MINUTES));
+        app.manage(ActiveProfiles::new());
+        app.manage(Confirmations::new());
+
+        Self { app, server }
+    }
+
+    pub fn handle(&self) -> AppHandle<MockRuntime> {
+        self.app.handle().clone()
+    }
+
+    pub fn database(&self) -> State<'_, Database> {
+        self.app.state()
+    }
+
+    pub fn clients(&self) -> State<'_, ClientManager> {
+        self.app.state()
+    }
+
+    pub fn idle_lock(&self) -> State<'_, IdleLock> {
+        self.app.state()
+    }
+
+    pub fn active_profiles(&self) -> State<'_, ActiveProfiles> {
+        self.app.state()
+    }
+
+    pub fn confirmations(&self) -> State<'_, Confirmations> {
+        self.app.state()
+    }
+
+    /// Asks for a confirmation token the way the UI does before a destructive operation
+    pub fn confirmation_token(
+        &self,
+        action: DestructiveAction,
+        profile: SelectedProfile,
+    ) -> String {
+        request_confirmation(action, profile, self.database(), self.confirmations())
+            .expect("Confirmation should be issued")
+            .token
+    }
+
+    /// Unlocks with the owner PIN [`TEST_PIN`], signing in as the owner again
+    pub fn unlock(&self) {
+        assert!(self.database().verify_pin(TEST_PIN).unwrap());
+    }
+
+    /// Unlocks the database and saves another profile at `127.0.0.1:port`, returning its id
+    pub fn add_profile(&self, profile_name: &str, port: u16) -> i64 {
+        self.unlock();
+
+        let database = self.database();
+        database
+            .save_api_info(&ApiInfo {
+                id: 0,
+                profile_name: profile_name.to_string(),
+                api_key: TEST_API_KEY.to_string().into(),
+                api_secret: TEST_API_SECRET.to_string().into(),
+                api_url: self.server.api_url(),
+                port,
+                is_default: false,
+                cert_fingerprint: None,
+                ca_chain_pem: None,
+                client_cert_pem: None,
+                client_key_pem: None,
+                proxy: None,
+                ssh_tunnel: None,
+                read_only: false,
+            })
+            .expect("Failed to save extra test profile");
+
+        database
+            .get_api_info(Some(profile_name))
+            .unwrap()
+            .expect("Extra test profile should be saved")
+            .id
+    }
+
+    /// What a window that hasn't picked a profile sends: the default, mock profile
+    pub fn profile(&self) -> SelectedProfile {
+        SelectedProfile::new(None)
+    }
+}
+
+/// Drives a command future to completion on the Tauri runtime the mock server uses
+pub fn run<F: Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+/// Serializes a command result the way it reaches the frontend
+pub fn to_json<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).expect("Command result should serialize")
+}
+
+/// Path for a database file that outlives a `Database`, cleared of any previous run's file
+pub fn temp_database_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("opnmanager-{}-{}.db", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
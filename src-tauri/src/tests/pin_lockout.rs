@@ -0,0 +1,139 @@
+use super::{run, temp_database_path, TestApp, TEST_PIN};
+use crate::change_journal::ChangeFilter;
+use crate::db::Database;
+use crate::pin_lockout::{self, MAX_PIN_ATTEMPTS};
+use crate::routes;
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+const PIN: &str = "2468";
+
+fn database_with_pin(path: &Path) -> Database {
+    let database = Database::open(path).unwrap();
+    database
+        .update_password_hash(&Database::hash_password(PIN).unwrap())
+        .unwrap();
+    database
+}
+
+#[test]
+fn repeated_failures_delay_further_attempts_across_restarts() {
+    let path = temp_database_path("pin-delay");
+    let database = database_with_pin(&path);
+
+    for _ in 0..5 {
+        assert!(!database.verify_pin("0000").unwrap());
+    }
+    let error = database.verify_pin(PIN).unwrap_err();
+    assert!(error.contains("Try again in"), "{}", error);
+
+    // Restarting the app keeps the counter and the delay
+    drop(database);
+    let database = Database::open(&path).unwrap();
+    assert!(database.verify_pin(PIN).is_err());
+    assert_eq!(database.pin_lockout().unwrap().failed_attempts, 5);
+
+    Connection::open(&path)
+        .unwrap()
+        .execute("UPDATE app_settings SET pin_retry_after = 0", [])
+        .unwrap();
+    assert!(database.verify_pin(PIN).unwrap());
+    assert_eq!(database.pin_lockout().unwrap().failed_attempts, 0);
+}
+
+#[test]
+fn hard_lockout_refuses_the_right_pin() {
+    let path = temp_database_path("pin-lockout");
+    let database = database_with_pin(&path);
+    Connection::open(&path)
+        .unwrap()
+        .execute(
+            "UPDATE app_settings SET failed_pin_attempts = ?1",
+            params![MAX_PIN_ATTEMPTS - 1],
+        )
+        .unwrap();
+
+    assert!(!database.verify_pin("0000").unwrap());
+    assert!(database.pin_lockout().unwrap().is_locked_out());
+
+    Connection::open(&path)
+        .unwrap()
+        .execute("UPDATE app_settings SET pin_retry_after = 0", [])
+        .unwrap();
+    let error = database.verify_pin(PIN).unwrap_err();
+    assert!(error.contains("Reset the app"), "{}", error);
+}
+
+#[test]
+fn credentials_are_wiped_after_threshold() {
+    let app = TestApp::new();
+    app.database().set_has_run().unwrap();
+
+    assert!(
+        pin_lockout::set_pin_wipe_threshold("0000".to_string(), Some(3), app.database()).is_err()
+    );
+    pin_lockout::set_pin_wipe_threshold(TEST_PIN.to_string(), Some(3), app.database()).unwrap();
+
+    // Leaves a pooled client, audited requests and a journaled change behind
+    app.server
+        .respond(
+            "POST",
+            "/api/routes/routes/delroute/r1",
+            200,
+            r#"{"result":"deleted"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+    run(routes::delete_route(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "r1".to_string(),
+    ))
+    .unwrap();
+    assert_eq!(app.clients().pooled_clients(), 1);
+    assert!(!app
+        .database()
+        .audit_log()
+        .recent(None, 10)
+        .unwrap()
+        .is_empty());
+
+    assert!(!app.database().verify_pin("0000").unwrap());
+    assert!(!app.database().verify_pin("0000").unwrap());
+    let error = app.database().verify_pin("0000").unwrap_err();
+    assert!(error.contains("erased"), "{}", error);
+
+    assert_eq!(app.clients().pooled_clients(), 0);
+    assert!(app
+        .database()
+        .audit_log()
+        .recent(None, 10)
+        .unwrap()
+        .is_empty());
+    assert!(app
+        .database()
+        .change_journal()
+        .search(&ChangeFilter::default(), None)
+        .unwrap()
+        .is_empty());
+
+    assert!(app.database().is_first_run().unwrap());
+    assert!(!app.database().verify_pin(TEST_PIN).unwrap());
+    assert_eq!(app.database().pin_lockout().unwrap().failed_attempts, 0);
+}
+
+#[test]
+fn reset_is_only_allowed_when_locked_out() {
+    let app = TestApp::new();
+    app.database()
+        .update_password_hash(&Database::hash_password(PIN).unwrap())
+        .unwrap();
+
+    assert!(pin_lockout::reset_locked_app(app.database()).is_err());
+    assert!(app.database().get_default_api_info().unwrap().is_some());
+}
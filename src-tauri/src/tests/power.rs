@@ -0,0 +1,26 @@
+use super::{run, TestApp, TEST_PIN};
+use crate::confirmation::DestructiveAction;
+use crate::power;
+
+#[test]
+fn failed_reboot_is_not_retried() {
+    let app = TestApp::new();
+    app.unlock();
+    app.server
+        .respond("POST", "/api/core/system/reboot", 502, "Bad Gateway");
+    let token = app.confirmation_token(DestructiveAction::RebootFirewall, app.profile());
+
+    let error = run(power::reboot_firewall(
+        token,
+        TEST_PIN.to_string(),
+        app.profile(),
+        app.database(),
+        app.clients(),
+        app.confirmations(),
+    ))
+    .unwrap_err()
+    .to_string();
+
+    assert!(error.contains("502"), "unexpected error: {}", error);
+    assert_eq!(app.server.requests_to("/api/core/system/reboot").len(), 1);
+}
@@ -0,0 +1,188 @@
+use super::{to_json, TestApp, TEST_API_KEY};
+use crate::db::DashboardWidgetPref;
+use crate::error::CommandError;
+use crate::profile_transfer::{self, CollisionAction};
+use std::collections::HashMap;
+
+const PASSPHRASE: &str = "correct horse battery";
+
+fn export_mock_profile(app: &TestApp) -> String {
+    let profile = app.database().get_api_info(Some("mock")).unwrap().unwrap();
+    app.database()
+        .save_dashboard_preferences(
+            profile.id,
+            &[DashboardWidgetPref {
+                widget_key: "traffic".to_string(),
+                visible: false,
+                position: 3,
+            }],
+        )
+        .unwrap();
+
+    profile_transfer::export_profiles(
+        vec!["mock".to_string()],
+        PASSPHRASE.to_string(),
+        app.database(),
+    )
+    .unwrap()
+}
+
+fn import(
+    app: &TestApp,
+    contents: &str,
+    collisions: HashMap<String, CollisionAction>,
+) -> Result<profile_transfer::ImportSummary, CommandError> {
+    profile_transfer::import_profiles(
+        contents.to_string(),
+        PASSPHRASE.to_string(),
+        collisions,
+        app.database(),
+        app.clients(),
+    )
+}
+
+#[test]
+fn colliding_profile_is_imported_under_a_new_name() {
+    let source = TestApp::new();
+    source.unlock();
+    let contents = export_mock_profile(&source);
+    assert!(!contents.contains(TEST_API_KEY));
+
+    let target = TestApp::new();
+    target.unlock();
+
+    let preview = profile_transfer::preview_profile_import(
+        contents.clone(),
+        PASSPHRASE.to_string(),
+        target.database(),
+    )
+    .unwrap();
+    assert_eq!(to_json(&preview)[0]["exists"], true);
+
+    // Collisions need a decision before anything is written
+    assert!(import(&target, &contents, HashMap::new()).is_err());
+
+    let summary = import(
+        &target,
+        &contents,
+        HashMap::from([(
+            "mock".to_string(),
+            CollisionAction::Rename {
+                name: "branch".to_string(),
+            },
+        )]),
+    )
+    .unwrap();
+    assert_eq!(summary.imported, vec!["branch".to_string()]);
+
+    let imported = target
+        .database()
+        .get_api_info(Some("branch"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(imported.api_key.as_str(), TEST_API_KEY);
+    assert_eq!(imported.api_url, source.server.api_url());
+    assert!(!imported.is_default);
+
+    let dashboard = target
+        .database()
+        .get_dashboard_preferences(imported.id)
+        .unwrap();
+    assert!(!dashboard["traffic"].visible);
+    assert_eq!(dashboard["traffic"].position, 3);
+}
+
+#[test]
+fn merged_profile_keeps_its_id_and_default_flag() {
+    let source = TestApp::new();
+    source.unlock();
+    let contents = export_mock_profile(&source);
+
+    let target = TestApp::new();
+    target.unlock();
+    let before = target
+        .database()
+        .get_api_info(Some("mock"))
+        .unwrap()
+        .unwrap();
+
+    let summary = import(
+        &target,
+        &contents,
+        HashMap::from([("mock".to_string(), CollisionAction::Merge)]),
+    )
+    .unwrap();
+    assert_eq!(summary.merged, vec!["mock".to_string()]);
+
+    let merged = target
+        .database()
+        .get_api_info(Some("mock"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(merged.id, before.id);
+    assert!(merged.is_default);
+    assert_eq!(merged.api_url, source.server.api_url());
+}
+
+#[test]
+fn wrong_passphrase_is_rejected() {
+    let app = TestApp::new();
+    app.unlock();
+    let contents = export_mock_profile(&app);
+
+    let result = profile_transfer::preview_profile_import(
+        contents,
+        "not the passphrase".to_string(),
+        app.database(),
+    );
+
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Wrong passphrase or damaged export file"
+    );
+}
+
+#[test]
+fn duplicate_names_in_the_file_are_rejected() {
+    let source = TestApp::new();
+    source.unlock();
+    let contents = profile_transfer::export_profiles(
+        vec!["mock".to_string(), "mock".to_string()],
+        PASSPHRASE.to_string(),
+        source.database(),
+    )
+    .unwrap();
+
+    let target = TestApp::new();
+    target.unlock();
+    let error = import(
+        &target,
+        &contents,
+        HashMap::from([("mock".to_string(), CollisionAction::Merge)]),
+    )
+    .unwrap_err()
+    .to_string();
+
+    assert!(error.contains("more than once"), "{}", error);
+    let profiles = target.database().list_api_profiles().unwrap();
+    assert_eq!(profiles.len(), 1);
+}
+
+#[test]
+fn excessive_key_cost_is_rejected() {
+    let app = TestApp::new();
+    app.unlock();
+    let mut file: serde_json::Value = serde_json::from_str(&export_mock_profile(&app)).unwrap();
+    file["kdf"]["memory_kib"] = serde_json::json!(u32::MAX);
+
+    let result = profile_transfer::preview_profile_import(
+        file.to_string(),
+        PASSPHRASE.to_string(),
+        app.database(),
+    );
+
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Unsupported export file key parameters"
+    );
+}
@@ -0,0 +1,79 @@
+use super::{run, to_json, TestApp};
+use crate::dashboard;
+use crate::proxy::{ProxyKind, ProxySettings};
+
+fn http_proxy(port: u16) -> ProxySettings {
+    ProxySettings {
+        kind: ProxyKind::Http,
+        host: "127.0.0.1".to_string(),
+        port,
+        username: Some("tech".to_string()),
+        password: Some("bastion-pass".to_string().into()),
+    }
+}
+
+#[test]
+fn requests_are_routed_through_profile_proxy() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status");
+
+    // The firewall itself is unreachable, only the proxy can get there
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    api_info.api_url = "http://firewall.invalid".to_string();
+    api_info.port = 80;
+    api_info.proxy = Some(http_proxy(app.server.port()));
+    app.database().save_api_info(&api_info).unwrap();
+
+    let status = run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    assert_eq!(to_json(status)["status"], "ok");
+
+    let requests = app.server.requests_to("/api/routes/gateway/status");
+    assert_eq!(requests.len(), 1);
+    let proxy_authorization = requests[0].proxy_authorization.as_deref().unwrap_or("");
+    assert!(proxy_authorization.starts_with("Basic "));
+}
+
+#[test]
+fn proxy_settings_are_persisted() {
+    let app = TestApp::new();
+
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    api_info.proxy = Some(http_proxy(3128));
+    app.database().save_api_info(&api_info).unwrap();
+
+    let saved = app.database().get_default_api_info().unwrap().unwrap();
+    assert_eq!(saved.proxy, Some(http_proxy(3128)));
+}
+
+#[test]
+fn socks_proxy_resolves_hostnames_remotely() {
+    let proxy = ProxySettings {
+        kind: ProxyKind::Socks5,
+        host: "bastion.example.net".to_string(),
+        port: 1080,
+        username: None,
+        password: None,
+    };
+
+    assert_eq!(proxy.url(), "socks5h://bastion.example.net:1080");
+}
+
+#[test]
+fn invalid_proxy_settings_are_rejected() {
+    let mut proxy = http_proxy(3128);
+    proxy.host = "http://proxy.example.net".to_string();
+    assert!(proxy.validate().is_err());
+
+    let mut proxy = http_proxy(0);
+    assert!(proxy.validate().is_err());
+
+    proxy.port = 3128;
+    proxy.username = None;
+    assert!(proxy.validate().is_err());
+}
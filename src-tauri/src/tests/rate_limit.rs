@@ -0,0 +1,126 @@
+use super::{run, TestApp};
+use crate::commands;
+use crate::rate_limit::{Priority, RateLimits, RequestLimiter, DEFAULT_LIMITS};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn limits(max_concurrent: u32, requests_per_second: f64, burst: u32) -> RateLimits {
+    RateLimits {
+        max_concurrent,
+        requests_per_second,
+        burst,
+    }
+}
+
+#[test]
+fn concurrent_requests_are_capped() {
+    let limiter = RequestLimiter::new(limits(1, 1000.0, 100));
+
+    run(async {
+        let first = limiter.acquire(Priority::Interactive, "first").await;
+
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(50),
+            limiter.acquire(Priority::Interactive, "second"),
+        )
+        .await;
+        assert!(blocked.is_err(), "second request should wait for a slot");
+
+        drop(first);
+        let second = tokio::time::timeout(
+            Duration::from_millis(500),
+            limiter.acquire(Priority::Interactive, "second"),
+        )
+        .await;
+        assert!(second.is_ok(), "released slot should be handed on");
+    });
+}
+
+#[test]
+fn token_bucket_spaces_out_requests() {
+    let limiter = RequestLimiter::new(limits(10, 20.0, 1));
+
+    let elapsed = run(async {
+        let started = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire(Priority::Interactive, "request").await;
+        }
+        started.elapsed()
+    });
+
+    // One token up front, then one every 50ms
+    assert!(elapsed >= Duration::from_millis(90), "took {:?}", elapsed);
+}
+
+#[test]
+fn interactive_requests_are_served_before_pollers() {
+    let limiter = Arc::new(RequestLimiter::new(limits(1, 1000.0, 100)));
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    run(async {
+        let held = limiter.acquire(Priority::Interactive, "held").await;
+
+        let queue = |priority: Priority, name: &'static str| {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            tauri::async_runtime::spawn(async move {
+                let _permit = limiter.acquire(priority, name).await;
+                order.lock().unwrap().push(name);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            })
+        };
+
+        // The poller queues first but the user action still goes ahead of it
+        let poller = queue(Priority::Background, "poller");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let user = queue(Priority::Interactive, "user");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        drop(held);
+        user.await.unwrap();
+        poller.await.unwrap();
+    });
+
+    assert_eq!(*order.lock().unwrap(), vec!["user", "poller"]);
+}
+
+#[test]
+fn profile_rate_limits_are_persisted() {
+    let app = TestApp::new();
+    let custom = limits(1, 2.0, 3);
+
+    commands::set_profile_rate_limits(
+        "mock".to_string(),
+        Some(custom),
+        app.database(),
+        app.clients(),
+    )
+    .unwrap();
+    let saved = commands::get_profile_rate_limits("mock".to_string(), app.database()).unwrap();
+    assert_eq!(saved, custom);
+
+    commands::set_profile_rate_limits("mock".to_string(), None, app.database(), app.clients())
+        .unwrap();
+    let reset = commands::get_profile_rate_limits("mock".to_string(), app.database()).unwrap();
+    assert_eq!(reset, DEFAULT_LIMITS);
+}
+
+#[test]
+fn invalid_rate_limits_are_rejected() {
+    let app = TestApp::new();
+
+    let result = commands::set_profile_rate_limits(
+        "mock".to_string(),
+        Some(limits(0, 5.0, 5)),
+        app.database(),
+        app.clients(),
+    );
+
+    assert!(result.is_err());
+    let profile = app.database().get_default_api_info().unwrap().unwrap();
+    assert!(app
+        .database()
+        .get_rate_limits(profile.id)
+        .unwrap()
+        .is_none());
+}
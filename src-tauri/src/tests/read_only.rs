@@ -0,0 +1,162 @@
+use super::{run, MockOpnsense, TestApp, TEST_PIN};
+use crate::active_profile::SelectedProfile;
+use crate::http_client::ApiError;
+use crate::read_only::is_mutating;
+use crate::{commands, dashboard, firewall, power, update_checker};
+
+/// App with a second profile, switched to read-only
+fn app_with_read_only_branch() -> (TestApp, MockOpnsense, SelectedProfile) {
+    let app = TestApp::new();
+    let branch = MockOpnsense::start();
+    let branch_id = app.add_profile("branch", branch.port());
+    branch
+        .fixture("GET", "/api/routes/gateway/status", "gateway_status")
+        .respond(
+            "POST",
+            "/api/firewall/filter/apply",
+            200,
+            r#"{"status":"OK"}"#,
+        )
+        .respond("POST", "/api/core/system/reboot", 200, r#"{"status":"ok"}"#);
+
+    commands::set_profile_read_only("branch".to_string(), true, app.database(), app.clients())
+        .unwrap();
+    (app, branch, SelectedProfile::new(Some(branch_id)))
+}
+
+#[test]
+fn read_only_profile_refuses_changes_before_sending() {
+    let (app, branch, profile) = app_with_read_only_branch();
+
+    let error = run(firewall::apply_firewall_changes(
+        profile,
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap_err();
+    assert_eq!(error.kind(), "read_only");
+    assert!(error.to_string().contains("read-only"), "{}", error);
+    assert!(run(power::reboot_firewall(
+        "unused".to_string(),
+        TEST_PIN.to_string(),
+        profile,
+        app.database(),
+        app.clients(),
+        app.confirmations(),
+    ))
+    .is_err());
+
+    // Requests that skip the command layer are refused by the client itself
+    let client = run(app.clients().client(&app.database(), profile)).unwrap();
+    let error = run(client.post("/api/core/system/reboot", serde_json::json!({}))).unwrap_err();
+    assert!(matches!(error, ApiError::ReadOnly { .. }));
+    assert_eq!(error.kind(), "read_only");
+
+    assert!(branch.requests_to("/api/firewall/filter/apply").is_empty());
+    assert!(branch.requests_to("/api/core/system/reboot").is_empty());
+}
+
+#[test]
+fn read_only_profile_still_reads() {
+    let (app, branch, profile) = app_with_read_only_branch();
+
+    let gateways = run(dashboard::get_gateway_status(
+        profile,
+        app.database(),
+        app.clients(),
+    ));
+    assert!(gateways.is_ok(), "{:?}", gateways.err());
+    assert_eq!(branch.requests_to("/api/routes/gateway/status").len(), 1);
+    assert!(
+        app.database()
+            .get_api_info(Some("branch"))
+            .unwrap()
+            .unwrap()
+            .read_only
+    );
+}
+
+#[test]
+fn turning_read_only_off_allows_changes_again() {
+    let (app, branch, profile) = app_with_read_only_branch();
+
+    commands::set_profile_read_only("branch".to_string(), false, app.database(), app.clients())
+        .unwrap();
+    run(firewall::apply_firewall_changes(
+        profile,
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(branch.requests_to("/api/firewall/filter/apply").len(), 1);
+}
+
+#[test]
+fn read_only_profile_still_checks_for_updates() {
+    let (app, branch, profile) = app_with_read_only_branch();
+    branch
+        .respond(
+            "POST",
+            "/api/core/firmware/check",
+            200,
+            r#"{"status":"ok"}"#,
+        )
+        .respond(
+            "GET",
+            "/api/core/firmware/upgradestatus",
+            200,
+            r#"{"status":"done"}"#,
+        )
+        .fixture("GET", "/api/core/firmware/status", "firmware_status_minor")
+        .fixture("GET", "/api/core/firmware/info", "firmware_info")
+        .respond(
+            "POST",
+            "/api/core/firmware/changelog/25.1.3",
+            200,
+            r#"{"html":"<p>Fixes</p>"}"#,
+        );
+
+    let updates = run(update_checker::check_for_updates(
+        profile,
+        app.database(),
+        app.clients(),
+    ));
+    assert!(updates.is_ok(), "{:?}", updates.err());
+    assert_eq!(branch.requests_to("/api/core/firmware/check").len(), 1);
+
+    let changelog = run(update_checker::get_changelog(
+        profile,
+        app.database(),
+        app.clients(),
+        "25.1.3".to_string(),
+    ))
+    .unwrap();
+    assert_eq!(changelog, "<p>Fixes</p>");
+}
+
+#[test]
+fn mutating_requests_are_recognized() {
+    for endpoint in [
+        "/api/firewall/filter/apply",
+        "/api/firewall/alias/setItem/abc",
+        "/api/routes/routes/delroute/abc",
+        "/api/core/snapshots/activate/abc",
+        "/api/core/system/reboot",
+        "/api/core/firmware/update",
+    ] {
+        assert!(is_mutating(endpoint), "{}", endpoint);
+    }
+
+    for endpoint in [
+        "/api/routes/gateway/status",
+        "/api/firewall/filter/search_rule",
+        "/api/diagnostics/interface/getArp",
+        "/api/core/firmware/upgradestatus?v=1",
+        "/api/diagnostics/system/systemResources",
+        "/api/core/firmware/check",
+        "/api/core/firmware/changelog/25.1.3",
+    ] {
+        assert!(!is_mutating(endpoint), "{}", endpoint);
+    }
+}
@@ -0,0 +1,70 @@
+use super::{run, to_json, TestApp};
+use crate::routes;
+
+#[test]
+fn routes_are_searched() {
+    let app = TestApp::new();
+    app.server
+        .fixture("POST", "/api/routes/routes/searchroute", "routes_search");
+
+    let routes = run(routes::get_routes(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        1,
+        50,
+        None,
+        "10.20".to_string(),
+    ))
+    .unwrap();
+    let routes = to_json(routes);
+
+    assert_eq!(routes["rowCount"], 1);
+    assert_eq!(routes["rows"][0]["network"], "10.20.0.0/16");
+
+    let search = app.server.requests_to("/api/routes/routes/searchroute");
+    assert_eq!(search.len(), 1);
+    assert_eq!(search[0].json()["searchPhrase"], "10.20");
+}
+
+#[test]
+fn added_route_is_applied() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "POST",
+            "/api/routes/routes/addroute",
+            200,
+            r#"{"result":"saved","uuid":"d1f5e0a2"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    let result = run(routes::add_route(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "10.30.0.0/16".to_string(),
+        "VPN_GW".to_string(),
+        "Site C".to_string(),
+        false,
+    ))
+    .unwrap();
+
+    assert_eq!(to_json(result)["uuid"], "d1f5e0a2");
+
+    let added = app.server.requests_to("/api/routes/routes/addroute");
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].json()["route"]["network"], "10.30.0.0/16");
+    assert_eq!(added[0].json()["route"]["disabled"], "0");
+    assert_eq!(
+        app.server
+            .requests_to("/api/routes/routes/reconfigure")
+            .len(),
+        1
+    );
+}
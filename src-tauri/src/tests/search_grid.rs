@@ -0,0 +1,144 @@
+use super::{run, TestApp};
+use crate::alias;
+use crate::search_grid::{SearchPager, SearchQuery, SortOrder};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const ENDPOINT: &str = "/api/firewall/alias/searchItem";
+
+fn collect_all(mut pager: SearchPager<'_, Value>) -> Vec<Value> {
+    let mut rows = Vec::new();
+    while let Some(page) = run(pager.next_page()).unwrap() {
+        rows.extend(page);
+    }
+    rows
+}
+
+#[test]
+fn pager_walks_every_page() {
+    let app = TestApp::new();
+    app.server.respond(
+        "POST",
+        ENDPOINT,
+        200,
+        r#"{"rows":[{"uuid":"a1"}],"rowCount":1,"total":3,"current":1}"#,
+    );
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let rows = collect_all(SearchPager::new(
+        &client,
+        ENDPOINT,
+        SearchQuery::new().rows(1),
+    ));
+
+    assert_eq!(rows.len(), 3);
+    let pages: Vec<Value> = app
+        .server
+        .requests_to(ENDPOINT)
+        .iter()
+        .map(|request| request.json()["current"].clone())
+        .collect();
+    assert_eq!(pages, vec![1, 2, 3]);
+}
+
+#[test]
+fn short_page_ends_the_walk_without_a_total() {
+    let app = TestApp::new();
+    app.server
+        .respond("POST", ENDPOINT, 200, r#"{"rows":[{"uuid":"a1"}]}"#);
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let rows = collect_all(SearchPager::new(
+        &client,
+        ENDPOINT,
+        SearchQuery::new().rows(2),
+    ));
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(app.server.requests_to(ENDPOINT).len(), 1);
+}
+
+#[test]
+fn query_is_sent_in_grid_format() {
+    let app = TestApp::new();
+    app.server
+        .respond("POST", ENDPOINT, 200, r#"{"rows":[],"total":0}"#);
+
+    let client = run(app.clients().client(&app.database(), app.profile())).unwrap();
+    let query = SearchQuery::new()
+        .page(2)
+        .rows(25)
+        .sort_by("name", SortOrder::Desc)
+        .search("web")
+        .filter("category", "servers");
+    run(SearchPager::<Value>::new(&client, ENDPOINT, query).next_page()).unwrap();
+
+    let body = app.server.requests_to(ENDPOINT)[0].json();
+    assert_eq!(body["current"], 2);
+    assert_eq!(body["rowCount"], 25);
+    assert_eq!(body["sort"]["name"], "desc");
+    assert_eq!(body["searchPhrase"], "web");
+    assert_eq!(body["category"], "servers");
+}
+
+#[test]
+fn alias_lookup_matches_exact_name() {
+    let app = TestApp::new();
+    app.server.respond(
+        "POST",
+        ENDPOINT,
+        200,
+        r#"{"rows":[
+            {"uuid":"a1","name":"webservers_v6","type":"host"},
+            {"uuid":"a2","name":"webservers","type":"host"}
+        ],"rowCount":2,"total":2,"current":1}"#,
+    );
+
+    let found = run(alias::get_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "webservers".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(found.uuid, "a2");
+    assert_eq!(
+        app.server.requests_to(ENDPOINT)[0].json()["searchPhrase"],
+        "webservers"
+    );
+}
+
+#[test]
+fn alias_search_fetches_only_the_requested_page() {
+    let app = TestApp::new();
+    app.server.respond(
+        "POST",
+        ENDPOINT,
+        200,
+        r#"{"rows":[{"uuid":"a3","name":"mailservers"}],"rowCount":1,"total":40,"current":3}"#,
+    );
+
+    let page = run(alias::search_alias_items(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        3,
+        1,
+        Some(BTreeMap::from([("name".to_string(), SortOrder::Desc)])),
+        "servers".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(page.rows.len(), 1);
+    assert_eq!(page.total, 40);
+    assert_eq!(page.current, 3);
+
+    let requests = app.server.requests_to(ENDPOINT);
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].json();
+    assert_eq!(body["current"], 3);
+    assert_eq!(body["rowCount"], 1);
+    assert_eq!(body["sort"]["name"], "desc");
+    assert_eq!(body["searchPhrase"], "servers");
+}
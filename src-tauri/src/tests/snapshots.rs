@@ -0,0 +1,26 @@
+use super::{run, TestApp};
+use crate::snapshots;
+
+#[test]
+fn snapshots_are_paged() {
+    let app = TestApp::new();
+    app.server
+        .fixture("POST", "/api/core/snapshots/search", "snapshots_search");
+
+    let result = run(snapshots::get_snapshots(
+        2,
+        25,
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(result.rows.len(), 1);
+    assert_eq!(result.rows[0].name, "default");
+    assert_eq!(result.rows[0].active, "NR");
+
+    let search = app.server.requests_to("/api/core/snapshots/search");
+    assert_eq!(search[0].json()["current"], 2);
+    assert_eq!(search[0].json()["rowCount"], 25);
+}
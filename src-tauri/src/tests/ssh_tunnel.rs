@@ -0,0 +1,213 @@
+use super::{run, TestApp};
+use crate::dashboard;
+use crate::db::Database;
+use crate::proxy::{ProxyKind, ProxySettings};
+use crate::ssh_tunnel::{SshTunnel, SshTunnelSettings};
+use async_trait::async_trait;
+use russh::server::{self, Auth};
+use russh_keys::key::KeyPair;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn password_tunnel(port: u16) -> SshTunnelSettings {
+    SshTunnelSettings {
+        host: "127.0.0.1".to_string(),
+        port,
+        username: "root".to_string(),
+        password: Some("ssh-pass".to_string().into()),
+        private_key: None,
+        passphrase: None,
+        remote_host: "127.0.0.1".to_string(),
+        host_key_fingerprint: None,
+    }
+}
+
+fn closed_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[test]
+fn unreachable_ssh_server_is_reported() {
+    let app = TestApp::new();
+
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    api_info.ssh_tunnel = Some(password_tunnel(closed_port()));
+    app.database().save_api_info(&api_info).unwrap();
+
+    let error = run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap_err()
+    .to_string();
+
+    assert!(
+        error.contains("SSH connection"),
+        "unexpected error: {}",
+        error
+    );
+    assert!(app.server.requests().is_empty());
+}
+
+#[test]
+fn tunnel_settings_are_persisted() {
+    let app = TestApp::new();
+
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    api_info.ssh_tunnel = Some(password_tunnel(2222));
+    app.database().save_api_info(&api_info).unwrap();
+
+    let saved = app.database().get_default_api_info().unwrap().unwrap();
+    assert_eq!(saved.ssh_tunnel, Some(password_tunnel(2222)));
+}
+
+#[test]
+fn proxy_and_tunnel_are_exclusive() {
+    let app = TestApp::new();
+
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    api_info.ssh_tunnel = Some(password_tunnel(22));
+    api_info.proxy = Some(ProxySettings {
+        kind: ProxyKind::Socks5,
+        host: "127.0.0.1".to_string(),
+        port: 1080,
+        username: None,
+        password: None,
+    });
+
+    assert!(app.clients().client_for(&api_info).is_err());
+}
+
+#[test]
+fn tunnel_needs_credentials() {
+    let mut settings = password_tunnel(22);
+    settings.password = None;
+    assert!(settings.validate().is_err());
+
+    settings.private_key = Some("not a key".to_string().into());
+    assert!(settings.validate().is_err());
+}
+
+#[test]
+fn tunnel_only_forwards_connections_with_its_secret() {
+    let tunnel = SshTunnel::start(&password_tunnel(closed_port()), 443).unwrap();
+    let address = format!("127.0.0.1:{}", tunnel.local_port());
+
+    run(async {
+        // Another local process guessing the password is turned away
+        let mut guess = TcpStream::connect(&address).await.unwrap();
+        guess.write_all(&[5, 1, 0x02]).await.unwrap();
+        let mut reply = [0u8; 2];
+        guess.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, 0x02]);
+        guess
+            .write_all(&[
+                1, 10, b'o', b'p', b'n', b'm', b'a', b'n', b'a', b'g', b'e', b'r',
+            ])
+            .await
+            .unwrap();
+        guess
+            .write_all(&[5, b'g', b'u', b'e', b's', b's'])
+            .await
+            .unwrap();
+        guess.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [1, 0x01]);
+
+        // So is one that skips the handshake altogether
+        let mut plain = TcpStream::connect(&address).await.unwrap();
+        plain
+            .write_all(b"GET / HTTP/1.1\r\nHost: firewall\r\n\r\n")
+            .await
+            .unwrap();
+        // The rest of the request is never read, so the close may arrive as a reset
+        let mut response = Vec::new();
+        let _ = plain.read_to_end(&mut response).await;
+        assert!(!response.starts_with(b"HTTP"));
+    });
+
+    // Neither got as far as opening the SSH session
+    assert_eq!(tunnel.last_error(), None);
+}
+
+/// SSH server that rejects every login, noting whether the host key was saved by then
+#[derive(Clone)]
+struct RejectingServer {
+    database: Database,
+    pinned_at_login: Arc<Mutex<Option<bool>>>,
+}
+
+#[async_trait]
+impl server::Handler for RejectingServer {
+    type Error = russh::Error;
+
+    async fn auth_password(&mut self, _user: &str, _password: &str) -> Result<Auth, Self::Error> {
+        let saved = self.database.get_default_api_info().unwrap().unwrap();
+        *self.pinned_at_login.lock().unwrap() =
+            Some(saved.ssh_tunnel.unwrap().host_key_fingerprint.is_some());
+        Ok(Auth::Reject {
+            proceed_with_methods: None,
+        })
+    }
+}
+
+#[test]
+fn host_key_is_saved_before_logging_in() {
+    let app = TestApp::new();
+    let host_key = KeyPair::generate_ed25519().unwrap();
+    let fingerprint = format!(
+        "SHA256:{}",
+        host_key.clone_public_key().unwrap().fingerprint()
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let config = Arc::new(server::Config {
+        keys: vec![host_key],
+        auth_rejection_time: Duration::ZERO,
+        ..Default::default()
+    });
+    let pinned_at_login = Arc::new(Mutex::new(None));
+    let handler = RejectingServer {
+        database: app.database().inner().clone(),
+        pinned_at_login: pinned_at_login.clone(),
+    };
+    tauri::async_runtime::spawn(async move {
+        let listener = TcpListener::from_std(listener).unwrap();
+        // Failed requests are retried, each on a new SSH connection
+        while let Ok((stream, _)) = listener.accept().await {
+            if let Ok(session) = server::run_stream(config.clone(), stream, handler.clone()).await {
+                tauri::async_runtime::spawn(session);
+            }
+        }
+    });
+
+    let mut api_info = app.database().get_default_api_info().unwrap().unwrap();
+    api_info.ssh_tunnel = Some(password_tunnel(port));
+    app.database().save_api_info(&api_info).unwrap();
+
+    let error = run(dashboard::get_gateway_status(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap_err()
+    .to_string();
+
+    assert!(
+        error.contains("authentication"),
+        "unexpected error: {}",
+        error
+    );
+    assert_eq!(*pinned_at_login.lock().unwrap(), Some(true));
+    let saved = app.database().get_default_api_info().unwrap().unwrap();
+    assert_eq!(
+        saved.ssh_tunnel.unwrap().host_key_fingerprint,
+        Some(fingerprint)
+    );
+    assert!(app.server.requests().is_empty());
+}
@@ -0,0 +1,20 @@
+use super::{run, to_json, TestApp};
+use crate::system_resources;
+
+#[test]
+fn disk_usage_is_parsed() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/diagnostics/system/systemDisk", "system_disk");
+
+    let disk = run(system_resources::get_system_disk(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+    let disk = to_json(disk);
+
+    assert_eq!(disk["devices"][0]["type"], "zfs");
+    assert_eq!(disk["devices"][0]["used_pct"], 4);
+}
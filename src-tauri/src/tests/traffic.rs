@@ -0,0 +1,23 @@
+use super::{run, TestApp};
+use crate::traffic;
+
+#[test]
+fn interface_traffic_is_parsed() {
+    let app = TestApp::new();
+    app.server.fixture(
+        "GET",
+        "/api/diagnostics/traffic/interface",
+        "interface_traffic",
+    );
+
+    let traffic = run(traffic::get_interface_traffic(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    let lan = &traffic.interfaces["lan"];
+    assert_eq!(lan.device, "igb1");
+    assert_eq!(lan.bytes_received, "1048576");
+}
@@ -0,0 +1,45 @@
+use super::{run, to_json, TestApp};
+use crate::tunables;
+
+#[test]
+fn tunables_are_searched() {
+    let app = TestApp::new();
+    app.server
+        .fixture("POST", "/api/core/tunables/search_item/", "tunables_search");
+
+    let result = to_json(
+        run(tunables::search_tunables(
+            app.profile(),
+            app.database(),
+            app.clients(),
+            1,
+            50,
+            "random".to_string(),
+        ))
+        .unwrap(),
+    );
+
+    assert_eq!(result["rows"][0]["tunable"], "net.inet.ip.random_id");
+    let search = app.server.requests_to("/api/core/tunables/search_item/");
+    assert_eq!(search[0].json()["searchPhrase"], "random");
+}
+
+#[test]
+fn old_firmware_gets_version_hint() {
+    let app = TestApp::new();
+
+    let error = run(tunables::search_tunables(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        1,
+        50,
+        String::new(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.kind(), "not_found");
+    assert!(error
+        .to_string()
+        .contains("Tunables API requires OPNsense 25.x or newer"));
+}
@@ -0,0 +1,18 @@
+use super::{run, TestApp};
+use crate::unbound;
+
+#[test]
+fn selected_blocklists_are_flattened() {
+    let app = TestApp::new();
+    app.server
+        .fixture("GET", "/api/unbound/settings/get", "unbound_settings");
+
+    let settings = run(unbound::get_unbound_settings(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(settings["unbound"]["dnsbl"]["active_types"], "atf,hgz002");
+}
@@ -0,0 +1,335 @@
+use super::{run, TestApp};
+use crate::change_journal::{self, Module};
+use crate::{alias, firewall, routes, undo};
+use serde_json::json;
+
+fn undo_last(app: &TestApp, module: Option<Module>) -> Result<String, String> {
+    run(undo::undo_last_change(
+        module,
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .map(|entry| entry.action)
+    .map_err(|e| e.to_string())
+}
+
+#[test]
+fn rule_edit_is_reverted_and_applied() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "GET",
+            "/api/firewall/filter/get_rule/abc",
+            200,
+            r#"{"rule":{"enabled":"1","description":"Allow web",
+                "interface":{"lan":{"value":"LAN","selected":1},"wan":{"value":"WAN","selected":0}},
+                "action":{"pass":{"value":"Pass","selected":1},"block":{"value":"Block","selected":0}}}}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/filter/set_rule/abc",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/filter/apply",
+            200,
+            r#"{"status":"OK"}"#,
+        );
+
+    run(firewall::set_rule(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "abc".to_string(),
+        json!({ "description": "Alow web" }),
+    ))
+    .unwrap();
+
+    assert_eq!(undo_last(&app, None).unwrap(), "set");
+
+    let saved = app.server.requests_to("/api/firewall/filter/set_rule/abc");
+    assert_eq!(saved.len(), 2);
+    assert_eq!(
+        saved[1].json(),
+        json!({ "rule": {
+            "enabled": "1",
+            "description": "Allow web",
+            "interface": "lan",
+            "action": "pass"
+        }})
+    );
+    assert_eq!(
+        app.server.requests_to("/api/firewall/filter/apply").len(),
+        2
+    );
+
+    let entries = change_journal::get_change_journal(None, app.database()).unwrap();
+    assert_eq!(entries[0].action, "undo");
+    assert!(entries[1].undone_at.is_some());
+
+    // The undo itself isn't undone, and there is nothing older
+    assert_eq!(
+        undo_last(&app, None).unwrap_err(),
+        "There is no change to undo"
+    );
+}
+
+#[test]
+fn removed_alias_address_is_put_back() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "GET",
+            "/api/firewall/alias/getItem/abc",
+            200,
+            r#"{"alias":{"name":"blocklist",
+                "type":{"host":{"value":"Host(s)","selected":1},"network":{"value":"Network(s)","selected":0}},
+                "content":{"192.0.2.1":{"value":"192.0.2.1","selected":1},"192.0.2.2":{"value":"192.0.2.2","selected":1}}}}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/setItem/abc",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/set",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    run(alias::remove_ip_from_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "abc".to_string(),
+        "192.0.2.1".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(undo_last(&app, Some(Module::Alias)).unwrap(), "remove_ip");
+
+    let saved = app.server.requests_to("/api/firewall/alias/setItem/abc");
+    let restored = &saved[1].json()["alias"];
+    assert_eq!(restored["content"], "192.0.2.1\n192.0.2.2");
+    assert_eq!(restored["type"], "host");
+    assert_eq!(
+        app.server
+            .requests_to("/api/firewall/alias/reconfigure")
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn deleted_route_is_added_back() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "GET",
+            "/api/routes/routes/getroute/r1",
+            200,
+            r#"{"route":{"network":"10.30.0.0/16","descr":"Site C","disabled":"0",
+                "gateway":{"VPN_GW":{"value":"VPN_GW - 10.0.0.1","selected":1}}}}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/delroute/r1",
+            200,
+            r#"{"result":"deleted"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/addroute",
+            200,
+            r#"{"result":"saved","uuid":"r2"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    run(routes::delete_route(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "r1".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(undo_last(&app, Some(Module::Routes)).unwrap(), "delete");
+
+    let added = app.server.requests_to("/api/routes/routes/addroute");
+    assert_eq!(added[0].json()["route"]["gateway"], "VPN_GW");
+    assert_eq!(added[0].json()["route"]["network"], "10.30.0.0/16");
+
+    let entries = change_journal::get_change_journal(None, app.database()).unwrap();
+    assert_eq!(entries[0].target_uuid.as_deref(), Some("r2"));
+}
+
+#[test]
+fn only_edits_are_undone() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "POST",
+            "/api/firewall/alias/addItem/",
+            200,
+            r#"{"result":"saved","uuid":"f00d"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/set",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    run(alias::add_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "blocklist".to_string(),
+        "host".to_string(),
+        "192.0.2.1".to_string(),
+        String::new(),
+        true,
+    ))
+    .unwrap();
+
+    assert_eq!(
+        undo_last(&app, Some(Module::Alias)).unwrap_err(),
+        "There is no change to undo"
+    );
+    let error = undo_last(&app, Some(Module::Wol)).unwrap_err();
+    assert_eq!(error, "Changes to wol can't be undone");
+    assert!(app
+        .server
+        .requests_to("/api/firewall/alias/setItem/f00d")
+        .is_empty());
+}
+
+#[test]
+fn newer_adds_dont_block_older_edits() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "GET",
+            "/api/firewall/alias/getItem/abc",
+            200,
+            r#"{"alias":{"name":"blocklist",
+                "content":{"192.0.2.1":{"value":"192.0.2.1","selected":1}}}}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/setItem/abc",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/addItem/",
+            200,
+            r#"{"result":"saved","uuid":"f00d"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/set",
+            200,
+            r#"{"result":"saved"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/firewall/alias/reconfigure",
+            200,
+            r#"{"status":"ok"}"#,
+        );
+
+    run(alias::remove_ip_from_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "abc".to_string(),
+        "192.0.2.1".to_string(),
+    ))
+    .unwrap();
+    run(alias::add_alias(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "allowlist".to_string(),
+        "host".to_string(),
+        "192.0.2.9".to_string(),
+        String::new(),
+        true,
+    ))
+    .unwrap();
+
+    assert_eq!(undo_last(&app, Some(Module::Alias)).unwrap(), "remove_ip");
+    assert_eq!(
+        undo_last(&app, Some(Module::Alias)).unwrap_err(),
+        "There is no change to undo"
+    );
+}
+
+#[test]
+fn failed_apply_leaves_the_change_undoable() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "GET",
+            "/api/routes/routes/getroute/r1",
+            200,
+            r#"{"route":{"network":"10.30.0.0/16","disabled":"0"}}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/delroute/r1",
+            200,
+            r#"{"result":"deleted"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/addroute",
+            200,
+            r#"{"result":"saved","uuid":"r2"}"#,
+        )
+        .respond(
+            "POST",
+            "/api/routes/routes/reconfigure",
+            500,
+            r#"{"status":"failed"}"#,
+        );
+
+    run(routes::delete_route(
+        app.profile(),
+        app.database(),
+        app.clients(),
+        "r1".to_string(),
+    ))
+    .ok();
+
+    assert!(undo_last(&app, Some(Module::Routes)).is_err());
+
+    let entries = change_journal::get_change_journal(None, app.database()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, "delete");
+    assert!(entries[0].undone_at.is_none());
+}
@@ -0,0 +1,34 @@
+use super::{run, TestApp};
+use crate::update_checker;
+
+#[test]
+fn minor_upgrade_is_detected() {
+    let app = TestApp::new();
+    app.server
+        .respond(
+            "POST",
+            "/api/core/firmware/check",
+            200,
+            r#"{"status":"ok"}"#,
+        )
+        .respond(
+            "GET",
+            "/api/core/firmware/upgradestatus",
+            200,
+            r#"{"status":"done"}"#,
+        )
+        .fixture("GET", "/api/core/firmware/status", "firmware_status_minor")
+        .fixture("GET", "/api/core/firmware/info", "firmware_info");
+
+    let result = run(update_checker::check_for_updates(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(result["latest_version"], "25.1.3");
+    assert_eq!(result["has_major_upgrade"], false);
+    assert_eq!(result["has_minor_upgrade"], true);
+    assert_eq!(result["target_version"], "25.1.3");
+}
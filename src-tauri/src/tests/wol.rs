@@ -0,0 +1,55 @@
+use super::{run, to_json, TestApp};
+use crate::wol;
+
+#[test]
+fn missing_plugin_is_reported_as_not_installed() {
+    let app = TestApp::new();
+
+    let status = run(wol::check_wol_plugin_installed(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(status["installed"], false);
+    assert_eq!(status["permission_error"], false);
+}
+
+#[test]
+fn forbidden_plugin_is_reported_as_permission_error() {
+    let app = TestApp::new();
+    app.server.respond("GET", "/api/wol/wol/getwake", 403, "{}");
+
+    let status = run(wol::check_wol_plugin_installed(
+        app.profile(),
+        app.database(),
+        app.clients(),
+    ))
+    .unwrap();
+
+    assert_eq!(status["installed"], true);
+    assert_eq!(status["permission_error"], true);
+}
+
+#[test]
+fn hosts_are_searched() {
+    let app = TestApp::new();
+    app.server
+        .fixture("POST", "/api/wol/wol/searchHost", "wol_hosts");
+
+    let hosts = to_json(
+        run(wol::search_wol_hosts(
+            app.profile(),
+            app.database(),
+            app.clients(),
+            1,
+            50,
+            None,
+            String::new(),
+        ))
+        .unwrap(),
+    );
+
+    assert_eq!(hosts["rows"][0]["descr"], "NAS");
+}
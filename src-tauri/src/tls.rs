@@ -0,0 +1,173 @@
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+use crate::db::ApiInfo;
+
+/// SHA-256 fingerprint of a DER certificate as colon separated uppercase hex,
+/// the same format OPNsense shows under System > Trust > Certificates
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+pub fn fingerprints_match(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// Trust-on-first-use verifier for the self-signed certificates OPNsense ships with
+///
+/// With no pinned fingerprint every certificate is accepted and recorded so the caller
+/// can store it. Once pinned, any other certificate fails the handshake.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    pinned: Option<String>,
+    observed: Arc<Mutex<Option<String>>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = fingerprint(end_entity.as_ref());
+        *self.observed.lock().unwrap() = Some(presented.clone());
+
+        match &self.pinned {
+            Some(pinned) if !fingerprints_match(pinned, &presented) => Err(rustls::Error::General(
+                "Certificate fingerprint mismatch".to_string(),
+            )),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// TLS settings for one firewall profile
+pub struct TlsSettings {
+    pub config: ClientConfig,
+    /// Fingerprint of the last certificate the server presented, only tracked when pinning
+    pub observed: Arc<Mutex<Option<String>>>,
+}
+
+fn parse_certificates(pem: &str, label: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_slice_iter(pem.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid {}: {}", label, e))?;
+
+    if certs.is_empty() {
+        return Err(format!("Invalid {}: no PEM certificates found", label));
+    }
+
+    Ok(certs)
+}
+
+/// Builds the TLS config for a profile
+///
+/// A configured CA chain switches the profile to strict WebPKI verification (including
+/// hostname checks) against that chain; otherwise the pinned fingerprint is enforced.
+/// A client certificate and key are presented to servers that request one.
+pub fn build_tls_settings(api_info: &ApiInfo) -> Result<TlsSettings, String> {
+    let provider = Arc::new(ring::default_provider());
+    let observed = Arc::new(Mutex::new(None));
+
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to configure TLS: {}", e))?;
+
+    let builder = match api_info.ca_chain_pem.as_deref() {
+        Some(ca_chain) => {
+            let mut roots = RootCertStore::empty();
+            for cert in parse_certificates(ca_chain, "CA chain")? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid CA certificate: {}", e))?;
+            }
+            builder.with_root_certificates(roots)
+        }
+        None => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+                pinned: api_info.cert_fingerprint.clone(),
+                observed: observed.clone(),
+                provider,
+            })),
+    };
+
+    let config = match (
+        api_info.client_cert_pem.as_deref(),
+        api_info.client_key_pem.as_deref().map(String::as_str),
+    ) {
+        (Some(cert), Some(key)) => {
+            let certs = parse_certificates(cert, "client certificate")?;
+            let key = PrivateKeyDer::from_pem_slice(key.as_bytes())
+                .map_err(|e| format!("Invalid client key: {}", e))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| format!("Client certificate and key don't match: {}", e))?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err("A client certificate requires both a certificate and a key".to_string()),
+    };
+
+    Ok(TlsSettings { config, observed })
+}
+
+/// Digest of the CA and client certificate settings, used to notice when they change
+pub fn settings_digest(api_info: &ApiInfo) -> String {
+    let mut hasher = Sha256::new();
+    for value in [
+        api_info.ca_chain_pem.as_deref(),
+        api_info.client_cert_pem.as_deref(),
+        api_info.client_key_pem.as_deref().map(String::as_str),
+    ] {
+        hasher.update(value.unwrap_or("").as_bytes());
+        hasher.update([0u8]);
+    }
+    fingerprint(&hasher.finalize())
+}
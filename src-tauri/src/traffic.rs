@@ -1,11 +1,15 @@
+use crate::active_profile::SelectedProfile;
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ApiRequest, ClientManager, OpnsenseClient};
+use crate::rate_limit::Priority;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Mutex;
 use tauri::{Manager, State};
 
 const MAX_DATA_POINTS: usize = 120;
+const TRAFFIC_ENDPOINT: &str = "/api/diagnostics/traffic/interface";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InterfaceTraffic {
@@ -165,48 +169,44 @@ impl TrafficCache {
 
 #[tauri::command]
 pub async fn get_interface_traffic(
+    profile: SelectedProfile,
     database: State<'_, Database>,
-) -> Result<InterfaceTraffic, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/traffic/interface",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    clients: State<'_, ClientManager>,
+) -> Result<InterfaceTraffic, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    fetch_interface_traffic(&client, ApiRequest::get(TRAFFIC_ENDPOINT)).await
+}
+
+async fn fetch_interface_traffic(
+    client: &OpnsenseClient,
+    request: ApiRequest,
+) -> Result<InterfaceTraffic, CommandError> {
+    let response = client.send(request).await?;
 
     response
         .json::<InterfaceTraffic>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 #[tauri::command]
 pub fn get_traffic_graph_data(
     traffic_cache: State<'_, TrafficCache>,
-) -> Result<Vec<TrafficDataPoint>, String> {
+) -> Result<Vec<TrafficDataPoint>, CommandError> {
     Ok(traffic_cache.get_data_points())
 }
 
 #[tauri::command]
 pub async fn update_traffic_data(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     traffic_cache: State<'_, TrafficCache>,
-) -> Result<(), String> {
-    let traffic = get_interface_traffic(database).await?;
+) -> Result<(), CommandError> {
+    // Polled every second by the traffic graph, so it yields to user actions
+    let client = clients.client(&database, profile).await?;
+    let request = ApiRequest::get(TRAFFIC_ENDPOINT).priority(Priority::Background);
+    let traffic = fetch_interface_traffic(&client, request).await?;
     traffic_cache.add_data_point(&traffic);
     Ok(())
 }
@@ -218,7 +218,7 @@ pub fn register_traffic_cache(app: &mut tauri::App) -> Result<(), Box<dyn std::e
 }
 
 #[tauri::command]
-pub fn clear_traffic_cache(traffic_cache: State<'_, TrafficCache>) -> Result<(), String> {
+pub fn clear_traffic_cache(traffic_cache: State<'_, TrafficCache>) -> Result<(), CommandError> {
     traffic_cache.clear();
     Ok(())
 }
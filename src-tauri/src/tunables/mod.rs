@@ -1,95 +1,140 @@
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
 use crate::db::Database;
-use crate::http_client::make_http_request;
-use serde_json::{json, Value};
+use crate::error::CommandError;
+use crate::http_client::{ApiError, ApiRequest, ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::search_grid::{self, SearchPage, SearchQuery};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
 use tauri::State;
 
-#[tauri::command]
-pub async fn search_tunables(
-    database: State<'_, Database>,
-    current_page: u32,
-    row_count: u32,
-    search_phrase: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+const UNSUPPORTED: &str = "Tunables API requires OPNsense 25.x or newer";
+
+/// Row of the tunables grid; fields the app doesn't use are passed through untouched
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tunable {
+    pub uuid: String,
+    pub tunable: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub descr: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
 
-    let url = format!(
-        "{}:{}/api/core/tunables/search_item/",
-        api_info.api_url, api_info.port
-    );
+/// Client for the selected profile, refusing firmware known to predate the tunables API
+async fn tunables_client(
+    profile: SelectedProfile,
+    database: &Database,
+    clients: &ClientManager,
+) -> Result<Arc<OpnsenseClient>, CommandError> {
+    let client = clients.client(database, profile).await?;
+
+    let capabilities = clients.capabilities(&client).await?;
+    if !capabilities.tunables {
+        return Err(format!(
+            "{} (this firewall runs {})",
+            UNSUPPORTED,
+            capabilities
+                .firmware_version
+                .as_deref()
+                .unwrap_or("an older release")
+        )
+        .into());
+    }
 
-    let payload = json!({
-        "current": current_page,
-        "rowCount": row_count,
-        "sort": {},
-        "searchPhrase": search_phrase
-    });
+    Ok(client)
+}
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+/// A 404 from an unknown firmware version means the API isn't there yet
+fn explain(error: ApiError) -> ApiError {
+    match error {
+        ApiError::NotFound { endpoint, .. } => ApiError::NotFound {
+            endpoint,
+            hint: UNSUPPORTED.to_string(),
+        },
+        e => e,
+    }
+}
+
+async fn send(client: &OpnsenseClient, request: ApiRequest) -> Result<Value, CommandError> {
+    let response = client.send(request).await.map_err(explain)?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
+}
+
+async fn tunables_request(
+    profile: SelectedProfile,
+    database: &Database,
+    clients: &ClientManager,
+    request: ApiRequest,
+) -> Result<Value, CommandError> {
+    let client = tunables_client(profile, database, clients).await?;
+
+    send(&client, request).await
+}
+
+async fn get_item(client: &OpnsenseClient, uuid: &str) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/core/tunables/get_item/{}", uuid);
+
+    send(client, ApiRequest::get(endpoint)).await
 }
 
 #[tauri::command]
-pub async fn get_tunable(database: State<'_, Database>, uuid: String) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/tunables/get_item/{}",
-        api_info.api_url, api_info.port, uuid
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
+pub async fn search_tunables(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    current_page: u32,
+    row_count: u32,
+    search_phrase: String,
+) -> Result<SearchPage<Tunable>, CommandError> {
+    let client = tunables_client(profile, &database, &clients).await?;
+
+    let query = SearchQuery::new()
+        .page(current_page)
+        .rows(row_count)
+        .search(search_phrase);
+
+    Ok(
+        search_grid::search(&client, "/api/core/tunables/search_item/", &query)
+            .await
+            .map_err(explain)?,
     )
-    .await?;
+}
 
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+#[tauri::command]
+pub async fn get_tunable(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/core/tunables/get_item/{}", uuid);
+
+    tunables_request(profile, &database, &clients, ApiRequest::get(endpoint)).await
 }
 
 #[tauri::command]
 pub async fn set_tunable(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
     tunable: String,
     value: String,
     description: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/tunables/set_item/{}",
-        api_info.api_url, api_info.port, uuid
-    );
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let client = tunables_client(profile, &database, &clients).await?;
+    let endpoint = format!("/api/core/tunables/set_item/{}", uuid);
 
     let payload = json!({
         "sysctl": {
@@ -99,69 +144,66 @@ pub async fn set_tunable(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let before = get_item(&client, &uuid).await.ok();
+    let result = send(&client, ApiRequest::post(endpoint, payload)).await?;
 
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+    if result["result"].as_str() == Some("saved") {
+        Change::new(Module::Tunables, "set")
+            .target(&uuid)
+            .before(before)
+            .after(get_item(&client, &uuid).await.ok())
+            .record(&database, &client);
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
-pub async fn apply_tunables(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/tunables/reconfigure",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
+pub async fn apply_tunables(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    tunables_request(
+        profile,
+        &database,
+        &clients,
+        ApiRequest::post("/api/core/tunables/reconfigure", json!({})),
     )
-    .await?;
-
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+    .await
 }
 
-
 #[tauri::command]
 pub async fn save_and_apply_tunable(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
     tunable: String,
     value: String,
     description: String,
-) -> Result<Value, String> {
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
     // First set the tunable
-    let set_result = set_tunable(database.clone(), uuid, tunable, value, description).await?;
+    let set_result = set_tunable(
+        profile,
+        database.clone(),
+        clients.clone(),
+        uuid,
+        tunable,
+        value,
+        description,
+    )
+    .await?;
 
     // Check if save was successful
     if let Some(result) = set_result.get("result") {
         if result.as_str() == Some("saved") {
             // Then apply changes
-            let apply_result = apply_tunables(database).await?;
+            let apply_result = apply_tunables(profile, database, clients).await?;
 
             // Return combined result
             return Ok(json!({
@@ -180,21 +222,16 @@ pub async fn save_and_apply_tunable(
 
 #[tauri::command]
 pub async fn add_tunable(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     tunable: String,
     value: String,
     description: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/tunables/add_item/",
-        api_info.api_url, api_info.port
-    );
-
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let client = tunables_client(profile, &database, &clients).await?;
     let payload = json!({
         "sysctl": {
             "tunable": tunable,
@@ -203,49 +240,46 @@ pub async fn add_tunable(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
+    let result = send(
+        &client,
+        ApiRequest::post("/api/core/tunables/add_item/", payload),
     )
     .await?;
 
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
-}
+    if result["result"].as_str() == Some("saved") {
+        let mut change = Change::new(Module::Tunables, "add");
+        if let Some(uuid) = result["uuid"].as_str() {
+            change = change
+                .target(uuid)
+                .after(get_item(&client, uuid).await.ok());
+        }
+        change.record(&database, &client);
+    }
 
+    Ok(result)
+}
 
 #[tauri::command]
-pub async fn delete_tunable(database: State<'_, Database>, uuid: String) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/core/tunables/del_item/{}",
-        api_info.api_url, api_info.port, uuid
-    );
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn delete_tunable(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let client = tunables_client(profile, &database, &clients).await?;
+    let endpoint = format!("/api/core/tunables/del_item/{}", uuid);
+
+    let before = get_item(&client, &uuid).await.ok();
+    let result = send(&client, ApiRequest::post(endpoint, json!({}))).await?;
+
+    if result["result"].as_str() == Some("deleted") {
+        Change::new(Module::Tunables, "delete")
+            .target(&uuid)
+            .before(before)
+            .record(&database, &client);
+    }
 
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+    Ok(result)
 }
@@ -1,46 +1,13 @@
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ClientManager, OpnsenseClient};
+use crate::local_users::Role;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tauri::State;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct UnboundSettings {
-    unbound: UnboundConfig,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct UnboundConfig {
-    general: Option<Value>,
-    advanced: Option<Value>,
-    acls: Option<Value>,
-    dnsbl: Option<DnsblConfig>,
-    forwarding: Option<Value>,
-    dots: Option<Value>,
-    hosts: Option<Value>,
-    aliases: Option<Value>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DnsblConfig {
-    enabled: String,
-    safesearch: String,
-    #[serde(rename = "type")]
-    blocklist_type: Option<String>,
-    lists: Option<Vec<String>>,
-    whitelists: Option<Vec<String>>,
-    blocklists: Option<Vec<String>>,
-    wildcards: Option<Vec<String>>,
-    address: Option<String>,
-    nxdomain: String,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DnsblTypeOption {
-    value: String,
-    selected: u8,
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CronJob {
     uuid: Option<String>,
@@ -64,29 +31,15 @@ pub struct CronJobsResponse {
     current: u32,
 }
 
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
-
 #[tauri::command]
-pub async fn get_unbound_settings(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/unbound/settings/get");
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_unbound_settings(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/unbound/settings/get").await?;
 
     let response_text = response
         .text()
@@ -105,7 +58,8 @@ pub async fn get_unbound_settings(database: State<'_, Database>) -> Result<Value
                 "Failed to parse response: {} - Response was: {}",
                 e,
                 &response_text.chars().take(500).collect::<String>()
-            ))
+            )
+            .into())
         }
     };
 
@@ -155,10 +109,11 @@ pub async fn get_unbound_settings(database: State<'_, Database>) -> Result<Value
     Ok(parsed)
 }
 
-#[warn(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn set_dnsbl_settings(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     enabled: bool,
     safesearch: bool,
     blocklist_types: Vec<String>,
@@ -168,13 +123,10 @@ pub async fn set_dnsbl_settings(
     wildcards: Vec<String>,
     address: String,
     nxdomain: bool,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/unbound/settings/set");
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     // Build the DNSBL config payload
     let dnsbl_config = json!({
@@ -200,16 +152,9 @@ pub async fn set_dnsbl_settings(
         serde_json::to_string(&payload).unwrap_or_default()
     );
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let before = get_dnsbl_config(&client).await.ok();
+
+    let response = client.post("/api/unbound/settings/set", payload).await?;
 
     let response_text = response
         .text()
@@ -218,51 +163,62 @@ pub async fn set_dnsbl_settings(
 
     log::info!("DNSBL set response: {}", response_text);
 
-    match serde_json::from_str::<Value>(&response_text) {
-        Ok(json_value) => Ok(json_value),
-        Err(e) => Err(format!(
+    let result = serde_json::from_str::<Value>(&response_text).map_err(|e| {
+        format!(
             "Failed to parse response: {} - Response was: {}",
             e, response_text
-        )),
+        )
+    })?;
+
+    if result["result"].as_str() == Some("saved") {
+        Change::new(Module::Unbound, "set_dnsbl")
+            .before(before)
+            .after(get_dnsbl_config(&client).await.ok())
+            .record(&database, &client);
     }
+
+    Ok(result)
+}
+
+/// The DNSBL section of the Unbound settings, as stored on the firewall
+async fn get_dnsbl_config(client: &OpnsenseClient) -> Result<Value, CommandError> {
+    let response = client.get("/api/unbound/settings/get").await?;
+
+    let settings = response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    Ok(settings["unbound"]["dnsbl"].clone())
 }
 
 #[tauri::command]
-pub async fn apply_dnsbl_settings(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/unbound/service/dnsbl");
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn apply_dnsbl_settings(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.post("/api/unbound/service/dnsbl", json!({})).await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 const UNBOUND_DNSBL_CRON_DESCRIPTION: &str = "OPNManager Unbound DNSBL Update";
 
 #[tauri::command]
-pub async fn get_dnsbl_cron_job(database: State<'_, Database>) -> Result<Option<CronJob>, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/cron/settings/searchJobs");
+pub async fn get_dnsbl_cron_job(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Option<CronJob>, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
     let payload = json!({
         "current": 1,
@@ -271,16 +227,9 @@ pub async fn get_dnsbl_cron_job(database: State<'_, Database>) -> Result<Option<
         "searchPhrase": UNBOUND_DNSBL_CRON_DESCRIPTION
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .post("/api/cron/settings/searchJobs", payload)
+        .await?;
 
     let jobs_response = response
         .json::<CronJobsResponse>()
@@ -298,27 +247,26 @@ pub async fn get_dnsbl_cron_job(database: State<'_, Database>) -> Result<Option<
 
 #[tauri::command]
 pub async fn add_dnsbl_cron_job(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     minutes: String,
     hours: String,
     days: String,
     months: String,
     weekdays: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
-    let existing_job = get_dnsbl_cron_job(database.clone()).await?;
+    let existing_job = get_dnsbl_cron_job(profile, database.clone(), clients.clone()).await?;
     if let Some(job) = existing_job {
         if let Some(uuid) = job.uuid {
-            delete_dnsbl_cron_job(database.clone(), uuid).await?;
+            delete_dnsbl_cron_job(profile, database.clone(), clients.clone(), uuid).await?;
         }
     }
 
-    let url = build_api_url(&api_info, "/api/cron/settings/addJob/");
-
     let payload = json!({
         "job": {
             "enabled": "1",
@@ -333,82 +281,89 @@ pub async fn add_dnsbl_cron_job(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post("/api/cron/settings/addJob/", payload).await?;
 
     let result = response
         .json::<Value>()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-    apply_cron_changes(database).await?;
+    if result["result"].as_str() == Some("saved") {
+        let mut change = Change::new(Module::Unbound, "add_cron_job");
+        if let Some(uuid) = result["uuid"].as_str() {
+            change = change
+                .target(uuid)
+                .after(get_cron_job(&client, uuid).await.ok());
+        }
+        change.record(&database, &client);
+    }
+
+    apply_cron_changes(profile, database, clients).await?;
 
     Ok(result)
 }
 
 #[tauri::command]
 pub async fn delete_dnsbl_cron_job(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     uuid: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, &format!("/api/cron/settings/delJob/{}", uuid));
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/cron/settings/delJob/{}", uuid);
+
+    let before = get_cron_job(&client, &uuid).await.ok();
+
+    let response = client.post(&endpoint, json!({})).await?;
 
     let result = response
         .json::<Value>()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-    apply_cron_changes(database).await?;
+    if result["result"].as_str() == Some("deleted") {
+        Change::new(Module::Unbound, "delete_cron_job")
+            .target(&uuid)
+            .before(before)
+            .record(&database, &client);
+    }
+
+    apply_cron_changes(profile, database, clients).await?;
 
     Ok(result)
 }
 
+async fn get_cron_job(client: &OpnsenseClient, uuid: &str) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/cron/settings/getJob/{}", uuid);
+
+    let response = client.get(&endpoint).await?;
+
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
+}
+
 #[tauri::command]
-pub async fn apply_cron_changes(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = build_api_url(&api_info, "/api/cron/service/reconfigure");
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn apply_cron_changes(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .post("/api/cron/service/reconfigure", json!({}))
+        .await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
@@ -0,0 +1,233 @@
+use log::{error, info};
+use serde_json::{json, Map, Value};
+use tauri::State;
+
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, ChangeEntry, Module, UNDO_ACTION};
+use crate::db::Database;
+use crate::error::CommandError;
+use crate::http_client::ClientManager;
+use crate::local_users::Role;
+use crate::{alias, firewall, routes, tunables};
+
+/// Modules whose edits can be reverted from their journaled before-state
+const UNDOABLE_MODULES: &[Module] = &[
+    Module::Alias,
+    Module::Firewall,
+    Module::Routes,
+    Module::Tunables,
+];
+
+/// Where a module saves its items
+struct ItemApi {
+    /// Key the item sits under in both the getter's response and the setter's payload
+    root: &'static str,
+    /// Followed by the item's uuid
+    set: &'static str,
+    add: &'static str,
+    /// Multi-value fields the setter expects one per line instead of comma separated
+    line_fields: &'static [&'static str],
+}
+
+fn item_api(module: Module) -> Option<ItemApi> {
+    match module {
+        Module::Alias => Some(ItemApi {
+            root: "alias",
+            set: "/api/firewall/alias/setItem/",
+            add: "/api/firewall/alias/addItem/",
+            line_fields: &["content"],
+        }),
+        Module::Firewall => Some(ItemApi {
+            root: "rule",
+            set: "/api/firewall/filter/set_rule/",
+            add: "/api/firewall/filter/add_rule/",
+            line_fields: &[],
+        }),
+        Module::Routes => Some(ItemApi {
+            root: "route",
+            set: "/api/routes/routes/setroute/",
+            add: "/api/routes/routes/addroute",
+            line_fields: &[],
+        }),
+        Module::Tunables => Some(ItemApi {
+            root: "sysctl",
+            set: "/api/core/tunables/set_item/",
+            add: "/api/core/tunables/add_item/",
+            line_fields: &[],
+        }),
+        _ => None,
+    }
+}
+
+/// Whether the journaled action can be reverted by saving its before-state again
+fn is_undoable(entry: &ChangeEntry) -> bool {
+    matches!(
+        (entry.module, entry.action.as_str()),
+        (Module::Alias, "add_ip" | "remove_ip")
+            | (Module::Firewall, "set" | "toggle" | "delete")
+            | (Module::Routes, "toggle" | "delete")
+            | (Module::Tunables, "set")
+    )
+}
+
+fn is_selected(option: &Value) -> bool {
+    match &option["selected"] {
+        Value::Bool(selected) => *selected,
+        Value::Number(selected) => selected.as_u64() == Some(1),
+        Value::String(selected) => selected == "1",
+        _ => false,
+    }
+}
+
+/// `{"key": {"value": ..., "selected": 1}, ...}`, how getters describe a choice
+fn is_option_list(options: &Map<String, Value>) -> bool {
+    options
+        .values()
+        .all(|option| option.is_object() && option.get("selected").is_some())
+}
+
+/// Turns an item as a getter returns it back into the form its setter accepts
+///
+/// Getters expand every choice into its options with a `selected` flag, while
+/// setters take the selected keys as a single string.
+fn to_form(item: &Map<String, Value>, line_fields: &[&str]) -> Value {
+    let form = item
+        .iter()
+        .map(|(field, value)| {
+            let value = match value {
+                Value::Object(options) if is_option_list(options) => {
+                    let separator = if line_fields.contains(&field.as_str()) {
+                        "\n"
+                    } else {
+                        ","
+                    };
+                    let selected: Vec<&str> = options
+                        .iter()
+                        .filter(|(_, option)| is_selected(option))
+                        .map(|(key, _)| key.as_str())
+                        .collect();
+                    Value::String(selected.join(separator))
+                }
+                Value::Object(nested) => to_form(nested, line_fields),
+                other => other.clone(),
+            };
+            (field.clone(), value)
+        })
+        .collect();
+    Value::Object(form)
+}
+
+async fn apply(
+    module: Module,
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<(), CommandError> {
+    match module {
+        Module::Alias => alias::apply_alias_changes(profile, database, clients)
+            .await
+            .map(|_| ()),
+        Module::Firewall => firewall::apply_firewall_changes(profile, database, clients)
+            .await
+            .map(|_| ()),
+        Module::Routes => routes::apply_changes(profile, database, clients)
+            .await
+            .map(|_| ()),
+        Module::Tunables => tunables::apply_tunables(profile, database, clients)
+            .await
+            .map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+/// Reverts the newest journaled change on the profile, or the newest to `module`
+///
+/// Edits are reverted by saving the item as it was before, deleted items are added
+/// back (under a new uuid), and the module's changes are applied. Changes that
+/// can't be reverted, like adds, are skipped. The revert is journaled as an
+/// `undo` itself, and the next call reverts the change before.
+#[tauri::command]
+pub async fn undo_last_change(
+    module: Option<Module>,
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<ChangeEntry, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let modules = match module {
+        Some(module) if UNDOABLE_MODULES.contains(&module) => vec![module],
+        Some(module) => return Err(format!("Changes to {} can't be undone", module).into()),
+        None => UNDOABLE_MODULES.to_vec(),
+    };
+
+    let journal = database.change_journal();
+    // Adds, toggles of aliases and the like are skipped rather than blocking the
+    // edits made before them
+    let mut entry = journal
+        .last_undoable(client.profile_id(), |entry| {
+            modules.contains(&entry.module) && is_undoable(entry)
+        })
+        .map_err(|e| format!("Failed to read change journal: {}", e))?
+        .ok_or_else(|| "There is no change to undo".to_string())?;
+
+    if entry.module == Module::Tunables {
+        database.require_role(Role::Admin)?;
+    }
+
+    let api = item_api(entry.module)
+        .ok_or_else(|| format!("Changes to {} can't be undone", entry.module))?;
+    let item = entry
+        .before
+        .as_ref()
+        .and_then(|before| before[api.root].as_object())
+        .ok_or_else(|| {
+            "The firewall didn't return the item before this change, so it can't be undone"
+                .to_string()
+        })?;
+    let payload = json!({ api.root: to_form(item, api.line_fields) });
+
+    let endpoint = match (&entry.target_uuid, entry.action.as_str()) {
+        (_, "delete") => api.add.to_string(),
+        (Some(uuid), _) => format!("{}{}", api.set, uuid),
+        (None, _) => return Err("This change doesn't say which item it made".into()),
+    };
+
+    let response = client.post(&endpoint, payload).await?;
+    let result = response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+    if result["result"].as_str() != Some("saved") {
+        return Err(format!("The firewall refused to undo the change: {}", result).into());
+    }
+
+    // Applied before the journal says so, so a failed apply leaves the change undoable
+    apply(entry.module, profile, database.clone(), clients).await?;
+
+    info!(
+        "Undid {} {} of {:?} on profile {}",
+        entry.module, entry.action, entry.target_uuid, entry.profile_name
+    );
+    let undone_at = chrono::Utc::now().to_rfc3339();
+    if let Err(e) = journal.mark_undone(entry.id, &undone_at) {
+        error!("Failed to mark change {} as undone: {}", entry.id, e);
+    }
+    entry.undone_at = Some(undone_at);
+
+    let target_uuid = result["uuid"]
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| entry.target_uuid.clone());
+    let mut undo = Change::new(entry.module, UNDO_ACTION)
+        .before(entry.after.clone())
+        .after(entry.before.clone());
+    if let Some(uuid) = target_uuid {
+        undo = undo.target(uuid);
+    }
+    undo.record(&database, &client);
+
+    Ok(entry)
+}
@@ -1,32 +1,26 @@
+use crate::active_profile::SelectedProfile;
+use crate::confirmation::{Confirmations, DestructiveAction};
 use crate::db::Database;
-use crate::http_client::make_http_request;
+use crate::error::CommandError;
+use crate::http_client::{ApiRequest, ClientManager, OpnsenseClient};
+use crate::local_users::Role;
 use serde_json::Value;
 use std::time::{Duration, Instant};
 use tauri::State;
 use tokio::time::sleep;
-
-fn build_api_url(api_info: &crate::db::ApiInfo, endpoint: &str) -> String {
-    format!("{}:{}{}", api_info.api_url, api_info.port, endpoint)
-}
+use zeroize::Zeroizing;
 
 #[tauri::command]
-pub async fn check_for_updates(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let check_url = build_api_url(&api_info, "/api/core/firmware/check");
-    let check_response = make_http_request(
-        "POST",
-        &check_url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn check_for_updates(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let check_response = client
+        .post("/api/core/firmware/check", serde_json::json!({}))
+        .await?;
 
     let check_body: Value = check_response
         .json()
@@ -34,22 +28,12 @@ pub async fn check_for_updates(database: State<'_, Database>) -> Result<Value, S
         .map_err(|e| format!("Failed to parse check response: {}", e))?;
 
     if check_body["status"] != "ok" {
-        return Err(format!("Check failed: {:?}", check_body));
+        return Err(format!("Check failed: {:?}", check_body).into());
     }
 
     // Poll for check status
-    let status_url = build_api_url(&api_info, "/api/core/firmware/upgradestatus");
     loop {
-        let status_response = make_http_request(
-            "GET",
-            &status_url,
-            None,
-            None,
-            Some(30),
-            Some(&api_info.api_key),
-            Some(&api_info.api_secret),
-        )
-        .await?;
+        let status_response = client.get("/api/core/firmware/upgradestatus").await?;
 
         let status_body: Value = status_response
             .json()
@@ -63,34 +47,16 @@ pub async fn check_for_updates(database: State<'_, Database>) -> Result<Value, S
         sleep(Duration::from_secs(2)).await;
     }
 
-    let firmware_status_url = build_api_url(&api_info, "/api/core/firmware/status");
-    let firmware_status_response = make_http_request(
-        "GET",
-        &firmware_status_url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let firmware_status_endpoint = "/api/core/firmware/status";
+    let firmware_status_response = client.get(firmware_status_endpoint).await?;
 
     let firmware_status: Value = firmware_status_response
         .json()
         .await
         .map_err(|e| format!("Failed to parse firmware status: {}", e))?;
 
-    let firmware_info_url = build_api_url(&api_info, "/api/core/firmware/info");
-    let firmware_info_response = make_http_request(
-        "GET",
-        &firmware_info_url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let firmware_info_endpoint = "/api/core/firmware/info";
+    let firmware_info_response = client.get(firmware_info_endpoint).await?;
 
     let firmware_info: Value = firmware_info_response
         .json()
@@ -137,28 +103,17 @@ pub async fn check_for_updates(database: State<'_, Database>) -> Result<Value, S
 
 #[tauri::command]
 pub async fn get_changelog(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     version: String,
-) -> Result<String, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let changelog_url = build_api_url(
-        &api_info,
-        &format!("/api/core/firmware/changelog/{}", version),
-    );
-    let response = make_http_request(
-        "POST",
-        &changelog_url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+) -> Result<String, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let changelog_endpoint = format!("/api/core/firmware/changelog/{}", version);
+    let response = client
+        .post(&changelog_endpoint, serde_json::json!({}))
+        .await?;
 
     let changelog: Value = response
         .json()
@@ -169,23 +124,29 @@ pub async fn get_changelog(
 }
 
 #[tauri::command]
-pub async fn start_update(database: State<'_, Database>) -> Result<String, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let update_url = build_api_url(&api_info, "/api/core/firmware/update");
-    let response = make_http_request(
-        "POST",
-        &update_url,
-        Some(serde_json::json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn start_update(
+    confirmation_token: String,
+    pin: String,
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    confirmations: State<'_, Confirmations>,
+) -> Result<String, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let pin = Zeroizing::new(pin);
+    confirmations.confirm_selected(
+        &database,
+        &confirmation_token,
+        &pin,
+        &DestructiveAction::StartUpdate,
+        profile,
+    )?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client
+        .post("/api/core/firmware/update", serde_json::json!({}))
+        .await?;
 
     let update_response: Value = response
         .json()
@@ -193,25 +154,20 @@ pub async fn start_update(database: State<'_, Database>) -> Result<String, Strin
         .map_err(|e| format!("Failed to parse update response: {}", e))?;
 
     if update_response["status"] != "ok" {
-        return Err(format!("Update failed: {:?}", update_response));
+        return Err(format!("Update failed: {:?}", update_response).into());
     }
 
-    let status_url = build_api_url(&api_info, "/api/core/firmware/upgradestatus");
+    // The new firmware may add or drop features
+    clients.forget_capabilities(client.profile_id());
+
     let start_time = Instant::now();
     let timeout = Duration::from_secs(1800); // 30 minutes timeout
     let mut reboot_detected = false;
 
     while start_time.elapsed() < timeout {
-        match make_http_request(
-            "GET",
-            &status_url,
-            None,
-            None,
-            Some(5),
-            Some(&api_info.api_key),
-            Some(&api_info.api_secret),
-        )
-        .await
+        match client
+            .send(ApiRequest::get("/api/core/firmware/upgradestatus").timeout(5))
+            .await
         {
             Ok(response) => {
                 if reboot_detected {
@@ -250,27 +206,23 @@ pub async fn start_update(database: State<'_, Database>) -> Result<String, Strin
         sleep(Duration::from_secs(10)).await;
     }
 
-    Err("Update timed out or failed to detect system coming back online".to_string())
+    Err("Update timed out or failed to detect system coming back online".into())
 }
 
 #[tauri::command]
-pub async fn get_current_firmware_status(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let firmware_status_url = build_api_url(&api_info, "/api/core/firmware/status");
-    let firmware_status_response = make_http_request(
-        "GET",
-        &firmware_status_url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_current_firmware_status(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+    firmware_status(&client).await
+}
+
+/// Last known firmware status, with the upgrade details flattened for the frontend
+pub async fn firmware_status(client: &OpnsenseClient) -> Result<Value, CommandError> {
+    let firmware_status_endpoint = "/api/core/firmware/status";
+    let firmware_status_response = client.get(firmware_status_endpoint).await?;
 
     let firmware_status: Value = firmware_status_response
         .json()
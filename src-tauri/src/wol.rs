@@ -1,33 +1,55 @@
+use crate::active_profile::SelectedProfile;
+use crate::change_journal::{Change, Module};
 use crate::db::Database;
-use crate::http_client::{make_http_request, make_http_request_with_form_data};
+use crate::error::CommandError;
+use crate::http_client::{ApiError, ApiRequest, ClientManager, OpnsenseClient};
+use crate::local_users::Role;
+use crate::search_grid::{self, SearchPage, SearchQuery, SortOrder};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use tauri::State;
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WolHost {
+    pub uuid: String,
+    #[serde(default)]
+    pub interface: String,
+    #[serde(default)]
+    pub mac: String,
+    #[serde(default)]
+    pub descr: String,
+}
+
 // Check if WoL plugin is installed and API has required permissions
 #[tauri::command]
-pub async fn check_wol_plugin_installed(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+pub async fn check_wol_plugin_installed(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    // No need to probe when the plugin list already says it's missing
+    if let Ok(capabilities) = clients.capabilities(&client).await {
+        if !capabilities.wol {
+            log::info!("WoL plugin is not installed");
+            return Ok(json!({
+                "installed": false,
+                "permission_error": false,
+                "error": null
+            }));
+        }
+    }
 
     // Try to access the WoL API endpoint
-    let url = format!("{}:{}/api/wol/wol/getwake", api_info.api_url, api_info.port);
+    let endpoint = "/api/wol/wol/getwake";
 
     // Log more detailed information about the request
-    log::info!("Checking if WoL plugin is installed at: {}", url);
-
-    match make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(10), // Short timeout
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await
-    {
+    log::info!("Checking if WoL plugin is installed at: {}", endpoint);
+
+    match client.send(ApiRequest::get(endpoint).timeout(10)).await {
         Ok(_) => {
             log::info!("WoL plugin is installed - API endpoint found with correct permissions");
             Ok(json!({
@@ -35,36 +57,40 @@ pub async fn check_wol_plugin_installed(database: State<'_, Database>) -> Result
                 "permission_error": false,
                 "error": null
             }))
-        },
+        }
         Err(e) => {
             // Three different cases:
             // 1. 404 - Plugin is not installed
             // 2. 403 - Plugin is installed but permissions are wrong
             // 3. Other errors - Network or server issues
 
-            if e.contains("404") || e.contains("API endpoint not found") {
-                log::info!("WoL plugin is not installed - API endpoint returned 404");
-                Ok(json!({
-                    "installed": false,
-                    "permission_error": false,
-                    "error": null
-                }))
-            } else if e.contains("403") || e.contains("Permission denied") {
-                // This likely means the plugin is installed, but API key doesn't have permission
-                log::warn!("WoL plugin permission error: {}", e);
-                Ok(json!({
-                    "installed": true,
-                    "permission_error": true,
-                    "error": e
-                }))
-            } else {
-                // For other errors, we can't be sure if the plugin is installed or not
-                log::warn!("Error checking if WoL plugin is installed: {}", e);
-                Ok(json!({
-                    "installed": false,
-                    "permission_error": false,
-                    "error": e
-                }))
+            match e {
+                ApiError::NotFound { .. } => {
+                    log::info!("WoL plugin is not installed - API endpoint returned 404");
+                    Ok(json!({
+                        "installed": false,
+                        "permission_error": false,
+                        "error": null
+                    }))
+                }
+                ApiError::Permission => {
+                    // This likely means the plugin is installed, but API key doesn't have permission
+                    log::warn!("WoL plugin permission error: {}", e);
+                    Ok(json!({
+                        "installed": true,
+                        "permission_error": true,
+                        "error": e.to_string()
+                    }))
+                }
+                _ => {
+                    // For other errors, we can't be sure if the plugin is installed or not
+                    log::warn!("Error checking if WoL plugin is installed: {}", e);
+                    Ok(json!({
+                        "installed": false,
+                        "permission_error": false,
+                        "error": e.to_string()
+                    }))
+                }
             }
         }
     }
@@ -72,24 +98,14 @@ pub async fn check_wol_plugin_installed(database: State<'_, Database>) -> Result
 
 // Get available interfaces for WoL
 #[tauri::command]
-pub async fn get_wol_interfaces(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!("{}:{}/api/wol/wol/getwake", api_info.api_url, api_info.port);
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+pub async fn get_wol_interfaces(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/wol/wol/getwake").await?;
 
     let result = response
         .json::<Value>()
@@ -103,90 +119,66 @@ pub async fn get_wol_interfaces(database: State<'_, Database>) -> Result<Value,
         }
     }
 
-    Err("Interface list not found".to_string())
+    Err("Interface list not found".into())
 }
 
 // Get configured WoL hosts
 #[tauri::command]
-pub async fn search_wol_hosts(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/wol/wol/searchHost",
-        api_info.api_url, api_info.port
-    );
-
-    let payload = json!({
-        "current": 1,
-        "rowCount": -1,
-        "sort": {},
-        "searchPhrase": ""
-    });
-
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-
-    response
-        .json::<Value>()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+pub async fn search_wol_hosts(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    current_page: u32,
+    row_count: u32,
+    sort: Option<BTreeMap<String, SortOrder>>,
+    search_phrase: String,
+) -> Result<SearchPage<WolHost>, CommandError> {
+    let client = clients.client(&database, profile).await?;
+
+    let query = SearchQuery::new()
+        .page(current_page)
+        .rows(row_count)
+        .sorted_by(sort.unwrap_or_default())
+        .search(search_phrase);
+
+    Ok(search_grid::search(&client, "/api/wol/wol/searchHost", &query).await?)
 }
 
 // Get ARP table devices for dropdown selection
 #[tauri::command]
-pub async fn get_arp_devices(database: State<'_, Database>) -> Result<Value, String> {
+pub async fn get_arp_devices(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
     // We'll use the existing devices API endpoint to get the ARP table
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/diagnostics/interface/getArp",
-        api_info.api_url, api_info.port
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let client = clients.client(&database, profile).await?;
+
+    let response = client.get("/api/diagnostics/interface/getArp").await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
 
 // Wake a device by UUID from saved devices
 #[tauri::command]
-pub async fn wake_device(database: State<'_, Database>, uuid: String) -> Result<Value, String> {
+pub async fn wake_device(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
     // Add debug logs for troubleshooting
     log::info!("wake_device called with UUID: {}", uuid);
-    
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
 
-    let url = format!("{}:{}/api/wol/wol/set", api_info.api_url, api_info.port);
-    log::info!("Wake-on-LAN URL: {}", url);
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = "/api/wol/wol/set";
+    log::info!("Wake-on-LAN URL: {}", endpoint);
 
     // The OPNsense WoL API expects form-urlencoded data for saved devices
     // Format: uuid=value (not JSON wrapped)
@@ -194,23 +186,23 @@ pub async fn wake_device(database: State<'_, Database>, uuid: String) -> Result<
     log::info!("Wake-on-LAN request payload: {}", form_data);
 
     // Use the form data specific HTTP request method
-    let response = make_http_request_with_form_data(
-        "POST",
-        &url,
-        form_data,
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
-    
+    let response = client
+        .send(ApiRequest::new(Method::POST, endpoint).form(form_data))
+        .await?;
+
     log::info!("Wake-on-LAN response status: {}", response.status());
 
     // Clone the response so we can both log it and return it
-    let response_text = response.text().await.map_err(|e| format!("Failed to get response text: {}", e))?;
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to get response text: {}", e))?;
     log::info!("Wake-on-LAN response body: {}", response_text);
-    
+
+    Change::new(Module::Wol, "wake")
+        .target(&uuid)
+        .record(&database, &client);
+
     // Parse the response text into JSON
     match serde_json::from_str::<Value>(&response_text) {
         Ok(json_value) => Ok(json_value),
@@ -225,17 +217,16 @@ pub async fn wake_device(database: State<'_, Database>, uuid: String) -> Result<
 // Send WoL to a MAC address directly (from dropdown selection)
 #[tauri::command]
 pub async fn wake_mac_address(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     interface: String,
     mac: String,
     description: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!("{}:{}/api/wol/wol/set", api_info.api_url, api_info.port);
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     // Create a temporary configuration for this wake request
     let payload = json!({
@@ -246,40 +237,33 @@ pub async fn wake_mac_address(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post("/api/wol/wol/set", payload.clone()).await?;
 
-    response
+    let result = response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    Change::new(Module::Wol, "wake")
+        .after(Some(payload))
+        .record(&database, &client);
+
+    Ok(result)
 }
 
 // Add a new WoL host
 #[tauri::command]
 pub async fn add_wol_host(
+    profile: SelectedProfile,
     database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
     interface: String,
     mac: String,
     description: String,
-) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
-
-    let url = format!(
-        "{}:{}/api/wol/wol/addHost/",
-        api_info.api_url, api_info.port
-    );
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     let payload = json!({
         "host": {
@@ -289,47 +273,49 @@ pub async fn add_wol_host(
         }
     });
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client.post("/api/wol/wol/addHost/", payload).await?;
 
-    response
+    let result = response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    if result["result"].as_str() == Some("saved") {
+        let mut change = Change::new(Module::Wol, "add");
+        if let Some(uuid) = result["uuid"].as_str() {
+            change = change
+                .target(uuid)
+                .after(get_host(&client, uuid).await.ok());
+        }
+        change.record(&database, &client);
+    }
+
+    Ok(result)
 }
 
 // Delete a WoL host
 #[tauri::command]
-pub async fn delete_wol_host(database: State<'_, Database>, uuid: String) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+pub async fn delete_wol_host(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+    uuid: String,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Operator)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
+
+    let endpoint = format!("/api/wol/wol/delHost/{}", uuid);
 
-    let url = format!(
-        "{}:{}/api/wol/wol/delHost/{}",
-        api_info.api_url, api_info.port, uuid
-    );
+    let before = get_host(&client, &uuid).await.ok();
 
     // Make the request, but we don't need the response body
-    let _response = make_http_request(
-        "POST",
-        &url,
-        Some(json!({})),
-        None,
-        Some(30),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let _response = client.post(&endpoint, json!({})).await?;
+
+    Change::new(Module::Wol, "delete")
+        .target(&uuid)
+        .before(before)
+        .record(&database, &client);
 
     // The delete endpoint returns an empty response when successful
     Ok(json!({"status": "OK"}))
@@ -337,48 +323,53 @@ pub async fn delete_wol_host(database: State<'_, Database>, uuid: String) -> Res
 
 // Start installation of WoL plugin
 #[tauri::command]
-pub async fn install_wol_plugin(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+pub async fn install_wol_plugin(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    database.require_role(Role::Admin)?;
+    profile.require_writable(&database)?;
+    let client = clients.client(&database, profile).await?;
 
     // The correct endpoint includes the package name in the URL
-    let url = format!(
-        "{}:{}/api/core/firmware/install/os-wol",
-        api_info.api_url, api_info.port
-    );
-
     // Send an empty JSON object as the payload
     let payload = json!({});
 
-    let response = make_http_request(
-        "POST",
-        &url,
-        Some(payload),
-        None,
-        Some(10), // Short timeout for just starting the installation
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let response = client
+        .send(ApiRequest::post("/api/core/firmware/install/os-wol", payload).timeout(10))
+        .await?;
 
     let result = response
         .json::<Value>()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
+    Change::new(Module::Wol, "install_plugin").record(&database, &client);
+
     // Return the message UUID for status checking
     Ok(result)
 }
 
+async fn get_host(client: &OpnsenseClient, uuid: &str) -> Result<Value, CommandError> {
+    let endpoint = format!("/api/wol/wol/getHost/{}", uuid);
+
+    let response = client.get(&endpoint).await?;
+
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
+}
+
 // Check the status of a plugin installation
 #[tauri::command]
-pub async fn check_install_status(database: State<'_, Database>) -> Result<Value, String> {
-    let api_info = database
-        .get_default_api_info()
-        .map_err(|e| format!("Failed to get API info: {}", e))?
-        .ok_or_else(|| "API info not found".to_string())?;
+pub async fn check_install_status(
+    profile: SelectedProfile,
+    database: State<'_, Database>,
+    clients: State<'_, ClientManager>,
+) -> Result<Value, CommandError> {
+    let client = clients.client(&database, profile).await?;
 
     // Add a timestamp parameter to prevent caching
     let timestamp = std::time::SystemTime::now()
@@ -386,24 +377,12 @@ pub async fn check_install_status(database: State<'_, Database>) -> Result<Value
         .unwrap_or_default()
         .as_millis();
 
-    let url = format!(
-        "{}:{}/api/core/firmware/upgradestatus?v={}",
-        api_info.api_url, api_info.port, timestamp
-    );
-
-    let response = make_http_request(
-        "GET",
-        &url,
-        None,
-        None,
-        Some(10),
-        Some(&api_info.api_key),
-        Some(&api_info.api_secret),
-    )
-    .await?;
+    let endpoint = format!("/api/core/firmware/upgradestatus?v={}", timestamp);
+
+    let response = client.send(ApiRequest::get(&endpoint).timeout(10)).await?;
 
     response
         .json::<Value>()
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))
+        .map_err(|e| format!("Failed to parse response: {}", e).into())
 }
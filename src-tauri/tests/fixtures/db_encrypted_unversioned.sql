@@ -0,0 +1,38 @@
+-- app.db from a release with encrypted credentials under the SHA-256 PIN key, before
+-- certificate pinning, rate limits and the audit log, and with no user_version
+CREATE TABLE first_run (
+    id INTEGER PRIMARY KEY,
+    has_run BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO first_run (id, has_run) VALUES (1, 1);
+
+CREATE TABLE app_settings (
+    id INTEGER PRIMARY KEY,
+    password_hash TEXT NOT NULL,
+    pin_salt TEXT NOT NULL DEFAULT ''
+);
+INSERT INTO app_settings (id, password_hash) VALUES (1, '$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHRzYWx0$aGFzaGhhc2hoYXNoaGFzaA');
+
+CREATE TABLE dashboard_preferences (
+    id INTEGER PRIMARY KEY,
+    profile_id INTEGER NOT NULL,
+    widget_key TEXT NOT NULL,
+    visible BOOLEAN NOT NULL DEFAULT 1,
+    position INTEGER NOT NULL,
+    FOREIGN KEY(profile_id) REFERENCES api_info(id)
+);
+
+CREATE TABLE api_info (
+    id INTEGER PRIMARY KEY,
+    profile_name TEXT NOT NULL UNIQUE,
+    encrypted_api_key BLOB NOT NULL,
+    api_key_nonce BLOB NOT NULL,
+    encrypted_api_secret BLOB NOT NULL,
+    api_secret_nonce BLOB NOT NULL,
+    api_url TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    is_default BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO api_info (id, profile_name, encrypted_api_key, api_key_nonce, encrypted_api_secret, api_secret_nonce, api_url, port, is_default)
+VALUES (1, 'office', X'00112233', X'070707070707070707070707', X'44556677', X'070707070707070707070707', 'https://10.0.0.1', 8443, 1);
+INSERT INTO dashboard_preferences (profile_id, widget_key, visible, position) VALUES (1, 'traffic', 1, 0);
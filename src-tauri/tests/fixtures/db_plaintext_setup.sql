@@ -0,0 +1,46 @@
+-- app.db as left by a release that finished first-run setup but was never unlocked:
+-- plaintext api_info, the staged encrypted table, no PIN salt and no user_version
+CREATE TABLE first_run (
+    id INTEGER PRIMARY KEY,
+    has_run BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO first_run (id, has_run) VALUES (1, 1);
+
+CREATE TABLE app_settings (
+    id INTEGER PRIMARY KEY,
+    password_hash TEXT NOT NULL
+);
+INSERT INTO app_settings (id, password_hash) VALUES (1, '$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHRzYWx0$aGFzaGhhc2hoYXNoaGFzaA');
+
+CREATE TABLE dashboard_preferences (
+    id INTEGER PRIMARY KEY,
+    profile_id INTEGER NOT NULL,
+    widget_key TEXT NOT NULL,
+    visible BOOLEAN NOT NULL DEFAULT 1,
+    position INTEGER NOT NULL,
+    FOREIGN KEY(profile_id) REFERENCES api_info(id)
+);
+
+CREATE TABLE api_info (
+    id INTEGER PRIMARY KEY,
+    profile_name TEXT NOT NULL UNIQUE,
+    api_key TEXT NOT NULL,
+    api_secret TEXT NOT NULL,
+    api_url TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    is_default BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO api_info (id, profile_name, api_key, api_secret, api_url, port, is_default)
+VALUES (1, 'home', 'home-key', 'home-secret', 'https://192.168.1.1', 443, 1);
+
+CREATE TABLE api_info_new (
+    id INTEGER PRIMARY KEY,
+    profile_name TEXT NOT NULL UNIQUE,
+    encrypted_api_key BLOB NOT NULL,
+    api_key_nonce BLOB NOT NULL,
+    encrypted_api_secret BLOB NOT NULL,
+    api_secret_nonce BLOB NOT NULL,
+    api_url TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    is_default BOOLEAN NOT NULL DEFAULT 0
+);
@@ -0,0 +1,14 @@
+import { writable } from 'svelte/store';
+import { errorKind } from '$lib/utils/commandError';
+
+/** Set when a command failed because the firewall presented a different certificate */
+export const certificateChanged = writable(false);
+
+/** Flags `certificate_changed` errors so the layout can offer to re-trust the firewall */
+export function reportCertificateChange(error: unknown): boolean {
+  if (errorKind(error) !== 'certificate_changed') {
+    return false;
+  }
+  certificateChanged.set(true);
+  return true;
+}
@@ -0,0 +1,27 @@
+/** Shape every Tauri command rejects with */
+export interface CommandError {
+  kind: string;
+  message: string;
+}
+
+export function isCommandError(error: unknown): error is CommandError {
+  return (
+    typeof error === "object" &&
+    error !== null &&
+    typeof (error as CommandError).kind === "string" &&
+    typeof (error as CommandError).message === "string"
+  );
+}
+
+/** Text to show for anything caught around an `invoke` */
+export function errorMessage(error: unknown): string {
+  if (isCommandError(error) || error instanceof Error) {
+    return error.message;
+  }
+  return String(error);
+}
+
+/** `kind` of a command error, e.g. `not_found` or `certificate_changed` */
+export function errorKind(error: unknown): string | undefined {
+  return isCommandError(error) ? error.kind : undefined;
+}